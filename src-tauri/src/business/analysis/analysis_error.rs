@@ -4,8 +4,10 @@ use crate::business::game_engine::engine_error::EngineError;
 pub enum AnalysisError {
     NoCardToPlay,
     AnalysisFinished,
+    Timeout,
     Engine(EngineError),
     RustError(String),
+    InvalidNetwork(String),
     Other(String),
 }
 
@@ -24,7 +26,11 @@ impl std::fmt::Display for AnalysisError {
             AnalysisError::AnalysisFinished => {
                 write!(f, "Analysis already finished",)
             }
+            AnalysisError::Timeout => write!(f, "Analysis ran out of time"),
             AnalysisError::RustError(arg) => write!(f, "Rust error: {}", arg),
+            AnalysisError::InvalidNetwork(arg) => {
+                write!(f, "Invalid network or training data: {}", arg)
+            }
         }
     }
 }
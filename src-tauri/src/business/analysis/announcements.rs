@@ -0,0 +1,222 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use rayon::prelude::*;
+
+use crate::business::analysis::{
+    analysis_error::AnalysisError, double_dummy::takes_every_trick, players::expert::Expert,
+    playout_policy::PlayoutPolicy, reward::camp_won, simulate_random_playout::simulate_playout,
+};
+use crate::business::game_engine::{
+    handfuls::Handfuls,
+    seed::{derive_seed, rng_from_seed},
+};
+use crate::business::{Card, Color, KnownGameState};
+
+const HANDFULS: [Handfuls; 3] = [Handfuls::Triple, Handfuls::Double, Handfuls::Simple];
+// Marque given away by showing trumps, which the playouts cannot see: the
+// opponents know where the trumps are and count them down
+const INFORMATION_COST: f64 = 5.0;
+// Below this many trumps, the taker is not worth a chelem search
+const CHELEM_MIN_TRUMPS: usize = 11;
+// Announcing turns a made chelem from 200 into 400 and a failed one from 0
+// into -200, which pays above one chance in two. The search sees every card
+// and overestimates the taker, hence the margin.
+const CHELEM_THRESHOLD: f64 = 0.75;
+
+// Decides on handfuls and chelem by playing sampled deals
+#[derive(Clone)]
+pub struct SimulatedAnnouncements {
+    pub samples: usize,
+    // Positions the chelem search may look at in each sample, a sample whose
+    // search runs out counts as a failed chelem
+    pub node_budget: u64,
+    pub playout: Arc<dyn PlayoutPolicy>,
+    seed: u64,
+}
+
+impl SimulatedAnnouncements {
+    pub fn new(samples: usize, seed: u64) -> Self {
+        Self {
+            samples,
+            node_budget: 20_000,
+            playout: Arc::new(Expert),
+            seed,
+        }
+    }
+
+    pub fn with_node_budget(mut self, node_budget: u64) -> Self {
+        self.node_budget = node_budget;
+        self
+    }
+
+    pub fn with_playout(mut self, playout: impl PlayoutPolicy + 'static) -> Self {
+        self.playout = Arc::new(playout);
+        self
+    }
+
+    // Share of the sampled deals where the taker, leading, takes every trick
+    pub fn chelem_probability(&self, known: &KnownGameState) -> Result<f64, AnalysisError> {
+        let trumps = known
+            .player_state
+            .hand
+            .iter()
+            .filter(|card| card.color == Color::Trump)
+            .count();
+        if known.shared_state.taker != Some(known.player_index)
+            || trumps < CHELEM_MIN_TRUMPS
+            || self.samples == 0
+        {
+            return Ok(0.0);
+        }
+        let seed = derive_seed(self.seed, known.player_index as u64);
+        let made: Vec<bool> = (0..self.samples)
+            .into_par_iter()
+            .map(|sample| {
+                let mut rng = rng_from_seed(derive_seed(seed, sample as u64));
+                let mut deal = known
+                    .possible_random_full_state_v2_with_rng(&mut rng)
+                    .map_err(AnalysisError::Engine)?;
+                deal.shared_state
+                    .declare_chelem(known.player_index)
+                    .map_err(AnalysisError::Engine)?;
+                Ok(takes_every_trick(&deal, self.node_budget)? == Some(true))
+            })
+            .collect::<Result<_, AnalysisError>>()?;
+        Ok(made.iter().filter(|made| **made).count() as f64 / made.len() as f64)
+    }
+
+    pub fn declare_chelem(&self, known: &KnownGameState) -> Result<bool, AnalysisError> {
+        Ok(self.chelem_probability(known)? >= CHELEM_THRESHOLD)
+    }
+
+    // Expected marque the handful brings to the camp of the player: its bonus
+    // goes to the camp winning the contract
+    pub fn handful_value(
+        &self,
+        known: &KnownGameState,
+        cards: &HashSet<Card>,
+    ) -> Result<f64, AnalysisError> {
+        let handful = Handfuls::from_size(cards.len())
+            .ok_or_else(|| AnalysisError::Other(format!("No handful of {} cards", cards.len())))?;
+        let player = known.player_index;
+        let seed = derive_seed(self.seed, player as u64);
+        let won: Vec<bool> = (0..self.samples)
+            .into_par_iter()
+            .map(|sample| {
+                let mut rng = rng_from_seed(derive_seed(seed, sample as u64));
+                let mut deal = known
+                    .possible_random_full_state_v2_with_rng(&mut rng)
+                    .map_err(AnalysisError::Engine)?;
+                deal.declare_handful(player, cards.clone())
+                    .map_err(AnalysisError::Engine)?;
+                simulate_playout(&mut deal, self.playout.as_ref(), &mut rng)?;
+                camp_won(&deal, player)
+            })
+            .collect::<Result<_, AnalysisError>>()?;
+        if won.is_empty() {
+            return Ok(-INFORMATION_COST);
+        }
+        let win_probability = won.iter().filter(|won| **won).count() as f64 / won.len() as f64;
+        Ok(handful.points() as f64 * (2.0 * win_probability - 1.0) - INFORMATION_COST)
+    }
+
+    // The biggest handful the hand allows, when it is expected to pay
+    pub fn declare_handful(
+        &self,
+        known: &KnownGameState,
+    ) -> Result<Option<HashSet<Card>>, AnalysisError> {
+        let Some(cards) = handful_to_show(&known.player_state.hand) else {
+            return Ok(None);
+        };
+        if self.handful_value(known, &cards)? > 0.0 {
+            Ok(Some(cards))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+// The biggest handful of the hand, showing the smallest trumps so that the
+// Petit and the 21 stay hidden when possible. The Excuse only completes a
+// handful the trumps are one short of.
+pub fn handful_to_show(hand: &HashSet<Card>) -> Option<HashSet<Card>> {
+    let mut trumps: Vec<Card> = hand
+        .iter()
+        .filter(|card| card.color == Color::Trump)
+        .copied()
+        .collect();
+    trumps.sort_by_key(|card| (card.is_oudler(), card.value));
+    let excuse = hand
+        .iter()
+        .find(|card| card.color == Color::Excuse)
+        .copied();
+    let handful = HANDFULS
+        .into_iter()
+        .find(|handful| trumps.len() + excuse.is_some() as usize >= handful.trumps_required())?;
+    let mut shown: HashSet<Card> = trumps.into_iter().take(handful.trumps_required()).collect();
+    if shown.len() < handful.trumps_required() {
+        shown.extend(excuse);
+    }
+    Some(shown)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::business::{game_engine::game_state::GameState, GameType};
+
+    // Player 0 takes a garde sans with the given cards, the others get the rest
+    fn taker_state(first_hand: Vec<Card>) -> GameState {
+        let mut state = GameState::with_first_hand(first_hand);
+        state
+            .shared_state
+            .bid(0, Some(GameType::GardeSans { chelem: false }))
+            .unwrap();
+        state
+    }
+
+    #[test]
+    fn test_handful_hides_the_oudlers() {
+        let mut hand: HashSet<Card> = (1..=9)
+            .map(|value| Card::new(Color::Trump, value).unwrap())
+            .collect();
+        assert_eq!(handful_to_show(&hand), None);
+        hand.insert(Card::from_str("EX").unwrap());
+        let shown = handful_to_show(&hand).unwrap();
+        assert_eq!(shown.len(), 10);
+        assert!(shown.contains(&Card::from_str("EX").unwrap()));
+
+        // Fourteen trumps make a double handful, the 21 stays hidden
+        let hand: HashSet<Card> = (1..=13)
+            .chain([21])
+            .map(|value| Card::new(Color::Trump, value).unwrap())
+            .collect();
+        let shown = handful_to_show(&hand).unwrap();
+        assert_eq!(shown.len(), 13);
+        assert!(shown.contains(&Card::from_str("T1").unwrap()));
+        assert!(!shown.contains(&Card::from_str("T21").unwrap()));
+    }
+
+    #[test]
+    fn test_announcements_follow_the_hand() {
+        let announcements = SimulatedAnnouncements::new(8, 43);
+        let trumps: Vec<Card> = (4..=21)
+            .map(|value| Card::new(Color::Trump, value).unwrap())
+            .collect();
+        let state = taker_state(trumps);
+        let known = KnownGameState::from_omniscient(&state, 0);
+        assert_eq!(announcements.chelem_probability(&known).unwrap(), 1.0);
+        assert!(announcements.declare_chelem(&known).unwrap());
+        let shown = announcements.declare_handful(&known).unwrap().unwrap();
+        assert_eq!(shown.len(), 15);
+        assert!(state.clone().declare_handful(0, shown).is_ok());
+
+        // A defender facing those trumps keeps quiet, and has no chelem to claim
+        let defender = KnownGameState::from_omniscient(&state, 1);
+        assert!(!announcements.declare_chelem(&defender).unwrap());
+        assert_eq!(announcements.declare_handful(&defender).unwrap(), None);
+    }
+}
@@ -0,0 +1,267 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use rand::Rng;
+use rayon::prelude::*;
+use serde::Serialize;
+
+use crate::business::analysis::{
+    analysis_error::AnalysisError,
+    evaluate_hand::evaluate_hand,
+    players::expert::{conventional_aside, Expert},
+    playout_policy::PlayoutPolicy,
+    simulate_random_playout::simulate_playout,
+};
+use crate::business::game_engine::{
+    game_state::GameState,
+    score::marque,
+    seed::{derive_seed, rng_from_seed, TarotRng},
+};
+use crate::business::{Card, GameType, KnownGameState, Player};
+
+const CONTRACTS: [GameType; 4] = [
+    GameType::Petit { chelem: false },
+    GameType::Garde { chelem: false },
+    GameType::GardeSans { chelem: false },
+    GameType::GardeContre { chelem: false },
+];
+// Weight, in simulated deals, of the point count of `evaluate_hand`
+const POINT_COUNT_WEIGHT: f64 = 4.0;
+// Deals drawn before settling for one the auction makes unlikely
+const AUCTION_ATTEMPTS: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ContractEstimate {
+    pub contract: GameType,
+    pub win_probability: f64,
+    // Paid by each defender, negative when the contract is expected to fail
+    pub expected_marque: f64,
+}
+
+// Plays sampled completions of the hand under every contract still open
+#[derive(Clone)]
+pub struct SimulatedBidding {
+    pub samples: usize,
+    pub playout: Arc<dyn PlayoutPolicy>,
+    seed: u64,
+}
+
+impl SimulatedBidding {
+    pub fn new(samples: usize, seed: u64) -> Self {
+        Self {
+            samples,
+            playout: Arc::new(Expert),
+            seed,
+        }
+    }
+
+    pub fn with_playout(mut self, playout: impl PlayoutPolicy + 'static) -> Self {
+        self.playout = Arc::new(playout);
+        self
+    }
+
+    // Contracts above the current bid, from the lowest
+    pub fn estimates(
+        &self,
+        known: &KnownGameState,
+    ) -> Result<Vec<ContractEstimate>, AnalysisError> {
+        let contracts: Vec<GameType> = CONTRACTS
+            .into_iter()
+            .filter(|contract| {
+                known
+                    .shared_state
+                    .game_type
+                    .is_none_or(|current| *contract > current)
+            })
+            .collect();
+        let seed = derive_seed(self.seed, known.player_index as u64);
+        // Every contract is played on the same deals
+        let results: Vec<Vec<(bool, i32)>> = (0..self.samples)
+            .into_par_iter()
+            .map(|sample| {
+                let mut rng = rng_from_seed(derive_seed(seed, sample as u64));
+                let deal = sample_deal(known, &mut rng)?;
+                contracts
+                    .iter()
+                    .map(|contract| {
+                        let mut state = deal.clone();
+                        let playout_seed = rng.random();
+                        play_contract(
+                            &mut state,
+                            known.player_index,
+                            *contract,
+                            self.playout.as_ref(),
+                            &mut rng_from_seed(playout_seed),
+                        )
+                    })
+                    .collect()
+            })
+            .collect::<Result<_, AnalysisError>>()?;
+
+        let counted = evaluate_hand(&known.player_state);
+        let weight = results.len() as f64 + POINT_COUNT_WEIGHT;
+        Ok(contracts
+            .iter()
+            .enumerate()
+            .map(|(index, contract)| {
+                let counted_won = counted.is_some_and(|counted| counted >= *contract);
+                let counted_marque = marque(*contract, counted_won, 0, 0) as f64;
+                let wins = results.iter().filter(|result| result[index].0).count() as f64;
+                let marque: f64 = results.iter().map(|result| result[index].1 as f64).sum();
+                ContractEstimate {
+                    contract: *contract,
+                    win_probability: (wins + POINT_COUNT_WEIGHT * counted_won as u8 as f64)
+                        / weight,
+                    expected_marque: (marque + POINT_COUNT_WEIGHT * counted_marque) / weight,
+                }
+            })
+            .collect())
+    }
+
+    // The contract with the best expected marque, passing when none is
+    // expected to pay
+    pub fn bid(&self, known: &KnownGameState) -> Result<Option<GameType>, AnalysisError> {
+        Ok(self
+            .estimates(known)?
+            .into_iter()
+            .filter(|estimate| estimate.expected_marque > 0.0)
+            .max_by(|a, b| a.expected_marque.total_cmp(&b.expected_marque))
+            .map(|estimate| estimate.contract))
+    }
+}
+
+// A deal matching the hand and the auction so far: players who spoke before
+// had no hand for a higher contract, the taker had one for theirs
+fn sample_deal(known: &KnownGameState, rng: &mut TarotRng) -> Result<GameState, AnalysisError> {
+    let mut deal = known
+        .possible_random_full_state_v2_with_rng(rng)
+        .map_err(AnalysisError::Engine)?;
+    for _ in 1..AUCTION_ATTEMPTS {
+        if fits_auction(&deal, known) {
+            break;
+        }
+        deal = known
+            .possible_random_full_state_v2_with_rng(rng)
+            .map_err(AnalysisError::Engine)?;
+    }
+    Ok(deal)
+}
+
+fn fits_auction(deal: &GameState, known: &KnownGameState) -> bool {
+    let shared_state = &known.shared_state;
+    let current = shared_state
+        .game_type
+        .map(|current| current.with_chelem(false));
+    (1..4)
+        .map(|offset| (shared_state.dealer + offset) % 4)
+        .take_while(|player| *player != known.player_index)
+        .all(|player| {
+            let counted = evaluate_hand(&deal.players_state[player as usize]);
+            if shared_state.taker == Some(player) {
+                counted >= current
+            } else {
+                counted <= current
+            }
+        })
+}
+
+// Whether the contract is made and its marque
+fn play_contract(
+    state: &mut GameState,
+    taker: u8,
+    contract: GameType,
+    playout: &dyn PlayoutPolicy,
+    rng: &mut TarotRng,
+) -> Result<(bool, i32), AnalysisError> {
+    state
+        .shared_state
+        .bid(taker, Some(contract))
+        .map_err(AnalysisError::Engine)?;
+    if contract.kitty_should_be_revealed() {
+        state.take_kitty().map_err(AnalysisError::Engine)?;
+        let aside = conventional_aside(&state.players_state[taker as usize].hand)?;
+        state.discard(aside).map_err(AnalysisError::Engine)?;
+    }
+    simulate_playout(state, playout, rng)?;
+    let score = state.final_score().map_err(AnalysisError::Engine)?;
+    Ok((score.contract_won, score.marque))
+}
+
+// Any player, bidding by simulation
+pub struct SimulationBidder<P: Player> {
+    pub player: P,
+    pub bidding: SimulatedBidding,
+}
+
+impl<P: Player> SimulationBidder<P> {
+    pub fn new(player: P, bidding: SimulatedBidding) -> Self {
+        Self { player, bidding }
+    }
+}
+
+impl<P: Player> Player for SimulationBidder<P> {
+    fn play_a_card(&mut self, game_state: &KnownGameState) -> Result<Card, AnalysisError> {
+        self.player.play_a_card(game_state)
+    }
+
+    fn bid(&self, game_state: &KnownGameState) -> Result<Option<GameType>, AnalysisError> {
+        self.bidding.bid(game_state)
+    }
+
+    fn seed(&self) -> Option<u64> {
+        self.player.seed()
+    }
+
+    fn new_deal(&mut self) {
+        self.player.new_deal()
+    }
+
+    fn chose_aside(&self, game_state: &KnownGameState) -> Result<[Card; 6], AnalysisError> {
+        self.player.chose_aside(game_state)
+    }
+
+    fn declare_chelem(&self, game_state: &KnownGameState) -> Result<bool, AnalysisError> {
+        self.player.declare_chelem(game_state)
+    }
+
+    fn declare_handful(
+        &self,
+        game_state: &KnownGameState,
+    ) -> Result<Option<HashSet<Card>>, AnalysisError> {
+        self.player.declare_handful(game_state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::business::Color;
+
+    fn known_state(first_hand: Vec<Card>) -> KnownGameState {
+        KnownGameState::from_omniscient(&GameState::with_first_hand(first_hand), 0)
+    }
+
+    #[test]
+    fn test_bids_follow_the_hand() {
+        let bidding = SimulatedBidding::new(32, 41);
+        let trumps: Vec<Card> = (4..=21)
+            .map(|value| Card::new(Color::Trump, value).unwrap())
+            .collect();
+        let strong = known_state(trumps);
+        let estimates = bidding.estimates(&strong).unwrap();
+        assert_eq!(estimates.len(), 4);
+        for estimate in &estimates {
+            assert!((0.0..=1.0).contains(&estimate.win_probability));
+        }
+        // The same deals are played, the kitty only changes sides
+        assert!(estimates[3].win_probability <= estimates[2].win_probability);
+        assert!(bidding.bid(&strong).unwrap().is_some());
+
+        let low_cards: Vec<Card> = [Color::Spade, Color::Heart, Color::Diamond, Color::Club]
+            .into_iter()
+            .flat_map(|color| (1..=5).map(move |value| Card::new(color, value).unwrap()))
+            .take(18)
+            .collect();
+        assert_eq!(bidding.bid(&known_state(low_cards)).unwrap(), None);
+    }
+}
@@ -0,0 +1,393 @@
+use rand::Rng;
+use serde::Serialize;
+
+use crate::business::analysis::analysis_error::AnalysisError;
+pub use crate::business::game_engine::deal_sampler::{KITTY, LOCATIONS};
+use crate::business::game_engine::{
+    card::NUMBER_OF_CARDS, game_state::GameState, known_game_state::PlayerConstraint,
+};
+use crate::business::{Card, Color, KnownGameState};
+// Likelihood of an honour, trump, oudler or king, with a player who bid,
+// growing with the contract, and with players who passed
+const TAKER_HONOUR_WEIGHT: f64 = 0.25;
+const PASSED_HONOUR_WEIGHT: f64 = 0.8;
+const FITTING_ROUNDS: usize = 200;
+const FITTING_TOLERANCE: f64 = 1e-9;
+
+// Where each card is likely to be, seen from one player. Certain locations
+// come from the rules (voids, trumps under the one to beat, shown handfuls,
+// the écart), likely ones from the auction. Marginals are fitted so that
+// every card is somewhere and every location holds its number of cards.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CardBeliefs {
+    pub observer: u8,
+    // By card index, zero everywhere for cards already played
+    probabilities: Vec<[f64; LOCATIONS]>,
+    // Likelihoods before fitting, which deals are drawn from
+    weights: Vec<[f64; LOCATIONS]>,
+}
+
+impl CardBeliefs {
+    pub fn infer(known: &KnownGameState) -> Result<Self, AnalysisError> {
+        let observer = known.player_index as usize;
+        let constraints: Vec<PlayerConstraint> = (0..4)
+            .map(|player| known.get_known_constraints(player))
+            .collect::<Result<_, _>>()
+            .map_err(AnalysisError::Engine)?;
+        let left_to_play = known.shared_state.cards_left_to_play();
+        let mut targets = [0.0; LOCATIONS];
+        for (player, constraint) in constraints.iter().enumerate() {
+            if player != observer {
+                targets[player] = constraint.number_cards as f64;
+            }
+        }
+        let held: usize = constraints
+            .iter()
+            .enumerate()
+            .map(|(player, constraint)| {
+                if player == observer {
+                    known.player_state.hand.len()
+                } else {
+                    constraint.number_cards
+                }
+            })
+            .sum();
+        // Nothing is left in the kitty while the taker holds it
+        targets[KITTY] = left_to_play.len().saturating_sub(held) as f64;
+
+        let taker = known.shared_state.taker.map(|taker| taker as usize);
+        let mut probabilities = vec![[0.0; LOCATIONS]; NUMBER_OF_CARDS];
+        let mut hidden: Vec<usize> = vec![];
+        for card in &left_to_play {
+            let row = &mut probabilities[card.index()];
+            if known.player_state.hand.contains(card) {
+                row[observer] = 1.0;
+                continue;
+            }
+            hidden.push(card.index());
+            if let Some(owner) = constraints
+                .iter()
+                .position(|constraint| constraint.known_cards.contains(card))
+            {
+                row[owner] = 1.0;
+                continue;
+            }
+            if known.shared_state.trumps_aside.contains(card) {
+                row[KITTY] = 1.0;
+                continue;
+            }
+            let aside = if known.may_be_set_aside(card) {
+                1.0
+            } else {
+                0.0
+            };
+            if known.kitty.is_some_and(|kitty| kitty.contains(card)) {
+                // The taker set it aside, or took it and may have kept it
+                row[KITTY] = aside;
+                if let Some(taker) = taker.filter(|taker| *taker != observer) {
+                    row[taker] = 1.0;
+                }
+                continue;
+            }
+            let honour = card.color == Color::Trump || card.is_oudler() || card.value == 14;
+            for (player, constraint) in constraints.iter().enumerate() {
+                if player == observer || !may_hold(constraint, card) {
+                    continue;
+                }
+                row[player] = if honour {
+                    honour_weight(known, player)
+                } else {
+                    1.0
+                };
+            }
+            row[KITTY] = aside;
+        }
+        let weights = probabilities.clone();
+        fit(&mut probabilities, &hidden, &targets);
+        Ok(Self {
+            observer: known.player_index,
+            probabilities,
+            weights,
+        })
+    }
+
+    // Probability of the card being at each location, the kitty last
+    pub fn probabilities(&self, card: &Card) -> [f64; LOCATIONS] {
+        self.probabilities[card.index()]
+    }
+
+    pub fn probability(&self, card: &Card, location: usize) -> f64 {
+        self.probabilities[card.index()]
+            .get(location)
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    // Cards of the location, with their probability, from the most likely
+    pub fn likely_cards(&self, location: usize) -> Vec<(Card, f64)> {
+        let mut cards: Vec<(Card, f64)> = Card::all_possibles()
+            .into_iter()
+            .map(|card| (card, self.probability(&card, location)))
+            .filter(|(_, probability)| *probability > 0.0)
+            .collect();
+        cards
+            .sort_by(|(card, a), (other, b)| b.total_cmp(a).then(card.index().cmp(&other.index())));
+        cards
+    }
+
+    // A deal drawn in proportion to the likelihood of every hidden card at its
+    // location
+    pub fn sample_deal<R: Rng + ?Sized>(
+        &self,
+        known: &KnownGameState,
+        rng: &mut R,
+    ) -> Result<GameState, AnalysisError> {
+        known
+            .weighted_random_full_state_with_rng(
+                |card, location| self.weights[card.index()][location],
+                rng,
+            )
+            .map_err(AnalysisError::Engine)
+    }
+
+    pub fn expected_cards(&self, location: usize) -> f64 {
+        self.probabilities
+            .iter()
+            .map(|row| row.get(location).copied().unwrap_or(0.0))
+            .sum()
+    }
+}
+
+// What the bid of the player tells of their honours
+fn honour_weight(known: &KnownGameState, player: usize) -> f64 {
+    let turn = (player + 3 - known.shared_state.dealer as usize) % 4;
+    let bid = match known.auction.get(turn) {
+        Some(bid) => *bid,
+        // Without the auction, only the taker is known to have bid
+        None if known.auction.is_empty() && known.shared_state.taker.is_some() => known
+            .shared_state
+            .game_type
+            .filter(|_| known.shared_state.taker == Some(player as u8)),
+        None => return 1.0,
+    };
+    match bid {
+        Some(contract) => 1.0 + TAKER_HONOUR_WEIGHT * contract.hand_points_multiplier() as f64,
+        None => PASSED_HONOUR_WEIGHT,
+    }
+}
+
+fn may_hold(constraint: &PlayerConstraint, card: &Card) -> bool {
+    !constraint.voided_colors.contains(&card.color)
+        && (card.color != Color::Trump || card.value <= constraint.highest_trump)
+}
+
+// Iterative proportional fitting: rows of hidden cards sum to one, columns to
+// the cards each location still holds unseen
+fn fit(probabilities: &mut [[f64; LOCATIONS]], hidden: &[usize], targets: &[f64; LOCATIONS]) {
+    for _ in 0..FITTING_ROUNDS {
+        for index in hidden {
+            let row = &mut probabilities[*index];
+            let total: f64 = row.iter().sum();
+            if total > 0.0 {
+                row.iter_mut().for_each(|probability| *probability /= total);
+            }
+        }
+        let mut largest_change: f64 = 0.0;
+        for location in 0..LOCATIONS {
+            let total: f64 = hidden
+                .iter()
+                .map(|index| probabilities[*index][location])
+                .sum();
+            if total <= 0.0 {
+                continue;
+            }
+            let scale = targets[location] / total;
+            largest_change = largest_change.max((scale - 1.0).abs());
+            for index in hidden {
+                probabilities[*index][location] *= scale;
+            }
+        }
+        if largest_change < FITTING_TOLERANCE {
+            break;
+        }
+    }
+    // Rows are left summing to one, the columns only approximately
+    for index in hidden {
+        let row = &mut probabilities[*index];
+        let total: f64 = row.iter().sum();
+        if total > 0.0 {
+            row.iter_mut().for_each(|probability| *probability /= total);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::business::{
+        analysis::ecart::legal_asides,
+        game_engine::{seed::TarotRng, trick::Trick},
+        GameType,
+    };
+
+    #[test]
+    fn test_failing_to_overtrump_after_the_leader_wraps_around() {
+        // Seat 3 leads, seat 0 plays a smaller trump: no trump above the 10
+        let mut trick = Trick::new(3);
+        trick.play_card(3, &Card::from_str("T10").unwrap()).unwrap();
+        trick.play_card(0, &Card::from_str("T5").unwrap()).unwrap();
+        assert_eq!(trick.did_not_have_trump_higher(0), Some(10));
+        trick.play_card(1, &Card::from_str("T12").unwrap()).unwrap();
+        assert_eq!(trick.did_not_have_trump_higher(1), None);
+        trick.play_card(2, &Card::from_str("3S").unwrap()).unwrap();
+        assert!(trick.did_not_have_trump(2));
+        assert_eq!(
+            trick.into_played().unwrap().did_not_have_trump_higher(0),
+            Some(10)
+        );
+    }
+
+    #[test]
+    fn test_beliefs_respect_the_deal() {
+        let mut rng = TarotRng::seed_from_u64(44);
+        for _ in 0..5 {
+            let state =
+                GameState::played_at_random(&mut rng, 1, GameType::GardeSans { chelem: false }, 22);
+            for observer in 0..4 {
+                let known = KnownGameState::from_omniscient(&state, observer);
+                let beliefs = CardBeliefs::infer(&known).unwrap();
+                for (player, player_state) in state.players_state.iter().enumerate() {
+                    for card in &player_state.hand {
+                        // The actual location is always possible
+                        assert!(beliefs.probability(card, player) > 0.0);
+                        assert!(
+                            (beliefs.probabilities(card).iter().sum::<f64>() - 1.0).abs() < 1e-9
+                        );
+                    }
+                    let constraint = known.get_known_constraints(player).unwrap();
+                    assert!(
+                        (beliefs.expected_cards(player) - constraint.number_cards as f64).abs()
+                            < 1e-3
+                    );
+                }
+                for card in &state.kitty {
+                    assert!(beliefs.probability(card, KITTY) > 0.0);
+                }
+                assert!((beliefs.expected_cards(KITTY) - 6.0).abs() < 1e-3);
+                let deals = [
+                    beliefs.sample_deal(&known, &mut rng).unwrap(),
+                    known
+                        .possible_random_full_state_v2_with_rng(&mut rng)
+                        .unwrap(),
+                ];
+                for deal in deals {
+                    assert_eq!(
+                        deal.players_state[observer as usize].hand,
+                        known.player_state.hand
+                    );
+                    // Every seat, the last one included, follows its constraints
+                    for (player, player_state) in deal.players_state.iter().enumerate() {
+                        let constraint = known.get_known_constraints(player).unwrap();
+                        assert!(player_state
+                            .hand
+                            .iter()
+                            .all(|card| may_hold(&constraint, card)));
+                    }
+                }
+                for trick in &state.shared_state.played_tricks {
+                    for card in &trick.cards {
+                        assert_eq!(beliefs.probabilities(card), [0.0; LOCATIONS]);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_the_taker_likely_holds_the_honours() {
+        let mut rng = TarotRng::seed_from_u64(44);
+        let mut state = GameState::random_init_with_rng(&mut rng).unwrap();
+        state
+            .shared_state
+            .bid(2, Some(GameType::Garde { chelem: false }))
+            .unwrap();
+        let known = KnownGameState::from_omniscient(&state, 0);
+        let beliefs = CardBeliefs::infer(&known).unwrap();
+        for card in Card::all_possibles() {
+            if known.player_state.hand.contains(&card)
+                || card.value != 14
+                || card.color == Color::Trump
+            {
+                continue;
+            }
+            assert!(beliefs.probability(&card, 2) > beliefs.probability(&card, 1));
+        }
+    }
+
+    #[test]
+    fn test_the_auction_weighs_the_honours() {
+        let mut state = GameState::random_init_with_rng(&mut TarotRng::seed_from_u64(44)).unwrap();
+        state.shared_state.dealer = 3;
+        let bids = [
+            None,
+            Some(GameType::Petit { chelem: false }),
+            Some(GameType::Garde { chelem: false }),
+        ];
+        for (player, bid) in bids.into_iter().enumerate() {
+            state.bid(player as u8, bid).unwrap();
+        }
+        assert!(state.bid(0, None).is_err());
+        let known = KnownGameState::from_omniscient(&state, 3);
+        assert_eq!(known.auction, bids);
+        let beliefs = CardBeliefs::infer(&known).unwrap();
+        let weight = |card: &Card, player| beliefs.weights[card.index()][player];
+        for card in Card::all_possibles() {
+            if known.player_state.hand.contains(&card)
+                || state.kitty.contains(&card)
+                || card.value != 14
+                || card.color == Color::Trump
+            {
+                continue;
+            }
+            // Overcalled, the second player still showed a strong hand
+            assert!(weight(&card, 2) > weight(&card, 1));
+            assert!(weight(&card, 1) > weight(&card, 0));
+        }
+    }
+
+    #[test]
+    fn test_only_discardable_cards_weigh_in_the_ecart() {
+        // Player 0 takes the T17 to T21 and the Excuse from the kitty, and
+        // must set aside three trumps
+        let mut hand: Vec<Card> = (2..=16)
+            .map(|value| Card::new(Color::Trump, value).unwrap())
+            .collect();
+        hand.extend((1..=3).map(|value| Card::new(Color::Spade, value).unwrap()));
+        let mut state = GameState::with_first_hand(hand);
+        for (player, bid) in [Some(GameType::Garde { chelem: false }), None, None, None]
+            .into_iter()
+            .enumerate()
+        {
+            state.bid(player as u8, bid).unwrap();
+        }
+        state.take_kitty().unwrap();
+        let aside = legal_asides(&state.players_state[0].hand)[0];
+        state.discard(aside).unwrap();
+
+        let known = KnownGameState::from_omniscient(&state, 3);
+        let beliefs = CardBeliefs::infer(&known).unwrap();
+        for card in &state.shared_state.trumps_aside {
+            assert_eq!(beliefs.probability(card, KITTY), 1.0);
+        }
+        for notation in ["T17", "T21", "EX", "KS", "KH", "T1"] {
+            let card = Card::from_str(notation).unwrap();
+            assert_eq!(beliefs.probability(&card, KITTY), 0.0, "{}", notation);
+        }
+        assert!(beliefs.probability(&Card::from_str("4S").unwrap(), KITTY) > 0.0);
+        assert!((beliefs.expected_cards(KITTY) - 6.0).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,116 @@
+use std::collections::HashMap;
+
+use crate::business::{
+    analysis::analysis_error::AnalysisError, game_engine::game_state::GameState,
+};
+
+use super::solver_position::{CardSet, SolverPosition};
+
+// Whether the taker takes every trick left whatever the defence plays, the
+// cards being known to everyone. `None` when the search would look at more
+// than `node_budget` positions.
+pub fn takes_every_trick(
+    state: &GameState,
+    node_budget: u64,
+) -> Result<Option<bool>, AnalysisError> {
+    let shared_state = &state.shared_state;
+    if shared_state
+        .played_tricks
+        .iter()
+        .any(|trick| Some(trick.winner) != shared_state.taker)
+    {
+        return Ok(Some(false));
+    }
+    let mut search = ChelemSearch {
+        node_budget,
+        nodes: 0,
+        solved: HashMap::new(),
+    };
+    Ok(search.search(SolverPosition::from_game_state(state)?))
+}
+
+struct ChelemSearch {
+    node_budget: u64,
+    nodes: u64,
+    // Positions between tricks already solved, defenders reach the same ones
+    // by discarding in different orders
+    solved: HashMap<u64, bool>,
+}
+
+impl ChelemSearch {
+    // The taker needs one card that keeps every trick, the defence one that
+    // wins a trick
+    fn search(&mut self, position: SolverPosition) -> Option<bool> {
+        if position.is_finished() {
+            return Some(true);
+        }
+        let taker_to_play = position.next_to_play() == position.taker();
+        if position.is_trick_start() {
+            if taker_to_play && position.taker_holds_master_trumps() {
+                return Some(true);
+            }
+            if let Some(solved) = self.solved.get(&position.hash()) {
+                return Some(*solved);
+            }
+        }
+        self.nodes += 1;
+        if self.nodes > self.node_budget {
+            return None;
+        }
+        let mut result = !taker_to_play;
+        let mut remaining: CardSet = position.distinct_moves();
+        while remaining != 0 {
+            let card = remaining.trailing_zeros() as u8;
+            remaining &= remaining - 1;
+            let mut next = position;
+            // The winner of a trick leads the next one
+            let trick_lost = next.play(card).is_some() && next.next_to_play() != next.taker();
+            let taker_keeps_all = !trick_lost && self.search(next)?;
+            if taker_keeps_all == taker_to_play {
+                result = taker_keeps_all;
+                break;
+            }
+        }
+        if position.is_trick_start() {
+            self.solved.insert(position.hash(), result);
+        }
+        Some(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::business::{Card, Color, GameType};
+
+    // Player 0 takes a garde with the given cards, the others get the rest
+    fn deal(taker: Vec<Card>) -> GameState {
+        let mut state = GameState::with_first_hand(taker);
+        state
+            .shared_state
+            .bid(0, Some(GameType::Garde { chelem: false }))
+            .unwrap();
+        state
+    }
+
+    #[test]
+    fn test_chelem_search() {
+        // Leading with the 18 best trumps, the defence never wins a trick
+        let trumps: Vec<Card> = (4..=21)
+            .map(|value| Card::new(Color::Trump, value).unwrap())
+            .collect();
+        let mut state = deal(trumps);
+        state.shared_state.declare_chelem(0).unwrap();
+        assert_eq!(takes_every_trick(&state, 100_000).unwrap(), Some(true));
+
+        let low_cards: Vec<Card> = [Color::Spade, Color::Heart, Color::Diamond, Color::Club]
+            .into_iter()
+            .flat_map(|color| (1..=5).map(move |value| Card::new(color, value).unwrap()))
+            .take(18)
+            .collect();
+        assert_eq!(
+            takes_every_trick(&deal(low_cards), 100_000).unwrap(),
+            Some(false)
+        );
+    }
+}
@@ -0,0 +1,6 @@
+pub mod chelem;
+pub mod solver;
+pub mod solver_position;
+
+pub use chelem::takes_every_trick;
+pub use solver::{DoubleDummySolver, SolverResult};
@@ -0,0 +1,350 @@
+use std::time::Instant;
+
+use crate::business::{
+    analysis::{
+        analysis_error::AnalysisError, search_control::CancellationToken,
+        transposition_table::TranspositionTable,
+    },
+    game_engine::game_state::GameState,
+    Card,
+};
+
+use super::solver_position::{beats, card_points, strength, to_card, CardSet, SolverPosition};
+
+const DEFAULT_CAPACITY: usize = 1 << 20;
+// Number of nodes between two looks at the clock and the cancellation
+const DEADLINE_CHECK_INTERVAL: u64 = 1 << 12;
+
+// Bounds on the points the taker still gets from a position between tricks
+#[derive(Debug, Clone, Copy)]
+struct Bounds {
+    lower: i32,
+    upper: i32,
+    best_card: Option<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SolverResult {
+    pub best_card: Card,
+    // Final card points of the taker, tricks won and écart included
+    pub taker_points: usize,
+    pub nodes: u64,
+}
+
+pub struct DoubleDummySolver {
+    table: TranspositionTable<Bounds>,
+    nodes: u64,
+    deadline: Option<Instant>,
+    cancellation: Option<CancellationToken>,
+    aborted: bool,
+}
+
+impl Default for DoubleDummySolver {
+    fn default() -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY)
+    }
+}
+
+impl DoubleDummySolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            table: TranspositionTable::with_capacity(capacity),
+            nodes: 0,
+            deadline: None,
+            cancellation: None,
+            aborted: false,
+        }
+    }
+
+    // Searches past the deadline or once cancelled give up with a timeout
+    pub fn set_limits(
+        &mut self,
+        deadline: Option<Instant>,
+        cancellation: Option<CancellationToken>,
+    ) {
+        self.deadline = deadline;
+        self.cancellation = cancellation;
+    }
+
+    pub fn nodes(&self) -> u64 {
+        self.nodes
+    }
+
+    // Entries stay valid between calls, positions of the same deal share them
+    pub fn clear(&mut self) {
+        self.table.clear();
+    }
+
+    pub fn solve(&mut self, state: &GameState) -> Result<SolverResult, AnalysisError> {
+        let position = SolverPosition::from_game_state(state)?;
+        self.aborted = false;
+        let start_nodes = self.nodes;
+        let remaining = position.remaining_points();
+        let value = self.bisect(remaining, |solver, alpha, beta| {
+            solver.search(&position, alpha, beta)
+        });
+        let maximizing = position.next_to_play() == position.taker();
+        let mut best_card = None;
+        for card in self.ordered_moves(&position) {
+            let reaches_value = if maximizing {
+                self.child_value(&position, card, value - 1, value) >= value
+            } else {
+                self.child_value(&position, card, value, value + 1) <= value
+            };
+            if reaches_value {
+                best_card = Some(card);
+                break;
+            }
+        }
+        if self.aborted {
+            return Err(AnalysisError::Timeout);
+        }
+        let best_card = best_card.ok_or(AnalysisError::NoCardToPlay)?;
+        Ok(SolverResult {
+            best_card: to_card(best_card)?,
+            taker_points: already_won(state) + value as usize,
+            nodes: self.nodes - start_nodes,
+        })
+    }
+
+    // Exact final taker points for every legal card, sorted by card
+    pub fn evaluate_moves(
+        &mut self,
+        state: &GameState,
+    ) -> Result<Vec<(Card, usize)>, AnalysisError> {
+        let position = SolverPosition::from_game_state(state)?;
+        self.aborted = false;
+        let remaining = position.remaining_points();
+        let mut result = Vec::new();
+        for card in self.ordered_moves(&position) {
+            let value = self.bisect(remaining, |solver, alpha, beta| {
+                solver.child_value(&position, card, alpha, beta)
+            });
+            if self.aborted {
+                return Err(AnalysisError::Timeout);
+            }
+            let mut equivalent = position.equivalent_moves(card);
+            while equivalent != 0 {
+                let index = equivalent.trailing_zeros() as u8;
+                equivalent &= equivalent - 1;
+                result.push((to_card(index)?, already_won(state) + value as usize));
+            }
+        }
+        result.sort_by_key(|(card, _)| card.index());
+        Ok(result)
+    }
+
+    // Null window searches are much cheaper than a full window one, the exact
+    // value is found by narrowing its bounds with them
+    fn bisect(&mut self, upper: i32, mut probe: impl FnMut(&mut Self, i32, i32) -> i32) -> i32 {
+        let (mut lower, mut upper) = (0, upper);
+        while lower < upper && !self.aborted {
+            let threshold = (lower + upper + 1) / 2;
+            let value = probe(self, threshold - 1, threshold);
+            if value >= threshold {
+                lower = value;
+            } else {
+                upper = value;
+            }
+        }
+        lower
+    }
+
+    fn child_value(&mut self, position: &SolverPosition, card: u8, alpha: i32, beta: i32) -> i32 {
+        let mut child = *position;
+        let trick_points = child.play(card).unwrap_or(0);
+        trick_points + self.search(&child, alpha - trick_points, beta - trick_points)
+    }
+
+    // Points the taker still gets with best play from both camps
+    fn search(&mut self, position: &SolverPosition, mut alpha: i32, mut beta: i32) -> i32 {
+        self.nodes += 1;
+        if self.nodes.is_multiple_of(DEADLINE_CHECK_INTERVAL)
+            && (self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+                || self
+                    .cancellation
+                    .as_ref()
+                    .is_some_and(CancellationToken::is_cancelled))
+        {
+            self.aborted = true;
+        }
+        if self.aborted {
+            // The result is thrown away, it only needs to unwind quickly
+            return 0;
+        }
+        if position.is_trick_start() {
+            if position.is_finished() {
+                return 0;
+            }
+            let remaining = position.remaining_points();
+            let bounds = self.table.get(position.hash()).copied().unwrap_or(Bounds {
+                lower: 0,
+                upper: remaining,
+                best_card: None,
+            });
+            if bounds.lower >= beta || bounds.lower == bounds.upper {
+                return bounds.lower;
+            }
+            if bounds.upper <= alpha {
+                return bounds.upper;
+            }
+            alpha = alpha.max(bounds.lower);
+            beta = beta.min(bounds.upper);
+        }
+        let window = (alpha, beta);
+        let maximizing = position.next_to_play() == position.taker();
+        let mut best = if maximizing { i32::MIN } else { i32::MAX };
+        let mut best_card = None;
+        for card in self.ordered_moves(position) {
+            let value = self.child_value(position, card, alpha, beta);
+            if (maximizing && value > best) || (!maximizing && value < best) {
+                best = value;
+                best_card = Some(card);
+            }
+            if maximizing {
+                alpha = alpha.max(value);
+            } else {
+                beta = beta.min(value);
+            }
+            if alpha >= beta {
+                break;
+            }
+        }
+        if position.is_trick_start() && !self.aborted {
+            self.store(position, best, best_card, window);
+        }
+        best
+    }
+
+    fn store(
+        &mut self,
+        position: &SolverPosition,
+        value: i32,
+        best_card: Option<u8>,
+        (alpha, beta): (i32, i32),
+    ) {
+        let mut bounds = self.table.get(position.hash()).copied().unwrap_or(Bounds {
+            lower: 0,
+            upper: position.remaining_points(),
+            best_card,
+        });
+        bounds.best_card = best_card;
+        if value > alpha {
+            bounds.lower = bounds.lower.max(value);
+        }
+        if value < beta {
+            bounds.upper = bounds.upper.min(value);
+        }
+        self.table.insert(position.hash(), bounds);
+    }
+
+    fn ordered_moves(&self, position: &SolverPosition) -> Vec<u8> {
+        let player = position.next_to_play();
+        let taker = position.taker();
+        let winner = position.current_winner();
+        let friend_winning = winner.is_some_and(|(seat, _)| (seat == taker) == (player == taker));
+        let mut moves: Vec<(i32, u8)> = Vec::with_capacity(18);
+        let mut remaining: CardSet = position.distinct_moves();
+        while remaining != 0 {
+            let card = remaining.trailing_zeros() as u8;
+            remaining &= remaining - 1;
+            let wins = winner.is_none_or(|(_, best)| beats(card, best));
+            let priority = match winner {
+                // Leading: strong cards first
+                None => strength(card),
+                Some(_) if wins && !friend_winning => 200 - strength(card),
+                Some(_) if friend_winning && !wins => 100 + card_points(card),
+                _ => 50 - card_points(card),
+            };
+            moves.push((priority, card));
+        }
+        // The best card found by a previous search of the same position goes first
+        let previous_best = if position.is_trick_start() {
+            self.table
+                .get(position.hash())
+                .and_then(|bounds| bounds.best_card)
+        } else {
+            None
+        };
+        moves.sort_by_key(|&(priority, card)| (Some(card) != previous_best, -priority, card));
+        moves.into_iter().map(|(_, card)| card).collect()
+    }
+}
+
+fn already_won(state: &GameState) -> usize {
+    let shared_state = &state.shared_state;
+    let ecart = match shared_state.game_type {
+        Some(game_type) if game_type.kitty_goes_to_taker() => {
+            state.kitty.iter().map(Card::points).sum()
+        }
+        _ => 0,
+    };
+    shared_state.current_score() + ecart
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::business::{game_engine::seed::TarotRng, GameType};
+
+    fn endgame(seed: u64, tricks_left: usize) -> GameState {
+        GameState::played_at_random(
+            &mut TarotRng::seed_from_u64(seed),
+            1,
+            GameType::GardeContre { chelem: false },
+            4 * (18 - tricks_left),
+        )
+    }
+
+    fn minimax(state: &GameState) -> usize {
+        let Some(player) = state.shared_state.next_to_play() else {
+            return state.shared_state.current_score();
+        };
+        let values = state.legal_moves(player).into_iter().map(|card| {
+            let mut child = state.clone();
+            child.play_card(player, &card).unwrap();
+            minimax(&child)
+        });
+        if Some(player) == state.shared_state.taker {
+            values.max().unwrap()
+        } else {
+            values.min().unwrap()
+        }
+    }
+
+    #[test]
+    fn test_solver_matches_exhaustive_search() {
+        for seed in 0..10 {
+            let mut state = endgame(seed, 3);
+            // Also start from the middle of a trick
+            if seed % 2 == 1 {
+                let player = state.shared_state.next_to_play().unwrap();
+                let card = state.legal_moves(player)[0];
+                state.play_card(player, &card).unwrap();
+            }
+            let mut solver = DoubleDummySolver::with_capacity(1 << 12);
+            let result = solver.solve(&state).unwrap();
+            assert_eq!(result.taker_points, minimax(&state));
+            let moves = solver.evaluate_moves(&state).unwrap();
+            let player = state.shared_state.next_to_play().unwrap();
+            assert_eq!(
+                moves.iter().map(|(card, _)| *card).collect::<Vec<_>>(),
+                state.legal_moves(player)
+            );
+            assert!(moves.contains(&(result.best_card, result.taker_points)));
+            for (card, value) in moves {
+                let mut child = state.clone();
+                child.play_card(player, &card).unwrap();
+                assert_eq!(value, minimax(&child));
+            }
+        }
+    }
+}
@@ -0,0 +1,379 @@
+use crate::business::{
+    analysis::analysis_error::AnalysisError,
+    game_engine::{
+        card::{Card, NUMBER_OF_CARDS},
+        engine_error::EngineError,
+        game_state::GameState,
+        zobrist::ZobristKeys,
+    },
+};
+
+// One bit per card, following `Card::index`
+pub type CardSet = u128;
+
+const TRUMP: usize = 4;
+const EXCUSE: usize = 5;
+const EXCUSE_INDEX: u8 = 77;
+const ALL_CARDS: CardSet = (1 << NUMBER_OF_CARDS) - 1;
+
+const fn suit_of(index: usize) -> usize {
+    if index < 56 {
+        index / 14
+    } else if index < 77 {
+        TRUMP
+    } else {
+        EXCUSE
+    }
+}
+
+const fn points_of(index: usize) -> i32 {
+    match suit_of(index) {
+        EXCUSE => 9,
+        TRUMP => {
+            if index == 56 || index == 76 {
+                9
+            } else {
+                1
+            }
+        }
+        _ => {
+            let value = (index % 14 + 1) as i32;
+            if value < 11 {
+                1
+            } else {
+                (value - 10) * 2 + 1
+            }
+        }
+    }
+}
+
+const fn suit_mask(suit: usize) -> CardSet {
+    match suit {
+        EXCUSE => 1 << EXCUSE_INDEX,
+        TRUMP => ((1 << 21) - 1) << 56,
+        _ => ((1 << 14) - 1) << (suit * 14),
+    }
+}
+
+// Cards strictly between two indices
+const fn between(low: u8, high: u8) -> CardSet {
+    if high <= low + 1 {
+        0
+    } else {
+        ((1 << (high - low - 1)) - 1) << (low + 1)
+    }
+}
+
+pub fn card_points(index: u8) -> i32 {
+    points_of(index as usize)
+}
+
+pub fn set_points(mut cards: CardSet) -> i32 {
+    let mut total = 0;
+    while cards != 0 {
+        total += points_of(cards.trailing_zeros() as usize);
+        cards &= cards - 1;
+    }
+    total
+}
+
+pub fn to_card(index: u8) -> Result<Card, AnalysisError> {
+    Card::from_index(index as usize).map_err(AnalysisError::Engine)
+}
+
+// A compact copy of the card play, cheap enough to be copied at every node
+#[derive(Debug, Clone, Copy)]
+pub struct SolverPosition {
+    hands: [CardSet; 4],
+    table: [Option<u8>; 4],
+    leader: u8,
+    cards_on_table: u8,
+    taker: u8,
+    // Only covers hands, leader and taker: it identifies positions between tricks
+    hash: u64,
+}
+
+impl SolverPosition {
+    pub fn from_game_state(state: &GameState) -> Result<Self, AnalysisError> {
+        let shared_state = &state.shared_state;
+        if shared_state.finished() {
+            return Err(AnalysisError::AnalysisFinished);
+        }
+        let taker = shared_state
+            .taker
+            .ok_or(AnalysisError::Engine(EngineError::NoTaker))?;
+        let keys = ZobristKeys::get();
+        let mut hands: [CardSet; 4] = [0; 4];
+        let mut hash = keys.taker(Some(taker));
+        for (player, player_state) in state.players_state.iter().enumerate() {
+            for card in &player_state.hand {
+                hands[player] |= 1 << card.index();
+                hash ^= keys.hand_by_index(player as u8, card.index());
+            }
+        }
+        let trick = shared_state.current_or_new_trick();
+        let table = trick.cards.map(|card| card.map(|card| card.index() as u8));
+        let cards_on_table = table.iter().flatten().count() as u8;
+        let hand_size = 18 - shared_state.played_tricks.len();
+        for (player, hand) in hands.iter().enumerate() {
+            let expected = hand_size - table[player].is_some() as usize;
+            if hand.count_ones() as usize != expected {
+                return Err(AnalysisError::Other(format!(
+                    "Player {} holds {} cards instead of {}",
+                    player,
+                    hand.count_ones(),
+                    expected
+                )));
+            }
+        }
+        Ok(Self {
+            hands,
+            table,
+            leader: trick.leader,
+            cards_on_table,
+            taker,
+            hash: hash ^ keys.leader(trick.leader),
+        })
+    }
+
+    pub fn taker(&self) -> u8 {
+        self.taker
+    }
+
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    pub fn is_trick_start(&self) -> bool {
+        self.cards_on_table == 0
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.cards_on_table == 0 && self.hands.iter().all(|hand| *hand == 0)
+    }
+
+    pub fn next_to_play(&self) -> u8 {
+        (self.leader + self.cards_on_table) % 4
+    }
+
+    pub fn remaining_points(&self) -> i32 {
+        let on_table: CardSet = self
+            .table
+            .iter()
+            .flatten()
+            .fold(0, |set, &card| set | 1 << card);
+        set_points(self.hands.iter().fold(on_table, |set, hand| set | hand))
+    }
+
+    // Cards which are neither in a hand nor on the table: played before or set aside
+    fn removed(&self) -> CardSet {
+        let present = self
+            .table
+            .iter()
+            .flatten()
+            .fold(0, |set, &card| set | 1 << card);
+        ALL_CARDS & !self.hands.iter().fold(present, |set, hand| set | hand)
+    }
+
+    fn lead_suit(&self) -> Option<usize> {
+        (0..self.cards_on_table)
+            .filter_map(|offset| self.table[((self.leader + offset) % 4) as usize])
+            .map(|card| suit_of(card as usize))
+            .find(|&suit| suit != EXCUSE)
+    }
+
+    // Seat and card currently winning the trick, the excuse never wins
+    pub fn current_winner(&self) -> Option<(u8, u8)> {
+        let mut best: Option<(u8, u8)> = None;
+        for offset in 0..self.cards_on_table {
+            let seat = (self.leader + offset) % 4;
+            let Some(card) = self.table[seat as usize] else {
+                continue;
+            };
+            if best.is_none_or(|(_, best_card)| beats(card, best_card)) && card != EXCUSE_INDEX {
+                best = Some((seat, card));
+            }
+        }
+        best
+    }
+
+    // Whether the taker holds nothing but trumps above every trump of the
+    // defence, so that leading them wins every trick left
+    pub fn taker_holds_master_trumps(&self) -> bool {
+        let taker_hand = self.hands[self.taker as usize];
+        let defence_trumps = (0..4)
+            .filter(|seat| *seat != self.taker)
+            .fold(0, |cards, seat| cards | self.hands[seat as usize])
+            & suit_mask(TRUMP);
+        taker_hand != 0
+            && taker_hand & !suit_mask(TRUMP) == 0
+            && (defence_trumps == 0
+                || taker_hand.trailing_zeros() > CardSet::BITS - 1 - defence_trumps.leading_zeros())
+    }
+
+    // Same rules as `PlayerGameState::allowed_to_play`
+    pub fn legal_moves(&self) -> CardSet {
+        let hand = self.hands[self.next_to_play() as usize];
+        let excuse = hand & suit_mask(EXCUSE);
+        let Some(suit) = self.lead_suit() else {
+            return hand;
+        };
+        let followed = hand & suit_mask(suit);
+        if suit != TRUMP && followed != 0 {
+            return followed | excuse;
+        }
+        let trumps = hand & suit_mask(TRUMP);
+        if trumps == 0 {
+            return hand;
+        }
+        let highest_trump = self
+            .table
+            .iter()
+            .flatten()
+            .filter(|&&card| suit_of(card as usize) == TRUMP)
+            .max();
+        let higher = match highest_trump {
+            Some(&card) => trumps & !((1 << (card + 1)) - 1),
+            None => trumps,
+        };
+        (if higher != 0 { higher } else { trumps }) | excuse
+    }
+
+    // Keeps the lowest card of each group of equivalent legal cards: same suit,
+    // same points and no card left in play between them
+    pub fn distinct_moves(&self) -> CardSet {
+        let legal = self.legal_moves();
+        let removed = self.removed();
+        let mut result = 0;
+        let mut previous: Option<u8> = None;
+        let mut remaining = legal;
+        while remaining != 0 {
+            let card = remaining.trailing_zeros() as u8;
+            remaining &= remaining - 1;
+            let equivalent = previous.is_some_and(|previous| {
+                suit_of(previous as usize) == suit_of(card as usize)
+                    && suit_of(card as usize) != EXCUSE
+                    && points_of(previous as usize) == points_of(card as usize)
+                    && between(previous, card) & !removed == 0
+            });
+            if !equivalent {
+                result |= 1 << card;
+            }
+            previous = Some(card);
+        }
+        result
+    }
+
+    // Legal cards sharing the outcome of `card`, itself included
+    pub fn equivalent_moves(&self, card: u8) -> CardSet {
+        let distinct = self.distinct_moves();
+        let mut group: CardSet = 0;
+        let mut remaining = self.legal_moves();
+        while remaining != 0 {
+            let current = remaining.trailing_zeros() as u8;
+            remaining &= remaining - 1;
+            if distinct & (1 << current) != 0 {
+                if group & (1 << card) != 0 {
+                    return group;
+                }
+                group = 0;
+            }
+            group |= 1 << current;
+        }
+        group
+    }
+
+    // Plays a legal card for the next player, returns the taker's points once
+    // the trick is over
+    pub fn play(&mut self, card: u8) -> Option<i32> {
+        let keys = ZobristKeys::get();
+        let player = self.next_to_play();
+        self.hands[player as usize] &= !(1 << card);
+        self.hash ^= keys.hand_by_index(player, card as usize);
+        self.table[player as usize] = Some(card);
+        self.cards_on_table += 1;
+        if self.cards_on_table < 4 {
+            return None;
+        }
+        let (winner, _) = self.current_winner()?;
+        let cards = self.table.map(|card| card.unwrap_or(EXCUSE_INDEX));
+        let points: i32 = cards.iter().map(|&card| card_points(card)).sum();
+        let taker_won = winner == self.taker;
+        let excuse_owner = (0..4).find(|&seat| cards[seat as usize] == EXCUSE_INDEX);
+        // The excuse goes back to its owner, who gives a low card in exchange
+        let taker_points = match excuse_owner {
+            Some(owner) if (owner == self.taker) != taker_won => {
+                if taker_won {
+                    points - 8
+                } else {
+                    8
+                }
+            }
+            _ => {
+                if taker_won {
+                    points
+                } else {
+                    0
+                }
+            }
+        };
+        self.hash ^= keys.leader(self.leader) ^ keys.leader(winner);
+        self.table = [None; 4];
+        self.leader = winner;
+        self.cards_on_table = 0;
+        Some(taker_points)
+    }
+}
+
+pub fn beats(card: u8, other: u8) -> bool {
+    match (suit_of(card as usize), suit_of(other as usize)) {
+        (EXCUSE, _) => false,
+        (_, EXCUSE) => true,
+        (suit, other_suit) if suit == other_suit => card > other,
+        (TRUMP, _) => true,
+        _ => false,
+    }
+}
+
+pub fn strength(card: u8) -> i32 {
+    match suit_of(card as usize) {
+        EXCUSE => 0,
+        TRUMP => card as i32 - 41,
+        _ => (card % 14) as i32 + 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{seq::IndexedRandom, SeedableRng};
+
+    use super::*;
+    use crate::business::{game_engine::seed::TarotRng, GameType};
+
+    #[test]
+    fn test_legal_moves_match_engine() {
+        for index in 0..NUMBER_OF_CARDS {
+            let card = Card::from_index(index).unwrap();
+            assert_eq!(points_of(index) as usize, card.points());
+        }
+        let mut rng = TarotRng::seed_from_u64(31);
+        for _ in 0..20 {
+            let mut state = GameState::random_init_with_rng(&mut rng).unwrap();
+            state
+                .shared_state
+                .bid(0, Some(GameType::GardeSans { chelem: false }))
+                .unwrap();
+            while let Some(player) = state.shared_state.next_to_play() {
+                let position = SolverPosition::from_game_state(&state).unwrap();
+                let expected: CardSet = state
+                    .legal_moves(player)
+                    .iter()
+                    .fold(0, |set, card| set | 1 << card.index());
+                assert_eq!(position.next_to_play(), player);
+                assert_eq!(position.legal_moves(), expected);
+                let card = *state.legal_moves(player).choose(&mut rng).unwrap();
+                state.play_card(player, &card).unwrap();
+            }
+        }
+    }
+}
@@ -0,0 +1,235 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use rand::seq::SliceRandom;
+use rayon::prelude::*;
+
+use crate::business::analysis::{
+    analysis_error::AnalysisError, players::expert::Expert, playout_policy::PlayoutPolicy,
+    simulate_random_playout::simulate_playout,
+};
+use crate::business::game_engine::{
+    game_state::GameState,
+    seed::{derive_seed, rng_from_seed},
+};
+use crate::business::{Card, Color, KnownGameState};
+
+const COLORS: [Color; 4] = [Color::Spade, Color::Heart, Color::Diamond, Color::Club];
+// Weights of the quick score of a discard, in half points
+const VOID_BONUS: i32 = 12;
+const SINGLETON_BONUS: i32 = 4;
+const TRUMP_PENALTY: i32 = 20;
+
+// Ranks the legal discards with a quick score, then plays the best ones out
+// on sampled deals and keeps the one bringing the taker the most points
+#[derive(Clone)]
+pub struct EcartOptimiser {
+    // Discards simulated after the quick score
+    pub shortlist: usize,
+    pub samples: usize,
+    pub playout: Arc<dyn PlayoutPolicy>,
+    seed: u64,
+}
+
+impl EcartOptimiser {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            shortlist: 6,
+            samples: 24,
+            playout: Arc::new(Expert),
+            seed,
+        }
+    }
+
+    pub fn with_shortlist(mut self, shortlist: usize) -> Self {
+        self.shortlist = shortlist;
+        self
+    }
+
+    pub fn with_samples(mut self, samples: usize) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    pub fn with_playout(mut self, playout: impl PlayoutPolicy + 'static) -> Self {
+        self.playout = Arc::new(playout);
+        self
+    }
+
+    // `known` is the taker with the kitty in hand
+    pub fn optimise(&self, known: &KnownGameState) -> Result<[Card; 6], AnalysisError> {
+        let hand = &known.player_state.hand;
+        let mut asides = legal_asides(hand);
+        // Stable, so that equal scores keep the order of the cards
+        asides.sort_by_key(|aside| -aside_score(hand, aside));
+        asides.truncate(self.shortlist.max(1));
+        if asides.len() <= 1 || self.samples == 0 {
+            return asides.first().copied().ok_or(AnalysisError::NoCardToPlay);
+        }
+
+        let mut others: Vec<Card> = Card::all_possibles()
+            .into_iter()
+            .filter(|card| !hand.contains(card))
+            .collect();
+        others.sort_by_key(Card::index);
+        let taker = known.player_index;
+        // Every discard is played on the same deals
+        let totals: Vec<Vec<usize>> = (0..self.samples)
+            .into_par_iter()
+            .map(|sample| {
+                let sample_seed = derive_seed(self.seed, sample as u64);
+                let mut cards = others.clone();
+                cards.shuffle(&mut rng_from_seed(sample_seed));
+                let mut chunks = cards.chunks(18);
+                let hands: [HashSet<Card>; 4] = [0, 1, 2, 3].map(|player| {
+                    if player == taker {
+                        hand.clone()
+                    } else {
+                        chunks
+                            .next()
+                            .map(|chunk| chunk.iter().copied().collect())
+                            .unwrap_or_default()
+                    }
+                });
+                asides
+                    .iter()
+                    .enumerate()
+                    .map(|(index, aside)| {
+                        let mut state = GameState::initialize(hands.clone(), *aside, 0);
+                        state.shared_state = known.shared_state.clone();
+                        state.discard(*aside).map_err(AnalysisError::Engine)?;
+                        let mut rng = rng_from_seed(derive_seed(sample_seed, index as u64));
+                        simulate_playout(&mut state, self.playout.as_ref(), &mut rng)?;
+                        let score = state.final_score().map_err(AnalysisError::Engine)?;
+                        Ok(score.taker_points)
+                    })
+                    .collect()
+            })
+            .collect::<Result<_, AnalysisError>>()?;
+        let best = (0..asides.len())
+            // The first of equal discards, the best by the quick score, is kept
+            .rev()
+            .max_by_key(|index| totals.iter().map(|sample| sample[*index]).sum::<usize>())
+            .ok_or(AnalysisError::NoCardToPlay)?;
+        Ok(asides[best])
+    }
+}
+
+// Every discard the rules allow: no king nor oudler, and trumps only when
+// nothing else is left
+pub fn legal_asides(hand: &HashSet<Card>) -> Vec<[Card; 6]> {
+    let mut candidates: Vec<Card> = hand
+        .iter()
+        .filter(|card| card.color != Color::Trump && !card.is_oudler() && card.value != 14)
+        .copied()
+        .collect();
+    candidates.sort_by_key(Card::index);
+    if candidates.len() >= 6 {
+        return combinations(&candidates, 6)
+            .into_iter()
+            .filter_map(|aside| aside.try_into().ok())
+            .collect();
+    }
+    let mut trumps: Vec<Card> = hand
+        .iter()
+        .filter(|card| card.color == Color::Trump && !card.is_oudler())
+        .copied()
+        .collect();
+    trumps.sort_by_key(Card::index);
+    combinations(&trumps, 6 - candidates.len())
+        .into_iter()
+        .filter_map(|chosen| {
+            let mut aside = candidates.clone();
+            aside.extend(chosen);
+            aside.try_into().ok()
+        })
+        .collect()
+}
+
+// Voids and short suits let the taker trump, points set aside are safe, and
+// every trump set aside weakens the control of the deal
+pub fn aside_score(hand: &HashSet<Card>, aside: &[Card; 6]) -> i32 {
+    let mut score: i32 = aside.iter().map(|card| card.points() as i32).sum();
+    for color in COLORS {
+        let left = hand
+            .iter()
+            .filter(|card| card.color == color && !aside.contains(card))
+            .count();
+        score += match left {
+            0 => VOID_BONUS,
+            1 => SINGLETON_BONUS,
+            _ => 0,
+        };
+    }
+    let trumps = aside
+        .iter()
+        .filter(|card| card.color == Color::Trump)
+        .count() as i32;
+    score - TRUMP_PENALTY * trumps
+}
+
+fn combinations(cards: &[Card], size: usize) -> Vec<Vec<Card>> {
+    if size == 0 {
+        return vec![vec![]];
+    }
+    if cards.len() < size {
+        return vec![];
+    }
+    let mut result: Vec<Vec<Card>> = combinations(&cards[1..], size - 1)
+        .into_iter()
+        .map(|mut rest| {
+            rest.insert(0, cards[0]);
+            rest
+        })
+        .collect();
+    result.extend(combinations(&cards[1..], size));
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::business::{game_engine::seed::TarotRng, GameType};
+
+    #[test]
+    fn test_discards_are_legal_and_create_voids() {
+        let mut rng = TarotRng::seed_from_u64(42);
+        for _ in 0..5 {
+            let mut state = GameState::random_init_with_rng(&mut rng).unwrap();
+            state
+                .shared_state
+                .bid(0, Some(GameType::Garde { chelem: false }))
+                .unwrap();
+            state.take_kitty().unwrap();
+            let hand = state.players_state[0].hand.clone();
+            let asides = legal_asides(&hand);
+            assert!(!asides.is_empty());
+            for aside in asides.iter().step_by(97) {
+                assert!(state.clone().discard(*aside).is_ok());
+            }
+
+            let known = KnownGameState::from_omniscient(&state, 0);
+            let aside = EcartOptimiser::new(42)
+                .with_samples(8)
+                .optimise(&known)
+                .unwrap();
+            assert!(state.clone().discard(aside).is_ok());
+            // A suit of at most two discardable cards is emptied
+            let short = COLORS.iter().any(|color| {
+                let cards: Vec<&Card> = hand.iter().filter(|card| card.color == *color).collect();
+                !cards.is_empty() && cards.len() <= 2 && cards.iter().all(|card| card.value != 14)
+            });
+            if short {
+                assert!(COLORS.iter().any(|color| {
+                    hand.iter().any(|card| card.color == *color)
+                        && hand
+                            .iter()
+                            .filter(|card| card.color == *color)
+                            .all(|card| aside.contains(card))
+                }));
+            }
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use crate::business::{
+    analysis::{analysis_error::AnalysisError, reward::TOTAL_POINTS},
+    game_engine::{engine_error::EngineError, game_state::GameState},
+    Card, Color,
+};
+
+const COLORS: [Color; 4] = [Color::Spade, Color::Heart, Color::Diamond, Color::Club];
+// Chances of the taker's camp to win a king: when every defender has to
+// follow, when one of them can trump it, and in between
+const SAFE_KING: f64 = 0.9;
+const TRUMPED_KING: f64 = 0.3;
+const UNCERTAIN_KING: f64 = 0.6;
+// A defender's king the taker can trump, or has to let go
+const KING_TRUMPED_BY_TAKER: f64 = 0.7;
+const KING_KEPT_BY_DEFENDERS: f64 = 0.1;
+
+// Estimates the card points the taker ends the deal with, in half points
+// like the score, without playing it out
+pub trait Evaluator: Send + Sync {
+    fn taker_points(&self, state: &GameState) -> Result<f64, AnalysisError>;
+}
+
+// Points already won and the kitty, plus the points still in hand weighted by
+// the taker's chances to win them: the share of the trumps left for most
+// cards, who can trump or must follow for kings, how well the Petit is
+// covered. The 21 and the Excuse stay with their camp.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HandCraftedEvaluator;
+
+impl Evaluator for HandCraftedEvaluator {
+    fn taker_points(&self, state: &GameState) -> Result<f64, AnalysisError> {
+        let shared_state = &state.shared_state;
+        let taker = shared_state
+            .taker
+            .ok_or(AnalysisError::Engine(EngineError::NoTaker))? as usize;
+        let game_type = shared_state
+            .game_type
+            .ok_or(AnalysisError::Engine(EngineError::NoTaker))?;
+        let mut points = shared_state.current_score() as f64;
+        if game_type.kitty_goes_to_taker() {
+            points += state.kitty.iter().map(Card::points).sum::<usize>() as f64;
+        }
+
+        let trumps = |player: usize| {
+            state.players_state[player]
+                .hand
+                .iter()
+                .filter(|card| card.color == Color::Trump && card.value > 1)
+                .count()
+        };
+        let has = |player: usize, color: Color| {
+            state.players_state[player]
+                .hand
+                .iter()
+                .any(|card| card.color == color)
+        };
+        let defenders: Vec<usize> = (0..4).filter(|player| *player != taker).collect();
+        let taker_trumps = trumps(taker);
+        let defender_trumps: usize = defenders.iter().map(|player| trumps(*player)).sum();
+        // Tricks are won with trumps as the deal goes on
+        let control = (taker_trumps as f64 + 1.0) / ((taker_trumps + defender_trumps) as f64 + 2.0);
+        let king_chances = |holder: usize, color: Color| {
+            if holder == taker {
+                let followed = defenders.iter().filter(|player| has(**player, color));
+                match followed.count() {
+                    3 => SAFE_KING,
+                    _ if defenders
+                        .iter()
+                        .any(|player| !has(*player, color) && trumps(*player) > 0) =>
+                    {
+                        TRUMPED_KING
+                    }
+                    _ => UNCERTAIN_KING,
+                }
+            } else if !has(taker, color) && taker_trumps > 0 {
+                KING_TRUMPED_BY_TAKER
+            } else if has(taker, color) {
+                KING_KEPT_BY_DEFENDERS
+            } else {
+                control
+            }
+        };
+
+        let in_hands = (0..4).flat_map(|player| {
+            state.players_state[player]
+                .hand
+                .iter()
+                .map(move |card| (player, card))
+        });
+        let in_trick = shared_state.current_trick.iter().flat_map(|trick| {
+            trick
+                .cards
+                .iter()
+                .enumerate()
+                .filter_map(|(player, card)| card.as_ref().map(|card| (player, card)))
+        });
+        for (holder, card) in in_hands.chain(in_trick) {
+            let chances = if card.color == Color::Excuse
+                || (card.color == Color::Trump && card.value == 21)
+            {
+                (holder == taker) as u8 as f64
+            } else if card.color == Color::Trump && card.value == 1 {
+                let (own, other) = if holder == taker {
+                    (taker_trumps, defender_trumps)
+                } else {
+                    (defender_trumps, taker_trumps)
+                };
+                // Lost once it is the last trump of its camp
+                let safety = (own as f64 + 1.0) / ((own + other) as f64 + 1.0);
+                if holder == taker {
+                    safety
+                } else {
+                    1.0 - safety
+                }
+            } else if card.value == 14 && COLORS.contains(&card.color) {
+                king_chances(holder, card.color)
+            } else {
+                control
+            };
+            points += chances * card.points() as f64;
+        }
+        Ok(points.clamp(0.0, TOTAL_POINTS))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::business::{game_engine::seed::TarotRng, GameType};
+
+    #[test]
+    fn test_estimates_follow_the_deal() {
+        let mut rng = TarotRng::seed_from_u64(48);
+        let (mut estimates, mut finals) = (vec![], vec![]);
+        for _ in 0..60 {
+            let mut state =
+                GameState::played_at_random(&mut rng, 1, GameType::Garde { chelem: false }, 36);
+            let estimate = HandCraftedEvaluator.taker_points(&state).unwrap();
+            state.play_at_random(&mut rng, usize::MAX);
+            // Nothing is left to guess once every card is down
+            let score = state.final_score().unwrap();
+            assert_eq!(
+                HandCraftedEvaluator.taker_points(&state).unwrap(),
+                score.taker_points as f64
+            );
+            estimates.push(estimate);
+            finals.push(score.taker_points as f64);
+        }
+        assert!(correlation(&estimates, &finals) > 0.5);
+    }
+
+    fn correlation(xs: &[f64], ys: &[f64]) -> f64 {
+        let mean = |values: &[f64]| values.iter().sum::<f64>() / values.len() as f64;
+        let (x_mean, y_mean) = (mean(xs), mean(ys));
+        let covariance: f64 = xs
+            .iter()
+            .zip(ys)
+            .map(|(x, y)| (x - x_mean) * (y - y_mean))
+            .sum();
+        let spread = |values: &[f64], mean: f64| {
+            values
+                .iter()
+                .map(|value| (value - mean).powi(2))
+                .sum::<f64>()
+                .sqrt()
+        };
+        covariance / (spread(xs, x_mean) * spread(ys, y_mean))
+    }
+}
@@ -1,4 +1,17 @@
 pub mod analysis_error;
+pub mod announcements;
+pub mod bidding;
+pub mod card_beliefs;
+pub mod double_dummy;
+pub mod ecart;
 pub mod evaluate_hand;
+pub mod evaluator;
+pub mod network;
 pub mod players;
+pub mod playout_policy;
+pub mod prior_policy;
+pub mod reward;
+pub mod search_control;
+pub mod search_report;
 pub mod simulate_random_playout;
+pub mod transposition_table;
@@ -0,0 +1,269 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::business::{
+    analysis::{
+        analysis_error::AnalysisError, evaluator::Evaluator, prior_policy::PriorPolicy,
+        reward::TOTAL_POINTS,
+    },
+    encoding::features::{encode_features, FEATURES_SIZE, FEATURES_VERSION},
+    game_engine::{
+        card::NUMBER_OF_CARDS, engine_error::EngineError, game_state::GameState,
+        seed::rng_from_seed,
+    },
+    Card, KnownGameState,
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Layer {
+    pub inputs: usize,
+    pub outputs: usize,
+    // One row of `inputs` weights per output
+    pub weights: Vec<f32>,
+    pub biases: Vec<f32>,
+}
+
+impl Layer {
+    fn new<R: Rng + ?Sized>(inputs: usize, outputs: usize, rng: &mut R) -> Self {
+        // He initialisation, suited to ReLU
+        let bound = (6.0 / inputs as f32).sqrt();
+        Self {
+            inputs,
+            outputs,
+            weights: (0..inputs * outputs)
+                .map(|_| rng.random_range(-bound..bound))
+                .collect(),
+            biases: vec![0.0; outputs],
+        }
+    }
+
+    pub fn zeroed(&self) -> Self {
+        Self {
+            inputs: self.inputs,
+            outputs: self.outputs,
+            weights: vec![0.0; self.weights.len()],
+            biases: vec![0.0; self.biases.len()],
+        }
+    }
+
+    // Features and ReLU outputs are mostly zeros, only the others are read
+    pub fn forward(&self, input: &[f32]) -> Vec<f32> {
+        let active: Vec<(usize, f32)> = input
+            .iter()
+            .copied()
+            .enumerate()
+            .filter(|(_, value)| *value != 0.0)
+            .collect();
+        self.weights
+            .chunks_exact(self.inputs)
+            .zip(&self.biases)
+            .map(|(row, bias)| bias + active.iter().map(|(i, value)| row[*i] * value).sum::<f32>())
+            .collect()
+    }
+
+    fn check(&self, inputs: usize, outputs: usize) -> Result<(), AnalysisError> {
+        if self.inputs != inputs
+            || self.outputs != outputs
+            || self.weights.len() != inputs * outputs
+            || self.biases.len() != outputs
+        {
+            return Err(AnalysisError::InvalidNetwork(format!(
+                "A layer from {} to {} values is expected",
+                inputs, outputs
+            )));
+        }
+        Ok(())
+    }
+}
+
+// What the network makes of a position
+#[derive(Debug, Clone, PartialEq)]
+pub struct Prediction {
+    // Share of the card points going to the taker
+    pub value: f32,
+    // Probabilities by card index, 0 outside the mask
+    pub policy: Vec<f32>,
+}
+
+// Feature encoding in, ReLU hidden layers, then a value head and a policy
+// head over the 78 cards
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Network {
+    // Layout of the features the network reads
+    pub features_version: u32,
+    pub hidden: Vec<Layer>,
+    pub value: Layer,
+    pub policy: Layer,
+}
+
+impl Network {
+    pub fn new(hidden_sizes: &[usize], seed: u64) -> Self {
+        let mut rng = rng_from_seed(seed);
+        let mut inputs = FEATURES_SIZE;
+        let mut hidden = vec![];
+        for size in hidden_sizes {
+            hidden.push(Layer::new(inputs, *size, &mut rng));
+            inputs = *size;
+        }
+        Self {
+            features_version: FEATURES_VERSION,
+            hidden,
+            value: Layer::new(inputs, 1, &mut rng),
+            policy: Layer::new(inputs, NUMBER_OF_CARDS, &mut rng),
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, AnalysisError> {
+        serde_json::to_string(self).map_err(|e| AnalysisError::RustError(e.to_string()))
+    }
+
+    pub fn from_json(text: &str) -> Result<Self, AnalysisError> {
+        let network: Network =
+            serde_json::from_str(text).map_err(|e| AnalysisError::InvalidNetwork(e.to_string()))?;
+        network.check()?;
+        Ok(network)
+    }
+
+    fn check(&self) -> Result<(), AnalysisError> {
+        if self.features_version != FEATURES_VERSION {
+            return Err(AnalysisError::InvalidNetwork(format!(
+                "Trained on features version {}, version {} is in use",
+                self.features_version, FEATURES_VERSION
+            )));
+        }
+        let mut inputs = FEATURES_SIZE;
+        for layer in &self.hidden {
+            layer.check(inputs, layer.outputs)?;
+            inputs = layer.outputs;
+        }
+        self.value.check(inputs, 1)?;
+        self.policy.check(inputs, NUMBER_OF_CARDS)
+    }
+
+    // The features followed by the output of every hidden layer
+    pub fn activations(&self, features: &[f32]) -> Result<Vec<Vec<f32>>, AnalysisError> {
+        if features.len() != FEATURES_SIZE {
+            return Err(AnalysisError::InvalidNetwork(format!(
+                "{} features are expected, not {}",
+                FEATURES_SIZE,
+                features.len()
+            )));
+        }
+        let mut activations = vec![features.to_vec()];
+        for layer in &self.hidden {
+            let mut output = layer.forward(&activations[activations.len() - 1]);
+            output.iter_mut().for_each(|value| *value = value.max(0.0));
+            activations.push(output);
+        }
+        Ok(activations)
+    }
+
+    pub fn predict(&self, features: &[f32], mask: &[f32]) -> Result<Prediction, AnalysisError> {
+        let activations = self.activations(features)?;
+        let last = &activations[activations.len() - 1];
+        Ok(Prediction {
+            value: sigmoid(self.value.forward(last)[0]),
+            policy: masked_softmax(&self.policy.forward(last), mask),
+        })
+    }
+
+    fn features(known: &KnownGameState) -> Result<Vec<f32>, AnalysisError> {
+        encode_features(known).map_err(|e| AnalysisError::Other(e.to_string()))
+    }
+}
+
+pub fn sigmoid(value: f32) -> f32 {
+    1.0 / (1.0 + (-value).exp())
+}
+
+// Softmax of the logits where the mask is set, 0 elsewhere
+pub fn masked_softmax(logits: &[f32], mask: &[f32]) -> Vec<f32> {
+    let max = logits
+        .iter()
+        .zip(mask)
+        .filter(|(_, allowed)| **allowed > 0.0)
+        .map(|(logit, _)| *logit)
+        .fold(f32::NEG_INFINITY, f32::max);
+    let mut probabilities: Vec<f32> = logits
+        .iter()
+        .zip(mask)
+        .map(|(logit, allowed)| {
+            if *allowed > 0.0 {
+                (logit - max).exp()
+            } else {
+                0.0
+            }
+        })
+        .collect();
+    let total: f32 = probabilities.iter().sum();
+    if total > 0.0 {
+        probabilities.iter_mut().for_each(|p| *p /= total);
+    }
+    probabilities
+}
+
+// The deal seen by the taker, the value being about the taker's points
+impl Evaluator for Network {
+    fn taker_points(&self, state: &GameState) -> Result<f64, AnalysisError> {
+        let taker = state
+            .shared_state
+            .taker
+            .ok_or(AnalysisError::Engine(EngineError::NoTaker))?;
+        let features = Self::features(&KnownGameState::from_omniscient(state, taker))?;
+        let prediction = self.predict(&features, &[0.0; NUMBER_OF_CARDS])?;
+        Ok(prediction.value as f64 * TOTAL_POINTS)
+    }
+}
+
+impl PriorPolicy for Network {
+    fn priors(
+        &self,
+        state: &GameState,
+        player: u8,
+        legal_moves: &[Card],
+    ) -> Result<Vec<f64>, AnalysisError> {
+        let features = Self::features(&KnownGameState::from_omniscient(state, player))?;
+        let mut mask = [0.0; NUMBER_OF_CARDS];
+        for card in legal_moves {
+            mask[card.index()] = 1.0;
+        }
+        let policy = self.predict(&features, &mask)?.policy;
+        Ok(legal_moves
+            .iter()
+            .map(|card| policy[card.index()] as f64)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::business::{game_engine::seed::TarotRng, GameType};
+
+    #[test]
+    fn test_network_is_saved_and_predicts() {
+        let network = Network::new(&[16], 50);
+        assert_eq!(
+            Network::from_json(&network.to_json().unwrap()).unwrap(),
+            network
+        );
+        let mut wrong = network.clone();
+        wrong.hidden[0].biases.pop();
+        assert!(Network::from_json(&wrong.to_json().unwrap()).is_err());
+
+        let mut state = GameState::random_init_with_rng(&mut TarotRng::seed_from_u64(50)).unwrap();
+        state
+            .shared_state
+            .bid(0, Some(GameType::Garde { chelem: false }))
+            .unwrap();
+        let points = network.taker_points(&state).unwrap();
+        assert!((0.0..=TOTAL_POINTS).contains(&points));
+        let player = state.shared_state.next_to_play().unwrap();
+        let legal_moves = state.legal_moves(player);
+        let priors = network.priors(&state, player, &legal_moves).unwrap();
+        assert_eq!(priors.len(), legal_moves.len());
+        assert!((priors.iter().sum::<f64>() - 1.0).abs() < 1e-5);
+    }
+}
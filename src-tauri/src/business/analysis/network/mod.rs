@@ -0,0 +1,6 @@
+pub mod mlp;
+pub mod samples;
+pub mod training;
+
+pub use mlp::Network;
+pub use training::{train, Optimizer, TrainingParameters};
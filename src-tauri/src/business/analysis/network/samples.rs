@@ -0,0 +1,85 @@
+use std::io::{BufRead, Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::business::{
+    analysis::{analysis_error::AnalysisError, reward::TOTAL_POINTS},
+    encoding::features::{encode_features, legal_move_mask, FEATURES_VERSION},
+    game_engine::{card::NUMBER_OF_CARDS, engine_error::EngineError, game_state::GameState},
+    Card, KnownGameState,
+};
+
+// A position seen by one player, with what was played there and how the deal
+// ended
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Sample {
+    pub features_version: u32,
+    pub features: Vec<f32>,
+    pub mask: Vec<f32>,
+    // Probabilities to aim at by card index, the card played for recorded deals
+    pub policy: Vec<f32>,
+    // Share of the card points the taker ended the deal with
+    pub value: f32,
+}
+
+// One sample per card of a deal played to the end, from the position before
+// the first card
+pub fn samples_from_deal(start: &GameState, plays: &[Card]) -> Result<Vec<Sample>, AnalysisError> {
+    let mut state = start.clone();
+    let mut samples = Vec::with_capacity(plays.len());
+    for card in plays {
+        let player = state
+            .shared_state
+            .next_to_play()
+            .ok_or(AnalysisError::Engine(EngineError::FinishedHand))?;
+        let known = KnownGameState::from_omniscient(&state, player);
+        let mut policy = vec![0.0; NUMBER_OF_CARDS];
+        policy[card.index()] = 1.0;
+        samples.push(Sample {
+            features_version: FEATURES_VERSION,
+            features: encode_features(&known).map_err(|e| AnalysisError::Other(e.to_string()))?,
+            mask: legal_move_mask(&known),
+            policy,
+            value: 0.0,
+        });
+        state
+            .play_card(player, card)
+            .map_err(AnalysisError::Engine)?;
+    }
+    let score = state.final_score().map_err(AnalysisError::Engine)?;
+    let value = score.taker_points as f32 / TOTAL_POINTS as f32;
+    samples.iter_mut().for_each(|sample| sample.value = value);
+    Ok(samples)
+}
+
+// One JSON sample per line
+pub fn write_samples(output: &mut impl Write, samples: &[Sample]) -> Result<(), AnalysisError> {
+    for sample in samples {
+        let line =
+            serde_json::to_string(sample).map_err(|e| AnalysisError::RustError(e.to_string()))?;
+        writeln!(output, "{}", line).map_err(|e| AnalysisError::RustError(e.to_string()))?;
+    }
+    Ok(())
+}
+
+pub fn read_samples(input: impl BufRead) -> Result<Vec<Sample>, AnalysisError> {
+    let mut samples = vec![];
+    for (index, line) in input.lines().enumerate() {
+        let line = line.map_err(|e| AnalysisError::RustError(e.to_string()))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let sample: Sample = serde_json::from_str(&line).map_err(|e| {
+            AnalysisError::InvalidNetwork(format!("Sample on line {}: {}", index + 1, e))
+        })?;
+        if sample.features_version != FEATURES_VERSION {
+            return Err(AnalysisError::InvalidNetwork(format!(
+                "Sample on line {} uses features version {}",
+                index + 1,
+                sample.features_version
+            )));
+        }
+        samples.push(sample);
+    }
+    Ok(samples)
+}
@@ -0,0 +1,445 @@
+use rand::seq::SliceRandom;
+use rayon::prelude::*;
+
+use crate::business::{
+    analysis::{
+        analysis_error::AnalysisError,
+        network::{
+            mlp::{masked_softmax, sigmoid, Layer, Network},
+            samples::Sample,
+        },
+    },
+    game_engine::seed::{derive_seed, rng_from_seed},
+};
+
+// Samples whose gradients one task sums, partial sums being added in order so
+// that training does not depend on the number of threads
+const GRADIENT_CHUNK: usize = 8;
+// Keeps the cross-entropy finite for cards given no chance at all
+const LOG_EPSILON: f32 = 1e-7;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Optimizer {
+    Sgd {
+        learning_rate: f32,
+        momentum: f32,
+    },
+    Adam {
+        learning_rate: f32,
+        beta1: f32,
+        beta2: f32,
+        epsilon: f32,
+    },
+}
+
+impl Optimizer {
+    pub fn adam(learning_rate: f32) -> Self {
+        Optimizer::Adam {
+            learning_rate,
+            beta1: 0.9,
+            beta2: 0.999,
+            epsilon: 1e-8,
+        }
+    }
+
+    pub fn sgd(learning_rate: f32) -> Self {
+        Optimizer::Sgd {
+            learning_rate,
+            momentum: 0.9,
+        }
+    }
+}
+
+impl Default for Optimizer {
+    fn default() -> Self {
+        Optimizer::adam(0.001)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TrainingParameters {
+    pub epochs: usize,
+    pub batch_size: usize,
+    pub optimizer: Optimizer,
+    // Weight of the value error against the policy cross-entropy
+    pub value_weight: f32,
+    pub seed: u64,
+}
+
+impl TrainingParameters {
+    pub fn new(epochs: usize, seed: u64) -> Self {
+        Self {
+            epochs,
+            batch_size: 64,
+            optimizer: Optimizer::default(),
+            value_weight: 1.0,
+            seed,
+        }
+    }
+
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    pub fn with_optimizer(mut self, optimizer: Optimizer) -> Self {
+        self.optimizer = optimizer;
+        self
+    }
+
+    pub fn with_value_weight(mut self, value_weight: f32) -> Self {
+        self.value_weight = value_weight;
+        self
+    }
+}
+
+impl Network {
+    fn zeroed(&self) -> Network {
+        Network {
+            features_version: self.features_version,
+            hidden: self.hidden.iter().map(Layer::zeroed).collect(),
+            value: self.value.zeroed(),
+            policy: self.policy.zeroed(),
+        }
+    }
+
+    fn layers(&self) -> impl Iterator<Item = &Layer> {
+        self.hidden.iter().chain([&self.value, &self.policy])
+    }
+
+    fn layers_mut(&mut self) -> impl Iterator<Item = &mut Layer> {
+        self.hidden
+            .iter_mut()
+            .chain([&mut self.value, &mut self.policy])
+    }
+
+    fn add(&mut self, other: &Network) {
+        for (layer, other) in self.layers_mut().zip(other.layers()) {
+            add(&mut layer.weights, &other.weights);
+            add(&mut layer.biases, &other.biases);
+        }
+    }
+
+    // Adds the gradient of the sample's loss to `gradient`, the loss is returned
+    fn backpropagate(
+        &self,
+        sample: &Sample,
+        value_weight: f32,
+        gradient: &mut Network,
+    ) -> Result<f32, AnalysisError> {
+        let activations = self.activations(&sample.features)?;
+        let last = &activations[activations.len() - 1];
+        let value = sigmoid(self.value.forward(last)[0]);
+        let policy = masked_softmax(&self.policy.forward(last), &sample.mask);
+        let mut loss = value_weight * (value - sample.value).powi(2);
+        let value_delta = value_weight * 2.0 * (value - sample.value) * value * (1.0 - value);
+        // Positions with nothing to play only teach the value
+        let policy_delta: Vec<f32> = if sample.mask.iter().any(|allowed| *allowed > 0.0) {
+            for (target, probability) in sample.policy.iter().zip(&policy) {
+                if *target > 0.0 {
+                    loss -= target * (probability + LOG_EPSILON).ln();
+                }
+            }
+            policy
+                .iter()
+                .zip(&sample.policy)
+                .zip(&sample.mask)
+                .map(|((probability, target), allowed)| {
+                    if *allowed > 0.0 {
+                        probability - target
+                    } else {
+                        0.0
+                    }
+                })
+                .collect()
+        } else {
+            vec![0.0; policy.len()]
+        };
+
+        let mut delta = vec![0.0; last.len()];
+        backward(
+            &self.value,
+            &mut gradient.value,
+            last,
+            &[value_delta],
+            Some(&mut delta),
+        );
+        backward(
+            &self.policy,
+            &mut gradient.policy,
+            last,
+            &policy_delta,
+            Some(&mut delta),
+        );
+        for index in (0..self.hidden.len()).rev() {
+            // Through the ReLU
+            for (delta, output) in delta.iter_mut().zip(&activations[index + 1]) {
+                if *output <= 0.0 {
+                    *delta = 0.0;
+                }
+            }
+            let input = &activations[index];
+            if index == 0 {
+                backward(
+                    &self.hidden[0],
+                    &mut gradient.hidden[0],
+                    input,
+                    &delta,
+                    None,
+                );
+            } else {
+                let mut input_delta = vec![0.0; input.len()];
+                backward(
+                    &self.hidden[index],
+                    &mut gradient.hidden[index],
+                    input,
+                    &delta,
+                    Some(&mut input_delta),
+                );
+                delta = input_delta;
+            }
+        }
+        Ok(loss)
+    }
+}
+
+fn add(values: &mut [f32], other: &[f32]) {
+    values
+        .iter_mut()
+        .zip(other)
+        .for_each(|(value, other)| *value += other);
+}
+
+// Gradient of a layer from the gradient of its outputs, and the gradient of its
+// inputs when asked for
+fn backward(
+    layer: &Layer,
+    gradient: &mut Layer,
+    input: &[f32],
+    output_delta: &[f32],
+    mut input_delta: Option<&mut Vec<f32>>,
+) {
+    let active: Vec<(usize, f32)> = input
+        .iter()
+        .copied()
+        .enumerate()
+        .filter(|(_, value)| *value != 0.0)
+        .collect();
+    for (output, delta) in output_delta.iter().enumerate() {
+        if *delta == 0.0 {
+            continue;
+        }
+        gradient.biases[output] += delta;
+        let start = output * layer.inputs;
+        let gradient_row = &mut gradient.weights[start..start + layer.inputs];
+        for (index, value) in &active {
+            gradient_row[*index] += delta * value;
+        }
+        if let Some(input_delta) = input_delta.as_mut() {
+            let row = &layer.weights[start..start + layer.inputs];
+            for (input_delta, weight) in input_delta.iter_mut().zip(row) {
+                *input_delta += delta * weight;
+            }
+        }
+    }
+}
+
+// Moments kept by the optimizer, one per weight or bias in the order of the
+// layers
+struct OptimizerState {
+    first: Vec<Vec<f32>>,
+    second: Vec<Vec<f32>>,
+    steps: i32,
+}
+
+impl OptimizerState {
+    fn new(network: &Network) -> Self {
+        let zeros: Vec<Vec<f32>> = network
+            .layers()
+            .flat_map(|layer| {
+                [
+                    vec![0.0; layer.weights.len()],
+                    vec![0.0; layer.biases.len()],
+                ]
+            })
+            .collect();
+        Self {
+            first: zeros.clone(),
+            second: zeros,
+            steps: 0,
+        }
+    }
+
+    fn step(&mut self, optimizer: &Optimizer, network: &mut Network, gradient: &Network) {
+        self.steps += 1;
+        let tensors = network
+            .layers_mut()
+            .flat_map(|layer| [&mut layer.weights, &mut layer.biases]);
+        let gradients = gradient
+            .layers()
+            .flat_map(|layer| [&layer.weights, &layer.biases]);
+        for (((values, gradients), first), second) in tensors
+            .zip(gradients)
+            .zip(&mut self.first)
+            .zip(&mut self.second)
+        {
+            for index in 0..values.len() {
+                let gradient = gradients[index];
+                match *optimizer {
+                    Optimizer::Sgd {
+                        learning_rate,
+                        momentum,
+                    } => {
+                        first[index] = momentum * first[index] + gradient;
+                        values[index] -= learning_rate * first[index];
+                    }
+                    Optimizer::Adam {
+                        learning_rate,
+                        beta1,
+                        beta2,
+                        epsilon,
+                    } => {
+                        first[index] = beta1 * first[index] + (1.0 - beta1) * gradient;
+                        second[index] = beta2 * second[index] + (1.0 - beta2) * gradient * gradient;
+                        let first_corrected = first[index] / (1.0 - beta1.powi(self.steps));
+                        let second_corrected = second[index] / (1.0 - beta2.powi(self.steps));
+                        values[index] -=
+                            learning_rate * first_corrected / (second_corrected.sqrt() + epsilon);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Mini-batch descent on the value error and the policy cross-entropy, the mean
+// loss of every epoch is returned
+pub fn train(
+    network: &mut Network,
+    samples: &[Sample],
+    parameters: &TrainingParameters,
+) -> Result<Vec<f32>, AnalysisError> {
+    if samples.is_empty() || parameters.batch_size == 0 {
+        return Err(AnalysisError::InvalidNetwork(String::from(
+            "Training needs samples and batches of at least one",
+        )));
+    }
+    let mut state = OptimizerState::new(network);
+    let mut order: Vec<usize> = (0..samples.len()).collect();
+    let mut losses = Vec::with_capacity(parameters.epochs);
+    for epoch in 0..parameters.epochs {
+        order.shuffle(&mut rng_from_seed(derive_seed(
+            parameters.seed,
+            epoch as u64,
+        )));
+        let mut epoch_loss = 0.0;
+        for batch in order.chunks(parameters.batch_size) {
+            let network_ref = &*network;
+            let partials: Vec<(Network, f32)> = batch
+                .par_chunks(GRADIENT_CHUNK)
+                .map(|chunk| {
+                    let mut gradient = network_ref.zeroed();
+                    let mut loss = 0.0;
+                    for index in chunk {
+                        loss += network_ref.backpropagate(
+                            &samples[*index],
+                            parameters.value_weight,
+                            &mut gradient,
+                        )?;
+                    }
+                    Ok((gradient, loss))
+                })
+                .collect::<Result<_, AnalysisError>>()?;
+            let mut gradient = network.zeroed();
+            for (partial, loss) in &partials {
+                gradient.add(partial);
+                epoch_loss += loss;
+            }
+            let scale = 1.0 / batch.len() as f32;
+            for layer in gradient.layers_mut() {
+                layer.weights.iter_mut().for_each(|value| *value *= scale);
+                layer.biases.iter_mut().for_each(|value| *value *= scale);
+            }
+            state.step(&parameters.optimizer, network, &gradient);
+        }
+        losses.push(epoch_loss / samples.len() as f32);
+    }
+    Ok(losses)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::business::{
+        analysis::network::samples::samples_from_deal,
+        encoding::features::FEATURES_SIZE,
+        game_engine::{game_state::GameState, seed::TarotRng},
+        GameType,
+    };
+
+    fn random_samples(deals: usize) -> Vec<Sample> {
+        let mut rng = TarotRng::seed_from_u64(50);
+        let mut samples = vec![];
+        for _ in 0..deals {
+            let start =
+                GameState::played_at_random(&mut rng, 2, GameType::GardeSans { chelem: false }, 0);
+            let plays = start.clone().play_at_random(&mut rng, usize::MAX);
+            samples.extend(samples_from_deal(&start, &plays).unwrap());
+        }
+        samples
+    }
+
+    #[test]
+    fn test_training_lowers_the_loss() {
+        let samples = random_samples(3);
+        assert_eq!(samples.len(), 3 * 72);
+        for optimizer in [Optimizer::default(), Optimizer::sgd(0.05)] {
+            let mut network = Network::new(&[32, 16], 50);
+            let parameters = TrainingParameters::new(15, 50)
+                .with_batch_size(16)
+                .with_optimizer(optimizer);
+            let losses = train(&mut network, &samples, &parameters).unwrap();
+            assert!(losses[14] < 0.7 * losses[0], "{:?}", losses);
+        }
+    }
+
+    #[test]
+    fn test_gradient_matches_finite_differences() {
+        let sample = &random_samples(1)[30];
+        let network = Network::new(&[8, 8], 51);
+        let mut gradient = network.zeroed();
+        let loss = network.backpropagate(sample, 1.0, &mut gradient).unwrap();
+        // A weight of the first layer reading a feature that is set
+        let feature = sample
+            .features
+            .iter()
+            .position(|value| *value != 0.0)
+            .unwrap();
+        for (layer, index) in [(0, FEATURES_SIZE + feature), (1, 5), (2, 2), (3, 40)] {
+            let mut moved = network.clone();
+            let step = 1e-2;
+            let (weights, expected) = match layer {
+                0 | 1 => (
+                    &mut moved.hidden[layer].weights,
+                    gradient.hidden[layer].weights[index],
+                ),
+                2 => (&mut moved.value.weights, gradient.value.weights[index]),
+                _ => (&mut moved.policy.weights, gradient.policy.weights[index]),
+            };
+            weights[index] += step;
+            let moved_loss = moved
+                .backpropagate(sample, 1.0, &mut network.zeroed())
+                .unwrap();
+            let estimate = (moved_loss - loss) / step;
+            assert!(
+                (estimate - expected).abs() < 0.05 + 0.1 * expected.abs(),
+                "layer {}: {} against {}",
+                layer,
+                estimate,
+                expected
+            );
+        }
+    }
+}
@@ -0,0 +1,374 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use rand::RngCore;
+
+use crate::business::analysis::analysis_error::AnalysisError;
+use crate::business::analysis::evaluate_hand::evaluate_hand;
+use crate::business::analysis::playout_policy::{
+    cheapest, most_points, winner_after, PlayoutPolicy,
+};
+use crate::business::game_engine::{
+    game_state::GameState, shared_game_state::SharedGameState, trick::Trick,
+};
+use crate::business::{Card, Color, GameType, KnownGameState, Player};
+
+const PETIT: Card = Card {
+    color: Color::Trump,
+    value: 1,
+};
+const EXCUSE: Card = Card {
+    color: Color::Excuse,
+    value: 0,
+};
+const COLORS: [Color; 4] = [Color::Spade, Color::Heart, Color::Diamond, Color::Club];
+// Half points of a trick worth giving the 21 for
+const TRICK_WORTH_THE_21: usize = 10;
+// Trumps a defender needs before leading them to catch the Petit
+const TRUMPS_TO_CHASE_THE_PETIT: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExpertReason {
+    OnlyCard,
+    // The Excuse would be lost in the last trick
+    ExcuseBeforeLastTrick,
+    // The trick is safe for the camp
+    SavePetit,
+    GivePoints,
+    WinCheaply,
+    Discard,
+    // Leads
+    DrawTrumps,
+    CashKing,
+    LeadShortSuit,
+    ChasePetit,
+    LeadLongSuit,
+    LeadLow,
+}
+
+impl fmt::Display for ExpertReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            ExpertReason::OnlyCard => "Only card allowed",
+            ExpertReason::ExcuseBeforeLastTrick => "Play the Excuse before the last trick",
+            ExpertReason::SavePetit => "Save the Petit in a trick won by the camp",
+            ExpertReason::GivePoints => "Give points to a trick won by the camp",
+            ExpertReason::WinCheaply => "Win the trick with the smallest card",
+            ExpertReason::Discard => "Trick lost, discard the smallest card",
+            ExpertReason::DrawTrumps => "Draw the trumps of the defence",
+            ExpertReason::CashKing => "Cash a king while the defence follows",
+            ExpertReason::LeadShortSuit => "Lead a suit the taker does not have",
+            ExpertReason::ChasePetit => "Lead trumps to catch the Petit",
+            ExpertReason::LeadLongSuit => "Lead the longest suit, likely short for the taker",
+            ExpertReason::LeadLow => "Lead a small card",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+// Plays by the usual conventions of the game, without any search: fast,
+// deterministic and able to tell why a card was chosen
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Expert;
+
+impl Expert {
+    pub fn new() -> Self {
+        Self
+    }
+
+    pub fn explain(&self, known: &KnownGameState) -> Result<(Card, ExpertReason), AnalysisError> {
+        let legal_moves = known
+            .player_state
+            .sorted_cards_allowed(&known.shared_state.current_or_new_trick());
+        expert_choice(
+            &known.player_state.hand,
+            &known.shared_state,
+            known.player_index,
+            &legal_moves,
+        )
+    }
+}
+
+impl Player for Expert {
+    fn play_a_card(&mut self, known: &KnownGameState) -> Result<Card, AnalysisError> {
+        self.explain(known).map(|(card, _)| card)
+    }
+
+    fn bid(&self, known: &KnownGameState) -> Result<Option<GameType>, AnalysisError> {
+        // Only contracts above the current one can be bid
+        Ok(evaluate_hand(&known.player_state).filter(|bid| {
+            known
+                .shared_state
+                .game_type
+                .is_none_or(|current| *bid > current)
+        }))
+    }
+
+    fn chose_aside(&self, known: &KnownGameState) -> Result<[Card; 6], AnalysisError> {
+        conventional_aside(&known.player_state.hand)
+    }
+}
+
+impl PlayoutPolicy for Expert {
+    fn choose_card(
+        &self,
+        state: &GameState,
+        player: u8,
+        legal_moves: &[Card],
+        _rng: &mut dyn RngCore,
+    ) -> Result<Card, AnalysisError> {
+        let hand = &state.players_state[player as usize].hand;
+        expert_choice(hand, &state.shared_state, player, legal_moves).map(|(card, _)| card)
+    }
+}
+
+pub fn expert_choice(
+    hand: &HashSet<Card>,
+    shared_state: &SharedGameState,
+    player: u8,
+    legal_moves: &[Card],
+) -> Result<(Card, ExpertReason), AnalysisError> {
+    let first = *legal_moves.first().ok_or(AnalysisError::NoCardToPlay)?;
+    if legal_moves.len() == 1 {
+        return Ok((first, ExpertReason::OnlyCard));
+    }
+    if hand.len() == 2 && legal_moves.contains(&EXCUSE) {
+        return Ok((EXCUSE, ExpertReason::ExcuseBeforeLastTrick));
+    }
+    let trick = shared_state.current_or_new_trick();
+    if trick.cards.iter().all(Option::is_none) {
+        Ok(lead(hand, shared_state, player, legal_moves))
+    } else {
+        follow_trick(&trick, shared_state.taker, player, legal_moves)
+    }
+}
+
+// Short suits are emptied first, their honours saved from the defence
+pub fn conventional_aside(hand: &HashSet<Card>) -> Result<[Card; 6], AnalysisError> {
+    let length = |color: Color| hand.iter().filter(|card| card.color == color).count();
+    let mut candidates: Vec<Card> = hand
+        .iter()
+        .filter(|card| card.color != Color::Trump && !card.is_oudler() && card.value != 14)
+        .copied()
+        .collect();
+    candidates.sort_by_key(|card| {
+        (
+            length(card.color),
+            std::cmp::Reverse(card.points()),
+            card.index(),
+        )
+    });
+    // Trumps only when there is nothing else left to discard
+    let mut trumps: Vec<Card> = hand
+        .iter()
+        .filter(|card| card.color == Color::Trump && !card.is_oudler())
+        .copied()
+        .collect();
+    trumps.sort_by_key(|card| card.value);
+    candidates.extend(trumps);
+    candidates.truncate(6);
+    candidates
+        .try_into()
+        .map_err(|_| AnalysisError::NoCardToPlay)
+}
+
+// Second to fourth card of a trick
+pub fn follow_trick(
+    trick: &Trick,
+    taker: Option<u8>,
+    player: u8,
+    legal_moves: &[Card],
+) -> Result<(Card, ExpertReason), AnalysisError> {
+    let cheapest = cheapest(legal_moves).ok_or(AnalysisError::NoCardToPlay)?;
+    let played = trick.cards.iter().flatten().count();
+    let partners = |a: u8, b: u8| (taker == Some(a)) == (taker == Some(b));
+    // Safe when nobody of the other camp plays after this player
+    let secured = |winner: u8| {
+        (played as u8 + 1..4)
+            .map(|offset| (trick.leader + offset) % 4)
+            .all(|next| partners(next, winner))
+    };
+    let winner_with = |card: &Card| winner_after(trick, player, card);
+
+    if legal_moves.contains(&PETIT) {
+        if let Some(winner) = winner_with(&PETIT) {
+            if partners(winner, player) && secured(winner) {
+                return Ok((PETIT, ExpertReason::SavePetit));
+            }
+        }
+    }
+    let winner = trick.winner().map_err(AnalysisError::Engine)?;
+    if partners(winner, player) && secured(winner) {
+        if let Some(card) = most_points(legal_moves) {
+            return Ok((card, ExpertReason::GivePoints));
+        }
+    }
+    let trick_points = trick.points();
+    let winning = legal_moves
+        .iter()
+        .filter(|card| card.color != Color::Excuse && **card != PETIT)
+        .filter(|card| !is_21(card) || trick_points >= TRICK_WORTH_THE_21)
+        .filter(|card| winner_with(card) == Some(player))
+        .min_by_key(|card| card.value);
+    match winning {
+        // A card worth points is only risked when nobody can take it back
+        Some(card) if secured(player) || card.points() == 1 => {
+            Ok((*card, ExpertReason::WinCheaply))
+        }
+        _ => Ok((cheapest, ExpertReason::Discard)),
+    }
+}
+
+fn lead(
+    hand: &HashSet<Card>,
+    shared_state: &SharedGameState,
+    player: u8,
+    legal_moves: &[Card],
+) -> (Card, ExpertReason) {
+    let taker = shared_state.taker;
+    let defenders: Vec<u8> = (0..4).filter(|other| taker != Some(*other)).collect();
+    let left_to_play = shared_state.cards_left_to_play();
+    let trumps_elsewhere: Vec<u8> = left_to_play
+        .iter()
+        .filter(|card| card.color == Color::Trump && !hand.contains(card))
+        .map(|card| card.value)
+        .collect();
+    let mut trumps: Vec<Card> = legal_moves
+        .iter()
+        .filter(|card| card.color == Color::Trump)
+        .copied()
+        .collect();
+    trumps.sort_by_key(|card| card.value);
+    let of_color = |color: Color| -> Vec<Card> {
+        let mut cards: Vec<Card> = legal_moves
+            .iter()
+            .filter(|card| card.color == color)
+            .copied()
+            .collect();
+        cards.sort_by_key(|card| card.value);
+        cards
+    };
+    let low = cheapest(legal_moves).unwrap_or(legal_moves[0]);
+
+    if taker == Some(player) {
+        let defence_out_of_trumps = trumps_elsewhere.is_empty()
+            || defenders.iter().all(|defender| {
+                shared_state
+                    .voided_colors(*defender as usize)
+                    .contains(&Color::Trump)
+            });
+        if trumps.contains(&PETIT) && defence_out_of_trumps {
+            return (PETIT, ExpertReason::SavePetit);
+        }
+        let drawing: Vec<&Card> = trumps.iter().filter(|card| **card != PETIT).collect();
+        if !defence_out_of_trumps && drawing.len() * 3 > trumps_elsewhere.len() {
+            let highest = drawing.last();
+            let master = highest
+                .is_some_and(|card| trumps_elsewhere.iter().all(|value| *value < card.value));
+            if let (true, Some(card)) = (master, highest) {
+                return (**card, ExpertReason::DrawTrumps);
+            }
+            if let Some(card) = drawing.first() {
+                return (**card, ExpertReason::DrawTrumps);
+            }
+        }
+        for color in COLORS {
+            let king = Card { color, value: 14 };
+            let followed = defenders.iter().all(|defender| {
+                !shared_state
+                    .voided_colors(*defender as usize)
+                    .contains(&color)
+            });
+            if legal_moves.contains(&king) && followed {
+                return (king, ExpertReason::CashKing);
+            }
+        }
+        return (low, ExpertReason::LeadLow);
+    }
+
+    if let Some(taker) = taker {
+        let taker_voids = shared_state.voided_colors(taker as usize);
+        let short = COLORS
+            .iter()
+            .filter(|color| taker_voids.contains(color))
+            .map(|color| of_color(*color))
+            .filter(|cards| !cards.is_empty())
+            .max_by_key(Vec::len);
+        if let Some(cards) = short {
+            return (cards[0], ExpertReason::LeadShortSuit);
+        }
+        let petit_out = left_to_play.contains(&PETIT) && !hand.contains(&PETIT);
+        if petit_out
+            && !taker_voids.contains(&Color::Trump)
+            && trumps.len() >= TRUMPS_TO_CHASE_THE_PETIT
+        {
+            if let Some(card) = trumps.last() {
+                return (*card, ExpertReason::ChasePetit);
+            }
+        }
+    }
+    let long = COLORS
+        .iter()
+        .map(|color| of_color(*color))
+        .filter(|cards| !cards.is_empty())
+        // The first colour wins ties so that choices do not depend on hashing
+        .rev()
+        .max_by_key(Vec::len);
+    match long {
+        Some(cards) => (cards[0], ExpertReason::LeadLongSuit),
+        None => (low, ExpertReason::LeadLow),
+    }
+}
+
+fn is_21(card: &Card) -> bool {
+    card.color == Color::Trump && card.value == 21
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::business::game_engine::seed::TarotRng;
+    use crate::business::tarot::Tarot;
+
+    #[test]
+    fn test_expert_plays_legal_cards_with_reasons() {
+        let mut rng = TarotRng::seed_from_u64(40);
+        for _ in 0..10 {
+            let mut state = GameState::random_init_with_rng(&mut rng).unwrap();
+            state
+                .shared_state
+                .bid(1, Some(GameType::GardeSans { chelem: false }))
+                .unwrap();
+            while let Some(player) = state.shared_state.next_to_play() {
+                let known = KnownGameState::from_omniscient(&state, player);
+                let (card, reason) = Expert.explain(&known).unwrap();
+                assert!(state.legal_moves(player).contains(&card));
+                assert!(!reason.to_string().is_empty());
+                state.play_card(player, &card).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn test_expert_games_are_deterministic() {
+        // Somebody takes in this deal, so that it is played to the end
+        let play = || {
+            let mut tarot = Tarot::initialize_with_seed(
+                [
+                    Box::new(Expert),
+                    Box::new(Expert),
+                    Box::new(Expert),
+                    Box::new(Expert),
+                ],
+                40,
+            );
+            tarot.play().unwrap();
+            tarot.record().plays.clone()
+        };
+        let plays = play();
+        assert_eq!(plays.len(), 72);
+        assert_eq!(plays, play());
+    }
+}
@@ -0,0 +1,150 @@
+use std::collections::BTreeMap;
+
+use crate::business::{analysis::reward::camp_reward, Card};
+
+// Value of a card not tried yet when priors guide the selection, halfway
+// between a lost and a won deal
+const FIRST_PLAY_URGENCY: f64 = 0.5;
+
+// A node stands for everything the observer has seen so far, whatever the
+// hidden cards are: its children are only reachable in some of the worlds
+pub struct ISMCTSNode {
+    // Player who played the card leading to the node, rewards are seen from their camp
+    pub player: Option<u8>,
+    pub visits: usize,
+    // Number of times the node could have been selected from its parent
+    pub availability: usize,
+    pub total_reward: f64,
+    pub total_squared_reward: f64,
+    // Playouts won by the camp of `player`
+    pub wins: usize,
+    // Selections waiting for their playout, counted as lost visits meanwhile
+    pub virtual_loss: usize,
+    pub children: BTreeMap<Card, Box<ISMCTSNode>>,
+}
+
+impl ISMCTSNode {
+    pub fn new(player: Option<u8>) -> Self {
+        Self {
+            player,
+            visits: 0,
+            availability: 0,
+            total_reward: 0.0,
+            total_squared_reward: 0.0,
+            wins: 0,
+            virtual_loss: 0,
+            children: BTreeMap::new(),
+        }
+    }
+
+    fn ucb_value(&self, c_param: f64) -> f64 {
+        let visits = (self.visits + self.virtual_loss) as f64;
+        if visits == 0.0 {
+            f64::INFINITY
+        } else {
+            self.total_reward / visits + c_param * ((self.availability as f64).ln() / visits).sqrt()
+        }
+    }
+
+    fn puct_value(&self, prior: f64, c_param: f64) -> f64 {
+        let visits = (self.visits + self.virtual_loss) as f64;
+        let mean = if visits == 0.0 {
+            FIRST_PLAY_URGENCY
+        } else {
+            self.total_reward / visits
+        };
+        mean + c_param * prior * (self.availability as f64).sqrt() / (1.0 + visits)
+    }
+
+    pub fn mean_reward(&self) -> f64 {
+        if self.visits == 0 {
+            0.0
+        } else {
+            self.total_reward / self.visits as f64
+        }
+    }
+
+    pub fn untried(&self, legal_moves: &[Card]) -> Vec<Card> {
+        legal_moves
+            .iter()
+            .filter(|card| !self.children.contains_key(card))
+            .copied()
+            .collect()
+    }
+
+    pub fn mark_available(&mut self, legal_moves: &[Card]) {
+        for card in legal_moves {
+            if let Some(child) = self.children.get_mut(card) {
+                child.availability += 1;
+            }
+        }
+    }
+
+    // Only children legal in the current world compete
+    pub fn select_child(&self, legal_moves: &[Card], c_param: f64) -> Option<Card> {
+        let mut best: Option<(Card, f64)> = None;
+        for card in legal_moves {
+            let Some(child) = self.children.get(card) else {
+                continue;
+            };
+            let ucb = child.ucb_value(c_param);
+            if best.is_none_or(|(_, best_ucb)| ucb > best_ucb) {
+                best = Some((*card, ucb));
+            }
+        }
+        best.map(|(card, _)| card)
+    }
+
+    // PUCT over the legal cards, `priors` in the same order. Cards without a
+    // child yet compete too, one of them is returned to be expanded
+    pub fn select_puct(&self, legal_moves: &[Card], priors: &[f64], c_param: f64) -> Option<Card> {
+        let mut best: Option<(Card, f64)> = None;
+        for (card, prior) in legal_moves.iter().zip(priors) {
+            let value = match self.children.get(card) {
+                Some(child) => child.puct_value(*prior, c_param),
+                // Scored as the child expanding it, available this once
+                None => {
+                    let mut child = ISMCTSNode::new(None);
+                    child.availability = 1;
+                    child.puct_value(*prior, c_param)
+                }
+            };
+            if best.is_none_or(|(_, best_value)| value > best_value) {
+                best = Some((*card, value));
+            }
+        }
+        best.map(|(card, _)| card)
+    }
+
+    // Adds the root statistics of another tree, deeper nodes are not merged
+    pub fn merge_root(&mut self, other: &ISMCTSNode) {
+        self.visits += other.visits;
+        self.availability += other.availability;
+        self.total_reward += other.total_reward;
+        self.total_squared_reward += other.total_squared_reward;
+        self.wins += other.wins;
+        for (card, other_child) in &other.children {
+            let child = self
+                .children
+                .entry(*card)
+                .or_insert_with(|| Box::new(ISMCTSNode::new(other_child.player)));
+            child.visits += other_child.visits;
+            child.availability += other_child.availability;
+            child.total_reward += other_child.total_reward;
+            child.total_squared_reward += other_child.total_squared_reward;
+            child.wins += other_child.wins;
+        }
+    }
+
+    pub fn backpropagate(&mut self, taker_reward: f64, taker_won: bool, taker: Option<u8>) {
+        self.visits += 1;
+        if let Some(player) = self.player {
+            let reward = camp_reward(taker_reward, player, taker);
+            self.total_reward += reward;
+            self.total_squared_reward += reward * reward;
+            if taker_won == (taker == Some(player)) {
+                self.wins += 1;
+            }
+        }
+    }
+}
@@ -0,0 +1,612 @@
+use std::collections::{btree_map::Entry, HashSet};
+
+use rand::{seq::IndexedRandom, Rng};
+use rayon::prelude::*;
+
+use crate::business::analysis::analysis_error::AnalysisError;
+use crate::business::analysis::prior_policy::PriorPolicy;
+use crate::business::analysis::search_control::remaining_plays;
+use crate::business::analysis::search_report::{
+    confidence_interval, CardReport, SearchReport, SearchStatistics,
+};
+use crate::business::analysis::simulate_random_playout::{
+    simulate_playout, simulate_playout_to_depth,
+};
+
+use crate::business::game_engine::{
+    engine_error::EngineError,
+    game_state::GameState,
+    known_game_state::PlayerConstraint,
+    seed::{derive_seed, rng_from_seed, TarotRng},
+    shared_game_state::SharedGameState,
+};
+use crate::business::{Card, KnownGameState};
+
+use super::ismcts_node::ISMCTSNode;
+use super::parameters::{MCTSParameters, Parallelism};
+
+// Iterations each tree runs between two looks at the search limits
+const ROOT_ROUND: usize = 8;
+
+// Draws the world of one iteration, `None` when it has to be skipped
+pub type WorldSampler<'a> =
+    dyn Fn(&mut TarotRng) -> Result<Option<GameState>, AnalysisError> + Sync + 'a;
+
+// Single observer information set MCTS: a new world is sampled at every
+// iteration and only walks the part of the tree it allows
+pub fn ismcts_search<R: Rng + ?Sized>(
+    known: &KnownGameState,
+    parameters: &MCTSParameters,
+    rng: &mut R,
+) -> Result<Card, AnalysisError> {
+    let (trees, statistics) = build_trees(known, parameters, Vec::new(), rng)?;
+    let allowed = known
+        .player_state
+        .sorted_cards_allowed(&known.shared_state.current_or_new_trick());
+    Ok(tree_report(&trees, &allowed, statistics)?.best_card)
+}
+
+// The most visited card is the best one, or the first allowed card when the
+// search stopped before its first iteration
+pub fn tree_report(
+    trees: &[ISMCTSNode],
+    allowed: &[Card],
+    statistics: SearchStatistics,
+) -> Result<SearchReport, AnalysisError> {
+    let root = merge_roots(trees);
+    let cards = allowed
+        .iter()
+        .map(|card| match root.children.get(card) {
+            Some(child) if child.visits > 0 => CardReport {
+                card: *card,
+                visits: child.visits,
+                mean_reward: child.mean_reward(),
+                confidence_interval: confidence_interval(
+                    child.visits,
+                    child.total_reward,
+                    child.total_squared_reward,
+                ),
+                win_probability: Some(child.wins as f64 / child.visits as f64),
+                principal_variation: principal_variation(trees, card),
+            },
+            _ => CardReport::unvisited(*card),
+        })
+        .collect();
+    let best_card = match best_child(&root) {
+        Some((card, _, _)) => card,
+        None => *allowed.first().ok_or(AnalysisError::NoCardToPlay)?,
+    };
+    Ok(SearchReport {
+        best_card,
+        cards,
+        statistics,
+    })
+}
+
+// Most visited line after the card, in the tree which visited it the most
+fn principal_variation(trees: &[ISMCTSNode], card: &Card) -> Vec<Card> {
+    let mut variation = vec![*card];
+    let Some(mut node) = trees
+        .iter()
+        .filter_map(|tree| tree.children.get(card))
+        .max_by_key(|child| child.visits)
+    else {
+        return variation;
+    };
+    while let Some((card, child)) = node.children.iter().max_by_key(|(_, child)| child.visits) {
+        variation.push(*card);
+        node = child;
+    }
+    variation
+}
+
+pub fn build_tree<R: Rng + ?Sized>(
+    known: &KnownGameState,
+    parameters: &MCTSParameters,
+    rng: &mut R,
+) -> Result<ISMCTSNode, AnalysisError> {
+    let (mut trees, _) = build_trees(known, parameters, Vec::new(), rng)?;
+    if trees.len() == 1 {
+        Ok(trees.remove(0))
+    } else {
+        Ok(merge_roots(&trees))
+    }
+}
+
+// Searches further from previous trees when there is one per tree to grow
+pub fn build_trees<R: Rng + ?Sized>(
+    known: &KnownGameState,
+    parameters: &MCTSParameters,
+    previous: Vec<ISMCTSNode>,
+    rng: &mut R,
+) -> Result<(Vec<ISMCTSNode>, SearchStatistics), AnalysisError> {
+    let sample = |rng: &mut TarotRng| match known.possible_random_full_state_v2_with_rng(rng) {
+        Ok(world) => Ok(Some(world)),
+        // No deal fits what the player knows, the iteration is skipped
+        Err(EngineError::HandGenerationNotPossible(_)) => Ok(None),
+        Err(e) => Err(AnalysisError::Engine(e)),
+    };
+    grow_trees(
+        &sample,
+        &known.shared_state,
+        parameters,
+        previous,
+        rng.random(),
+    )
+}
+
+// Statistics of the root and its children summed over the trees
+pub fn merge_roots(roots: &[ISMCTSNode]) -> ISMCTSNode {
+    let mut merged = ISMCTSNode::new(None);
+    for root in roots {
+        merged.merge_root(root);
+    }
+    merged
+}
+
+// Moves the root down along the cards played since the tree was built, and
+// drops the cards their players are now known not to have
+pub fn advance_tree(
+    root: ISMCTSNode,
+    plays: &[(u8, Card)],
+    known: &KnownGameState,
+) -> Option<ISMCTSNode> {
+    let mut root = root;
+    for (_, card) in plays {
+        root = *root.children.remove(card)?;
+    }
+    let constraints: Vec<PlayerConstraint> = (0..4)
+        .map(|player| known.get_known_constraints(player))
+        .collect::<Result<_, _>>()
+        .ok()?;
+    let constraints: [PlayerConstraint; 4] = constraints.try_into().ok()?;
+    let left_to_play = known.shared_state.cards_left_to_play();
+    let possible: Vec<HashSet<Card>> = (0..4)
+        .map(|player| {
+            if player == known.player_index as usize {
+                known.player_state.hand.clone()
+            } else {
+                let mut cards = known.possible_cards(&constraints, &left_to_play, player);
+                cards.extend(constraints[player].known_cards.iter().copied());
+                cards
+            }
+        })
+        .collect();
+    prune(&mut root, &possible);
+    Some(root)
+}
+
+fn prune(node: &mut ISMCTSNode, possible: &[HashSet<Card>]) {
+    node.children.retain(|card, child| {
+        child
+            .player
+            .is_none_or(|player| possible[player as usize].contains(card))
+    });
+    for child in node.children.values_mut() {
+        prune(child, possible);
+    }
+}
+
+// Most visited card, with its visits and mean reward
+pub fn best_child(root: &ISMCTSNode) -> Option<(Card, usize, f64)> {
+    root.children
+        .iter()
+        .max_by_key(|(_, node)| node.visits)
+        .map(|(card, node)| (*card, node.visits, node.mean_reward()))
+}
+
+pub fn grow_trees(
+    sample: &WorldSampler,
+    shared_state: &SharedGameState,
+    parameters: &MCTSParameters,
+    previous: Vec<ISMCTSNode>,
+    seed: u64,
+) -> Result<(Vec<ISMCTSNode>, SearchStatistics), AnalysisError> {
+    parameters.install(|| {
+        let trees = match parameters.parallelism {
+            Parallelism::Root { trees } => trees.max(1),
+            _ => 1,
+        };
+        let batch = match parameters.parallelism {
+            Parallelism::Sequential => 1,
+            Parallelism::Root { .. } => ROOT_ROUND,
+            Parallelism::Tree { batch } => batch.max(1),
+        };
+        let mut roots: Vec<(ISMCTSNode, usize)> = if previous.len() == trees {
+            previous.into_iter().map(|root| (root, 0)).collect()
+        } else {
+            (0..trees).map(|_| (ISMCTSNode::new(None), 0)).collect()
+        };
+        let shares: Vec<usize> = (0..trees)
+            .map(|tree| {
+                parameters.iterations / trees + (tree < parameters.iterations % trees) as usize
+            })
+            .collect();
+        let merged = |roots: &[(ISMCTSNode, usize)]| {
+            let mut merged = ISMCTSNode::new(None);
+            for (root, _) in roots {
+                merged.merge_root(root);
+            }
+            merged
+        };
+        let mut clock = parameters.control.start();
+        let taker = shared_state.taker;
+        let mut determinizations = 0;
+        while roots
+            .iter()
+            .zip(&shares)
+            .any(|((_, done), share)| done < share)
+            && !clock.should_stop()
+        {
+            let batches: Result<Vec<(usize, usize, usize)>, AnalysisError> = roots
+                .par_iter_mut()
+                .zip(&shares)
+                .enumerate()
+                .map(|(tree, ((root, done), share))| {
+                    let count = batch.min(share - *done);
+                    let tree_seed = if trees == 1 {
+                        seed
+                    } else {
+                        derive_seed(seed, tree as u64)
+                    };
+                    let (worlds, nodes) =
+                        run_batch(root, sample, parameters, taker, tree_seed, *done, count)?;
+                    *done += count;
+                    Ok((count, worlds, nodes))
+                })
+                .collect();
+            let batches = batches?;
+            let iterations: usize = batches.iter().map(|(count, _, _)| count).sum();
+            determinizations += batches.iter().map(|(_, worlds, _)| worlds).sum::<usize>();
+            let nodes = batches.iter().map(|(_, _, nodes)| nodes).sum();
+            clock.record_iterations(iterations, nodes);
+            if trees == 1 {
+                clock.report(|| best_child(&roots[0].0));
+            } else {
+                clock.report(|| best_child(&merged(&roots)));
+            }
+        }
+        if trees == 1 {
+            clock.report_now(|| best_child(&roots[0].0));
+        } else {
+            clock.report_now(|| best_child(&merged(&roots)));
+        }
+        let statistics = clock.statistics(determinizations);
+        Ok((
+            roots.into_iter().map(|(root, _)| root).collect(),
+            statistics,
+        ))
+    })
+}
+
+// Worlds are drawn and played out in parallel, but selections are made one
+// after the other so that the virtual losses of the previous ones are seen.
+// Returns the number of worlds which could be drawn and the cards played
+fn run_batch(
+    root: &mut ISMCTSNode,
+    sample: &WorldSampler,
+    parameters: &MCTSParameters,
+    taker: Option<u8>,
+    seed: u64,
+    first_iteration: usize,
+    count: usize,
+) -> Result<(usize, usize), AnalysisError> {
+    let worlds: Vec<Option<(GameState, TarotRng)>> = (first_iteration..first_iteration + count)
+        .into_par_iter()
+        .map(|iteration| {
+            let mut rng = rng_from_seed(derive_seed(seed, iteration as u64));
+            Ok(sample(&mut rng)?.map(|world| (world, rng)))
+        })
+        .collect::<Result<_, AnalysisError>>()?;
+    let mut selections = Vec::with_capacity(count);
+    let mut nodes = 0;
+    for (mut world, mut rng) in worlds.into_iter().flatten() {
+        nodes += remaining_plays(&world.shared_state);
+        let path = select(
+            root,
+            &mut world,
+            parameters.c_param,
+            parameters.priors.as_deref(),
+            &mut rng,
+        )?;
+        selections.push((path, world, rng));
+    }
+    let results: Vec<(f64, bool)> = selections
+        .par_iter_mut()
+        .map(|(_, world, rng)| playout(world, parameters, rng))
+        .collect::<Result<_, AnalysisError>>()?;
+    // Cards played in the tree and in the playouts, cut playouts stop early
+    for ((path, world, _), (result, taker_won)) in selections.iter().zip(results) {
+        nodes -= remaining_plays(&world.shared_state);
+        backpropagate(root, path, result, taker_won, taker);
+    }
+    Ok((selections.len(), nodes))
+}
+
+// The taker's reward and whether the contract is made, estimated when the
+// playout stops before the end of the deal
+fn playout(
+    world: &mut GameState,
+    parameters: &MCTSParameters,
+    rng: &mut TarotRng,
+) -> Result<(f64, bool), AnalysisError> {
+    match parameters.playout_depth {
+        Some(depth) => simulate_playout_to_depth(world, parameters.playout.as_ref(), depth, rng)?,
+        None => {
+            simulate_playout(world, parameters.playout.as_ref(), rng)?;
+        }
+    }
+    if !world.shared_state.finished() {
+        let taker_points = parameters.evaluator.taker_points(world)?;
+        return parameters
+            .reward
+            .estimated_taker_reward(world, taker_points);
+    }
+    let taker_won = world
+        .final_score()
+        .map_err(AnalysisError::Engine)?
+        .contract_won;
+    Ok((parameters.reward.taker_reward(world)?, taker_won))
+}
+
+// Walks down the tree in the given world and expands one node, the cards
+// played on the way are returned
+fn select<R: Rng + ?Sized>(
+    root: &mut ISMCTSNode,
+    world: &mut GameState,
+    c_param: f64,
+    priors: Option<&dyn PriorPolicy>,
+    rng: &mut R,
+) -> Result<Vec<Card>, AnalysisError> {
+    let mut path = Vec::new();
+    let mut node = root;
+    node.virtual_loss += 1;
+    while let Some(player) = world.shared_state.next_to_play() {
+        let legal_moves = world.legal_moves(player);
+        node.mark_available(&legal_moves);
+        let card = match priors {
+            Some(priors) => {
+                let priors = priors.priors(world, player, &legal_moves)?;
+                node.select_puct(&legal_moves, &priors, c_param)
+            }
+            None => node
+                .untried(&legal_moves)
+                .choose(rng)
+                .copied()
+                .or_else(|| node.select_child(&legal_moves, c_param)),
+        }
+        .ok_or(AnalysisError::NoCardToPlay)?;
+        world
+            .play_card(player, &card)
+            .map_err(AnalysisError::Engine)?;
+        path.push(card);
+        if let Entry::Vacant(entry) = node.children.entry(card) {
+            // Expansion
+            let mut child = ISMCTSNode::new(Some(player));
+            child.availability = 1;
+            child.virtual_loss = 1;
+            entry.insert(Box::new(child));
+            break;
+        }
+        // Selection
+        node = node
+            .children
+            .get_mut(&card)
+            .ok_or(AnalysisError::NoCardToPlay)?;
+        node.virtual_loss += 1;
+    }
+    Ok(path)
+}
+
+fn backpropagate(
+    root: &mut ISMCTSNode,
+    path: &[Card],
+    result: f64,
+    taker_won: bool,
+    taker: Option<u8>,
+) {
+    let mut node = root;
+    node.virtual_loss -= 1;
+    node.backpropagate(result, taker_won, taker);
+    for card in path {
+        let Some(child) = node.children.get_mut(card) else {
+            return;
+        };
+        node = child;
+        node.virtual_loss -= 1;
+        node.backpropagate(result, taker_won, taker);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+    use std::sync::Arc;
+
+    use crate::business::{
+        analysis::{network::Network, reward::RewardFunction},
+        GameType,
+    };
+
+    fn check_counts(node: &ISMCTSNode, is_root: bool) {
+        let children_visits: usize = node.children.values().map(|child| child.visits).sum();
+        // Every visit but the expanding one goes down to a child
+        assert_eq!(node.visits, children_visits + if is_root { 0 } else { 1 });
+        assert_eq!(node.virtual_loss, 0);
+        for child in node.children.values() {
+            assert!(child.visits <= child.availability);
+            assert!(child.availability <= node.visits);
+            check_counts(child, false);
+        }
+    }
+
+    fn known_state() -> KnownGameState {
+        let mut rng = TarotRng::seed_from_u64(33);
+        let mut state = GameState::random_init_with_rng(&mut rng).unwrap();
+        state
+            .shared_state
+            .bid(2, Some(GameType::GardeSans { chelem: false }))
+            .unwrap();
+        let player = state.shared_state.next_to_play().unwrap();
+        KnownGameState::from_omniscient(&state, player)
+    }
+
+    #[test]
+    fn test_tree_counts_are_consistent() {
+        let known = known_state();
+        for parallelism in [Parallelism::Sequential, Parallelism::Tree { batch: 8 }] {
+            let mut parameters = MCTSParameters::new(300, 1.41);
+            parameters.parallelism = parallelism;
+            let mut rng = TarotRng::seed_from_u64(33);
+            let root = build_tree(&known, &parameters, &mut rng).unwrap();
+            assert!(root.visits > 0);
+            check_counts(&root, true);
+            // Only cards the observer may play are at the root
+            let allowed = known
+                .player_state
+                .sorted_cards_allowed(&known.shared_state.current_or_new_trick());
+            assert!(root.children.keys().all(|card| allowed.contains(card)));
+        }
+    }
+
+    #[test]
+    fn test_cut_playouts_are_evaluated() {
+        let known = known_state();
+        for depth in [0, 8] {
+            let mut parameters = MCTSParameters::new(200, 1.41);
+            parameters.playout_depth = Some(depth);
+            parameters.reward = RewardFunction::ContractMargin;
+            let mut rng = TarotRng::seed_from_u64(48);
+            let root = build_tree(&known, &parameters, &mut rng).unwrap();
+            assert_eq!(root.visits, 200);
+            check_counts(&root, true);
+            assert!(root
+                .children
+                .values()
+                .all(|child| (0.0..=child.visits as f64).contains(&child.total_reward)));
+        }
+    }
+
+    // Almost every chance on one card, an even share elsewhere
+    struct Favourite(Card);
+
+    impl PriorPolicy for Favourite {
+        fn priors(
+            &self,
+            _state: &GameState,
+            _player: u8,
+            legal_moves: &[Card],
+        ) -> Result<Vec<f64>, AnalysisError> {
+            if !legal_moves.contains(&self.0) {
+                return Ok(vec![1.0 / legal_moves.len() as f64; legal_moves.len()]);
+            }
+            let others = 0.02 / (legal_moves.len() - 1) as f64;
+            Ok(legal_moves
+                .iter()
+                .map(|card| if *card == self.0 { 0.98 } else { others })
+                .collect())
+        }
+    }
+
+    #[test]
+    fn test_priors_guide_the_selection() {
+        let known = known_state();
+        let allowed = known
+            .player_state
+            .sorted_cards_allowed(&known.shared_state.current_or_new_trick());
+        let favourite = allowed[allowed.len() / 2];
+        let network = Network::new(&[16], 50);
+        for parallelism in [Parallelism::Sequential, Parallelism::Tree { batch: 8 }] {
+            let mut parameters = MCTSParameters::new(200, 1.41);
+            parameters.parallelism = parallelism;
+            parameters.priors = Some(Arc::new(network.clone()));
+            let mut rng = TarotRng::seed_from_u64(50);
+            let root = build_tree(&known, &parameters, &mut rng).unwrap();
+            assert_eq!(root.visits, 200);
+            check_counts(&root, true);
+
+            parameters.priors = Some(Arc::new(Favourite(favourite)));
+            let root = build_tree(&known, &parameters, &mut rng).unwrap();
+            check_counts(&root, true);
+            assert!(root.children[&favourite].visits > root.visits / 2);
+        }
+    }
+
+    #[test]
+    fn test_parallel_searches_do_not_depend_on_threads() {
+        let known = known_state();
+        for parallelism in [
+            Parallelism::Root { trees: 4 },
+            Parallelism::Tree { batch: 8 },
+        ] {
+            let statistics: Vec<Vec<(Card, usize)>> = [1, 4]
+                .into_iter()
+                .map(|threads| {
+                    let mut parameters = MCTSParameters::new(200, 1.41).with_threads(threads);
+                    parameters.parallelism = parallelism;
+                    let mut rng = TarotRng::seed_from_u64(36);
+                    let root = build_tree(&known, &parameters, &mut rng).unwrap();
+                    root.children
+                        .iter()
+                        .map(|(card, child)| (*card, child.visits))
+                        .collect()
+                })
+                .collect();
+            assert_eq!(statistics[0], statistics[1]);
+            assert_eq!(
+                statistics[0]
+                    .iter()
+                    .map(|(_, visits)| visits)
+                    .sum::<usize>(),
+                200
+            );
+        }
+    }
+
+    #[test]
+    fn test_advanced_tree_follows_the_deal() {
+        let mut rng = TarotRng::seed_from_u64(37);
+        let mut state = GameState::random_init_with_rng(&mut rng).unwrap();
+        state
+            .shared_state
+            .bid(1, Some(GameType::Garde { chelem: false }))
+            .unwrap();
+        let observer = state.shared_state.next_to_play().unwrap();
+        let known = KnownGameState::from_omniscient(&state, observer);
+        let parameters = MCTSParameters::new(400, 1.41);
+        let (trees, _) = build_trees(&known, &parameters, Vec::new(), &mut rng).unwrap();
+        let history = known.shared_state.play_history();
+
+        // Every player plays the most visited card, as long as it is in the tree
+        let mut node = &trees[0];
+        loop {
+            let player = state.shared_state.next_to_play().unwrap();
+            let card = state
+                .legal_moves(player)
+                .into_iter()
+                .filter_map(|card| node.children.get(&card).map(|child| (card, child)))
+                .max_by_key(|(_, child)| child.visits);
+            let Some((card, child)) = card else { break };
+            state.play_card(player, &card).unwrap();
+            node = child;
+            if state.shared_state.next_to_play() == Some(observer) {
+                break;
+            }
+        }
+        let visits = node.visits;
+        let known = KnownGameState::from_omniscient(&state, observer);
+        let plays = known.shared_state.play_history()[history.len()..].to_vec();
+        let root = advance_tree(trees.into_iter().next().unwrap(), &plays, &known).unwrap();
+        assert_eq!(root.visits, visits);
+        let allowed = known
+            .player_state
+            .sorted_cards_allowed(&known.shared_state.current_or_new_trick());
+        if state.shared_state.next_to_play() == Some(observer) {
+            assert!(root.children.keys().all(|card| allowed.contains(card)));
+        }
+        let (grown, _) = build_trees(&known, &parameters, vec![root], &mut rng).unwrap();
+        assert_eq!(grown[0].visits, visits + 400);
+    }
+}
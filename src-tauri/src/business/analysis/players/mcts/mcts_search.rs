@@ -1,40 +1,40 @@
+use rand::Rng;
+
 use crate::business::analysis::analysis_error::AnalysisError;
-use crate::business::analysis::simulate_random_playout::simulate_random_playout;
-use crate::business::game_engine::game_state::GameState;
+use crate::business::game_engine::{game_state::GameState, seed::TarotRng};
 use crate::business::Card;
 
-use super::mcts_node::MCTSNode;
+use crate::business::analysis::search_report::SearchReport;
+
+use super::ismcts_search::{grow_trees, tree_report};
+use super::parameters::MCTSParameters;
 
-pub fn mcts_search(
+// With every card known, every iteration plays in the same world
+pub fn mcts_search<R: Rng + ?Sized>(
     root_state: GameState,
-    iterations: usize,
-    c_param: f64,
+    parameters: &MCTSParameters,
+    rng: &mut R,
 ) -> Result<Card, AnalysisError> {
-    let mut root = MCTSNode::new(root_state);
-    for _ in 0..iterations {
-        let mut node = &mut root;
-        // Selection
-        while !node.children.is_empty() && !node.state.shared_state.finished() {
-            let (_, child) = node.select_child(c_param);
-            node = child;
-        }
-        // Expansion
-        if !node.state.shared_state.finished() {
-            let (card, mut new_node) = node.expand()?;
-            let mut sim_state = new_node.state.clone();
-            let result = simulate_random_playout(&mut sim_state)?;
-            new_node.backpropagate(result);
-            node.children.insert(card, Box::new(new_node));
-        } else {
-            // simulation on the leaf node
-            let mut sim_state = node.state.clone();
-            let result = simulate_random_playout(&mut sim_state)?;
-            node.backpropagate(result);
-        }
-    }
-    root.children
-        .iter()
-        .max_by_key(|(_, node)| (node.total_score as f64 / node.visits as f64 * 1000.0) as i64)
-        .map(|(card, _)| *card)
-        .ok_or(AnalysisError::NoCardToPlay)
+    Ok(mcts_analysis(root_state, parameters, rng)?.best_card)
+}
+
+pub fn mcts_analysis<R: Rng + ?Sized>(
+    root_state: GameState,
+    parameters: &MCTSParameters,
+    rng: &mut R,
+) -> Result<SearchReport, AnalysisError> {
+    let legal_moves = root_state
+        .shared_state
+        .next_to_play()
+        .map(|player| root_state.legal_moves(player))
+        .unwrap_or_default();
+    let sample = |_: &mut TarotRng| Ok(Some(root_state.clone()));
+    let (trees, statistics) = grow_trees(
+        &sample,
+        &root_state.shared_state,
+        parameters,
+        Vec::new(),
+        rng.random(),
+    )?;
+    tree_report(&trees, &legal_moves, statistics)
 }
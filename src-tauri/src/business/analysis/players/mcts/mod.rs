@@ -1,5 +1,8 @@
-pub mod mcts_node;
+pub mod ismcts_node;
+pub mod ismcts_search;
 pub mod mcts_search;
+pub mod parameters;
 pub mod player;
 
+pub use parameters::{MCTSParameters, Parallelism};
 pub use player::MCTS;
@@ -0,0 +1,84 @@
+use std::sync::Arc;
+
+use rayon::{ThreadPool, ThreadPoolBuilder};
+
+use crate::business::analysis::{
+    analysis_error::AnalysisError,
+    evaluator::{Evaluator, HandCraftedEvaluator},
+    playout_policy::{PlayoutPolicy, UniformPolicy},
+    prior_policy::PriorPolicy,
+    reward::RewardFunction,
+    search_control::SearchControl,
+};
+
+// Results only depend on the parallelism and the seed, never on the number of
+// threads actually running the search
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Parallelism {
+    #[default]
+    Sequential,
+    // Independent trees on different determinizations, merged at the root
+    Root {
+        trees: usize,
+    },
+    // One shared tree: `batch` iterations are selected together, a virtual loss
+    // spreading them, and their playouts run in parallel
+    Tree {
+        batch: usize,
+    },
+}
+
+#[derive(Clone)]
+pub struct MCTSParameters {
+    pub iterations: usize,
+    pub c_param: f64,
+    pub reward: RewardFunction,
+    pub playout: Arc<dyn PlayoutPolicy>,
+    pub parallelism: Parallelism,
+    // Dedicated pool built once for every search, the global rayon pool is
+    // used otherwise
+    pub pool: Option<Arc<ThreadPool>>,
+    pub control: SearchControl,
+    // Cards played out before the evaluator estimates the rest of the deal,
+    // every playout goes to the end otherwise
+    pub playout_depth: Option<usize>,
+    pub evaluator: Arc<dyn Evaluator>,
+    // Selection by PUCT with these priors, by UCB over every card otherwise
+    pub priors: Option<Arc<dyn PriorPolicy>>,
+}
+
+impl MCTSParameters {
+    pub fn new(iterations: usize, c_param: f64) -> Self {
+        Self {
+            iterations,
+            c_param,
+            reward: RewardFunction::default(),
+            playout: Arc::new(UniformPolicy),
+            parallelism: Parallelism::default(),
+            pool: None,
+            control: SearchControl::default(),
+            playout_depth: None,
+            evaluator: Arc::new(HandCraftedEvaluator),
+            priors: None,
+        }
+    }
+
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .expect("Could not start the search threads");
+        self.pool = Some(Arc::new(pool));
+        self
+    }
+
+    pub fn install<T: Send>(
+        &self,
+        op: impl FnOnce() -> Result<T, AnalysisError> + Send,
+    ) -> Result<T, AnalysisError> {
+        match &self.pool {
+            None => op(),
+            Some(pool) => pool.install(op),
+        }
+    }
+}
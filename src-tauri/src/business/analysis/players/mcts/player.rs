@@ -1,21 +1,216 @@
+use std::{collections::HashSet, sync::Arc};
+
 use crate::business::{
-    analysis::{analysis_error::AnalysisError, players::mcts::mcts_search::mcts_search},
+    analysis::{
+        analysis_error::AnalysisError,
+        evaluator::Evaluator,
+        players::mcts::{
+            ismcts_node::ISMCTSNode,
+            ismcts_search::{advance_tree, build_trees, tree_report},
+        },
+        playout_policy::PlayoutPolicy,
+        prior_policy::PriorPolicy,
+        reward::RewardFunction,
+        search_control::SearchControl,
+        search_report::{SearchAnalysis, SearchReport},
+    },
+    game_engine::seed::{derive_seed, rng_from_seed},
     Card, KnownGameState, Player,
 };
 
+use super::parameters::{MCTSParameters, Parallelism};
+
 pub struct MCTS {
-    pub n_iterations: usize,
-    pub c_param: f64, //1.41
+    pub parameters: MCTSParameters,
+    // Starts each search from the subtree of the cards played since the last one
+    pub reuse_tree: bool,
+    seed: u64,
+    decisions: u64,
+    // Trees of the last search, with the plays of the deal they started from
+    previous: Option<PreviousSearch>,
+}
+
+struct PreviousSearch {
+    trees: Vec<ISMCTSNode>,
+    history: Vec<(u8, Card)>,
+    // Whose search it was, and the hand then, as the history alone does not
+    // tell deals apart before their first card
+    player: u8,
+    hand: HashSet<Card>,
+}
+
+impl MCTS {
+    pub fn new(n_iterations: usize, c_param: f64, seed: u64) -> Self {
+        Self {
+            parameters: MCTSParameters::new(n_iterations, c_param),
+            reuse_tree: true,
+            seed,
+            decisions: 0,
+            previous: None,
+        }
+    }
+
+    pub fn with_reward(mut self, reward: RewardFunction) -> Self {
+        self.parameters.reward = reward;
+        self
+    }
+
+    pub fn with_playout(mut self, playout: impl PlayoutPolicy + 'static) -> Self {
+        self.parameters.playout = Arc::new(playout);
+        self
+    }
+
+    pub fn with_playout_depth(mut self, depth: usize) -> Self {
+        self.parameters.playout_depth = Some(depth);
+        self
+    }
+
+    pub fn with_evaluator(mut self, evaluator: impl Evaluator + 'static) -> Self {
+        self.parameters.evaluator = Arc::new(evaluator);
+        self
+    }
+
+    pub fn with_priors(mut self, priors: impl PriorPolicy + 'static) -> Self {
+        self.parameters.priors = Some(Arc::new(priors));
+        self
+    }
+
+    pub fn with_control(mut self, control: SearchControl) -> Self {
+        self.parameters.control = control;
+        self
+    }
+
+    pub fn with_parallelism(mut self, parallelism: Parallelism) -> Self {
+        self.parameters.parallelism = parallelism;
+        self
+    }
+
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.parameters = self.parameters.with_threads(threads);
+        self
+    }
+
+    pub fn with_tree_reuse(mut self, reuse_tree: bool) -> Self {
+        self.reuse_tree = reuse_tree;
+        self
+    }
+
+    // Subtrees of the previous search under what was played since, empty when
+    // the deal is not the one it was made for
+    fn reusable_trees(
+        &mut self,
+        known: &KnownGameState,
+        history: &[(u8, Card)],
+    ) -> Vec<ISMCTSNode> {
+        let Some(PreviousSearch {
+            trees,
+            history: previous_history,
+            player,
+            mut hand,
+        }) = self.previous.take()
+        else {
+            return Vec::new();
+        };
+        if !self.reuse_tree
+            || player != known.player_index
+            || !history.starts_with(&previous_history)
+        {
+            return Vec::new();
+        }
+        let plays = &history[previous_history.len()..];
+        for (_, card) in plays.iter().filter(|(by, _)| *by == player) {
+            hand.remove(card);
+        }
+        if hand != known.player_state.hand {
+            return Vec::new();
+        }
+        trees
+            .into_iter()
+            .map(|tree| advance_tree(tree, plays, known).unwrap_or_else(|| ISMCTSNode::new(None)))
+            .collect()
+    }
+}
+
+impl SearchAnalysis for MCTS {
+    fn analyse(&mut self, game_state: &KnownGameState) -> Result<SearchReport, AnalysisError> {
+        let mut rng = rng_from_seed(derive_seed(self.seed, self.decisions));
+        self.decisions += 1;
+        let history = game_state.shared_state.play_history();
+        let previous = self.reusable_trees(game_state, &history);
+        let (trees, statistics) = build_trees(game_state, &self.parameters, previous, &mut rng)?;
+        let allowed = game_state
+            .player_state
+            .sorted_cards_allowed(&game_state.shared_state.current_or_new_trick());
+        let report = tree_report(&trees, &allowed, statistics)?;
+        if self.reuse_tree {
+            self.previous = Some(PreviousSearch {
+                trees,
+                history,
+                player: game_state.player_index,
+                hand: game_state.player_state.hand.clone(),
+            });
+        }
+        Ok(report)
+    }
 }
 
 impl Player for MCTS {
     fn play_a_card(&mut self, game_state: &KnownGameState) -> Result<Card, AnalysisError> {
-        mcts_search(
-            game_state
-                .possible_random_full_state_v2()
-                .map_err(|e| AnalysisError::Engine(e))?,
-            self.n_iterations,
-            self.c_param,
-        )
+        Ok(self.analyse(game_state)?.best_card)
+    }
+
+    fn new_deal(&mut self) {
+        self.previous = None;
+        self.decisions = 0;
+    }
+
+    fn seed(&self) -> Option<u64> {
+        Some(self.seed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::business::{
+        game_engine::{game_state::GameState, seed::TarotRng},
+        GameType,
+    };
+
+    fn first_player_state(seed: u64) -> KnownGameState {
+        let mut state =
+            GameState::random_init_with_rng(&mut TarotRng::seed_from_u64(seed)).unwrap();
+        state.shared_state.dealer = 3;
+        state
+            .shared_state
+            .bid(1, Some(GameType::GardeSans { chelem: false }))
+            .unwrap();
+        KnownGameState::from_omniscient(&state, 0)
+    }
+
+    #[test]
+    fn test_trees_are_only_reused_in_the_same_deal() {
+        let known = first_player_state(37);
+        let mut mcts = MCTS::new(50, 1.41, 37);
+        mcts.analyse(&known).unwrap();
+        assert!(!mcts.reusable_trees(&known, &[]).is_empty());
+
+        // Another deal before its first card has the same empty history
+        mcts.analyse(&known).unwrap();
+        let other = first_player_state(38);
+        assert!(mcts.reusable_trees(&other, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_seed_replays_every_deal() {
+        let known = first_player_state(37);
+        let mut mcts = MCTS::new(50, 1.41, 30);
+        mcts.analyse(&first_player_state(38)).unwrap();
+        mcts.new_deal();
+        let replayed = mcts.analyse(&known).unwrap();
+        let fresh = MCTS::new(50, 1.41, 30).analyse(&known).unwrap();
+        assert_eq!(replayed.cards, fresh.cards);
     }
 }
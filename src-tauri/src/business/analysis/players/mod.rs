@@ -1,3 +1,5 @@
+pub mod expert;
 pub mod mcts;
 pub mod monte_carlo;
+pub mod pimc;
 pub mod random;
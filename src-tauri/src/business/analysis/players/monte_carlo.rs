@@ -1,54 +1,181 @@
+use std::sync::Arc;
+
 use rayon::prelude::*;
-use std::collections::HashSet;
 
 use crate::business::analysis::analysis_error::AnalysisError;
-use crate::business::analysis::simulate_random_playout::simulate_random_playout;
+use crate::business::analysis::playout_policy::{PlayoutPolicy, UniformPolicy};
+use crate::business::analysis::reward::{camp_won, RewardFunction};
+use crate::business::analysis::search_control::{remaining_plays, SearchControl};
+use crate::business::analysis::search_report::{
+    confidence_interval, CardReport, SearchAnalysis, SearchReport,
+};
+use crate::business::analysis::simulate_random_playout::simulate_playout;
+use crate::business::game_engine::engine_error::EngineError;
+use crate::business::game_engine::seed::{derive_seed, rng_from_seed};
 use crate::business::{Card, KnownGameState, Player};
 
-struct MonteCarlo {
-    sims_per_candidate: usize,
+// Samples simulated between two looks at the search limits
+const SAMPLES_PER_BATCH: usize = 16;
+
+pub struct MonteCarlo {
+    pub sims_per_candidate: usize,
+    pub reward: RewardFunction,
+    pub playout: Arc<dyn PlayoutPolicy>,
+    pub control: SearchControl,
+    seed: u64,
+    decisions: u64,
 }
 
-impl Player for MonteCarlo {
-    fn play_a_card(&mut self, known: &KnownGameState) -> Result<Card, AnalysisError> {
-        let allowed: HashSet<&Card> = known.player_state.cards_allowed(
-            &known
-                .shared_state
-                .current_trick
-                .ok_or(AnalysisError::NoCardToPlay)?,
-        );
-        let results: Vec<(Card, usize)> = allowed
-            .into_iter()
-            .filter_map(|card| {
-                let candidate = *card;
-                let total_score: Result<usize, AnalysisError> = (0..self.sims_per_candidate)
+impl MonteCarlo {
+    pub fn new(sims_per_candidate: usize, seed: u64) -> Self {
+        Self {
+            sims_per_candidate,
+            reward: RewardFunction::default(),
+            playout: Arc::new(UniformPolicy),
+            control: SearchControl::default(),
+            seed,
+            decisions: 0,
+        }
+    }
+
+    pub fn with_reward(mut self, reward: RewardFunction) -> Self {
+        self.reward = reward;
+        self
+    }
+
+    pub fn with_playout(mut self, playout: impl PlayoutPolicy + 'static) -> Self {
+        self.playout = Arc::new(playout);
+        self
+    }
+
+    pub fn with_control(mut self, control: SearchControl) -> Self {
+        self.control = control;
+        self
+    }
+}
+
+impl MonteCarlo {
+    // Reward of one candidate on one sampled deal, whether the camp of the
+    // player won and the cards played. The same sample number gives the same
+    // deal to every candidate, None when no deal could be sampled
+    fn simulate(
+        &self,
+        known: &KnownGameState,
+        candidate: &Card,
+        decision_seed: u64,
+        sample: usize,
+    ) -> Result<Option<(f64, bool, usize)>, AnalysisError> {
+        let mut rng = rng_from_seed(derive_seed(decision_seed, sample as u64));
+        let mut full = match known.possible_random_full_state_v2_with_rng(&mut rng) {
+            Ok(full) => full,
+            Err(EngineError::HandGenerationNotPossible(_)) => return Ok(None),
+            Err(error) => return Err(AnalysisError::Engine(error)),
+        };
+        let left = remaining_plays(&full.shared_state);
+        full.play_card(known.player_index, candidate)
+            .map_err(AnalysisError::Engine)?;
+        simulate_playout(&mut full, self.playout.as_ref(), &mut rng)?;
+        Ok(Some((
+            self.reward.reward(&full, known.player_index)?,
+            camp_won(&full, known.player_index)?,
+            left - remaining_plays(&full.shared_state),
+        )))
+    }
+}
+
+// Sums over the samples of one candidate
+#[derive(Debug, Clone, Copy, Default)]
+struct CandidateTotals {
+    samples: usize,
+    reward: f64,
+    squared_reward: f64,
+    wins: usize,
+}
+
+impl SearchAnalysis for MonteCarlo {
+    fn analyse(&mut self, known: &KnownGameState) -> Result<SearchReport, AnalysisError> {
+        let allowed: Vec<Card> = known
+            .player_state
+            .sorted_cards_allowed(&known.shared_state.current_or_new_trick());
+        let decision_seed = derive_seed(self.seed, self.decisions);
+        self.decisions += 1;
+        let mut clock = self.control.start();
+        let mut totals = vec![CandidateTotals::default(); allowed.len()];
+        let mut samples = 0;
+        let best = |totals: &[CandidateTotals]| {
+            allowed
+                .iter()
+                .zip(totals)
+                .filter(|(_, total)| total.samples > 0)
+                .map(|(card, total)| (*card, total.samples, total.reward / total.samples as f64))
+                .max_by(|a, b| a.2.total_cmp(&b.2))
+        };
+        while samples < self.sims_per_candidate && !clock.should_stop() {
+            let batch = samples..(samples + SAMPLES_PER_BATCH).min(self.sims_per_candidate);
+            let mut nodes = 0;
+            for (candidate, total) in allowed.iter().zip(totals.iter_mut()) {
+                let results: Vec<Option<(f64, bool, usize)>> = batch
+                    .clone()
                     .into_par_iter()
-                    .try_fold(
-                        || 0,
-                        |acc, _| {
-                            let mut full = known
-                                .possible_random_full_state_v2()
-                                .map_err(|e| AnalysisError::Engine(e))?;
-                            full.play_card(known.player_index, &candidate)
-                                .map_err(|e| AnalysisError::Engine(e))?;
-                            let val: usize = simulate_random_playout(&mut full)?;
-                            Ok(acc + val)
-                        },
-                    )
-                    .try_reduce(|| 0, |a, b| Ok(a + b));
-
-                total_score
-                    .map(|value| (candidate, value / self.sims_per_candidate))
-                    .ok()
+                    .map(|sample| self.simulate(known, candidate, decision_seed, sample))
+                    .collect::<Result<_, _>>()?;
+                // Summed in order so that the result does not depend on threads,
+                // deals which could not be sampled are skipped
+                for (reward, won, played) in results.into_iter().flatten() {
+                    nodes += played;
+                    total.samples += 1;
+                    total.reward += reward;
+                    total.squared_reward += reward * reward;
+                    total.wins += won as usize;
+                }
+            }
+            samples = batch.end;
+            clock.record_iterations(batch.len(), nodes);
+            clock.report(|| best(&totals));
+        }
+        clock.report_now(|| best(&totals));
+        let best_card = match best(&totals) {
+            Some((card, _, _)) => card,
+            // Stopped before the first sample
+            None => *allowed.first().ok_or(AnalysisError::NoCardToPlay)?,
+        };
+        let cards = allowed
+            .iter()
+            .zip(&totals)
+            .map(|(card, total)| match total.samples {
+                0 => CardReport::unvisited(*card),
+                visits => CardReport {
+                    card: *card,
+                    visits,
+                    mean_reward: total.reward / visits as f64,
+                    confidence_interval: confidence_interval(
+                        visits,
+                        total.reward,
+                        total.squared_reward,
+                    ),
+                    win_probability: Some(total.wins as f64 / visits as f64),
+                    principal_variation: vec![*card],
+                },
             })
             .collect();
+        Ok(SearchReport {
+            best_card,
+            cards,
+            statistics: clock.statistics(samples),
+        })
+    }
+}
+
+impl Player for MonteCarlo {
+    fn play_a_card(&mut self, known: &KnownGameState) -> Result<Card, AnalysisError> {
+        Ok(self.analyse(known)?.best_card)
+    }
 
-        // pick best
-        let best = results
-            .into_iter()
-            .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
-            .ok_or(AnalysisError::NoCardToPlay)?;
+    fn new_deal(&mut self) {
+        self.decisions = 0;
+    }
 
-        Ok(best.0)
+    fn seed(&self) -> Option<u64> {
+        Some(self.seed)
     }
 }
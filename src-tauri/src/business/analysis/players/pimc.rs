@@ -0,0 +1,387 @@
+use rayon::prelude::*;
+
+use crate::business::analysis::analysis_error::AnalysisError;
+use crate::business::analysis::double_dummy::DoubleDummySolver;
+use crate::business::analysis::reward::{camp_reward, RewardFunction, TOTAL_POINTS};
+use crate::business::analysis::search_control::SearchControl;
+use crate::business::analysis::search_report::{
+    confidence_interval, CardReport, SearchAnalysis, SearchReport, SearchStatistics,
+};
+use crate::business::game_engine::game_state::GameState;
+use crate::business::game_engine::seed::{derive_seed, rng_from_seed};
+use crate::business::{Card, KnownGameState, Player};
+
+// Each solver keeps its table from one batch to the next, and its memory from
+// one decision to the next
+const TABLE_CAPACITY: usize = 1 << 18;
+// Worlds solved in parallel between two looks at the search limits
+const WORLDS_PER_BATCH: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PimcAggregation {
+    // Best mean result over the sampled worlds
+    Average,
+    // Card which is the best in the largest number of worlds
+    Vote,
+}
+
+pub struct Pimc {
+    pub samples: usize,
+    pub aggregation: PimcAggregation,
+    pub control: SearchControl,
+    seed: u64,
+    decisions: u64,
+    solvers: Vec<DoubleDummySolver>,
+}
+
+impl Pimc {
+    pub fn new(samples: usize, seed: u64) -> Self {
+        Self {
+            samples,
+            aggregation: PimcAggregation::Average,
+            control: SearchControl::default(),
+            seed,
+            decisions: 0,
+            solvers: (0..WORLDS_PER_BATCH)
+                .map(|_| DoubleDummySolver::with_capacity(TABLE_CAPACITY))
+                .collect(),
+        }
+    }
+
+    pub fn with_aggregation(mut self, aggregation: PimcAggregation) -> Self {
+        self.aggregation = aggregation;
+        self
+    }
+
+    pub fn with_control(mut self, control: SearchControl) -> Self {
+        self.control = control;
+        self
+    }
+}
+
+enum World {
+    TimedOut {
+        nodes: usize,
+    },
+    // Final taker points for each candidate, and whether they make the
+    // contract
+    Solved {
+        values: Vec<(usize, bool)>,
+        nodes: usize,
+    },
+}
+
+// Worlds the solver could not finish in time are dropped rather than
+// estimated, guesses would weigh as much as exact values
+fn solve_world(world: &GameState, solver: &mut DoubleDummySolver) -> Result<World, AnalysisError> {
+    let start_nodes = solver.nodes();
+    let result = solver.evaluate_moves(world);
+    let nodes = (solver.nodes() - start_nodes) as usize;
+    match result {
+        Ok(values) => Ok(World::Solved {
+            // The oudlers of the world set the target
+            values: values
+                .into_iter()
+                .map(|(_, value)| {
+                    let (_, made) =
+                        RewardFunction::WinLoss.estimated_taker_reward(world, value as f64)?;
+                    Ok((value, made))
+                })
+                .collect::<Result<_, AnalysisError>>()?,
+            nodes,
+        }),
+        Err(AnalysisError::Timeout) => Ok(World::TimedOut { nodes }),
+        Err(error) => Err(error),
+    }
+}
+
+// Sums over the solved worlds, by candidate
+struct WorldTotals {
+    solved: usize,
+    // Points of the camp of the player
+    points: Vec<f64>,
+    votes: Vec<usize>,
+    // Share of the points won by the camp of the player, as a reward
+    rewards: Vec<(f64, f64)>,
+    wins: Vec<usize>,
+}
+
+impl WorldTotals {
+    fn new(candidates: usize) -> Self {
+        Self {
+            solved: 0,
+            points: vec![0.0; candidates],
+            votes: vec![0; candidates],
+            rewards: vec![(0.0, 0.0); candidates],
+            wins: vec![0; candidates],
+        }
+    }
+
+    fn add(&mut self, values: &[(usize, bool)], known: &KnownGameState) {
+        // Values are seen from the camp of the player
+        let for_player = |taker_points: usize| {
+            if known.shared_state.taker == Some(known.player_index) {
+                taker_points as f64
+            } else {
+                -(taker_points as f64)
+            }
+        };
+        let best = values
+            .iter()
+            .map(|&(value, _)| for_player(value))
+            .fold(f64::MIN, f64::max);
+        let is_taker = known.shared_state.taker == Some(known.player_index);
+        for (index, &(value, made)) in values.iter().enumerate() {
+            self.points[index] += for_player(value);
+            if for_player(value) == best {
+                self.votes[index] += 1;
+            }
+            let reward = camp_reward(
+                value as f64 / TOTAL_POINTS,
+                known.player_index,
+                known.shared_state.taker,
+            );
+            self.rewards[index].0 += reward;
+            self.rewards[index].1 += reward * reward;
+            self.wins[index] += (made == is_taker) as usize;
+        }
+        self.solved += 1;
+    }
+
+    // Index of the best candidate, none before the first solved world
+    fn best(&self, aggregation: PimcAggregation) -> Option<usize> {
+        (0..self.points.len())
+            .filter(|_| self.solved > 0)
+            .max_by(|&a, &b| {
+                let by_votes = self.votes[a].cmp(&self.votes[b]);
+                let by_points = self.points[a].total_cmp(&self.points[b]);
+                match aggregation {
+                    PimcAggregation::Average => by_points,
+                    PimcAggregation::Vote => by_votes.then(by_points),
+                }
+                // Lower cards are preferred on ties
+                .then(b.cmp(&a))
+            })
+    }
+
+    fn report(&self, index: usize, card: Card) -> CardReport {
+        if self.solved == 0 {
+            return CardReport::unvisited(card);
+        }
+        let (total, total_squared) = self.rewards[index];
+        CardReport {
+            card,
+            visits: self.solved,
+            mean_reward: total / self.solved as f64,
+            confidence_interval: confidence_interval(self.solved, total, total_squared),
+            win_probability: Some(self.wins[index] as f64 / self.solved as f64),
+            principal_variation: vec![card],
+        }
+    }
+}
+
+impl SearchAnalysis for Pimc {
+    fn analyse(&mut self, known: &KnownGameState) -> Result<SearchReport, AnalysisError> {
+        let candidates: Vec<Card> = known
+            .player_state
+            .sorted_cards_allowed(&known.shared_state.current_or_new_trick());
+        if candidates.len() <= 1 {
+            let card = *candidates.first().ok_or(AnalysisError::NoCardToPlay)?;
+            return Ok(SearchReport {
+                best_card: card,
+                cards: vec![CardReport::unvisited(card)],
+                statistics: SearchStatistics::default(),
+            });
+        }
+        let decision_seed = derive_seed(self.seed, self.decisions);
+        self.decisions += 1;
+        let mut clock = self.control.start();
+        for solver in &mut self.solvers {
+            solver.clear();
+            solver.set_limits(clock.deadline(), self.control.cancellation.clone());
+        }
+        let samples = self.samples.max(1);
+        let mut totals = WorldTotals::new(candidates.len());
+        let mut drawn = 0;
+        let best = |totals: &WorldTotals| {
+            totals.best(self.aggregation).map(|index| {
+                let report = totals.report(index, candidates[index]);
+                (report.card, report.visits, report.mean_reward)
+            })
+        };
+        while drawn < samples && !clock.should_stop() {
+            let batch = drawn..(drawn + WORLDS_PER_BATCH).min(samples);
+            let worlds: Vec<World> = self
+                .solvers
+                .par_iter_mut()
+                .zip(batch.clone().into_par_iter())
+                .map(|(solver, sample)| {
+                    let mut rng = rng_from_seed(derive_seed(decision_seed, sample as u64));
+                    let world = known
+                        .possible_random_full_state_v2_with_rng(&mut rng)
+                        .map_err(AnalysisError::Engine)?;
+                    solve_world(&world, solver)
+                })
+                .collect::<Result<_, AnalysisError>>()?;
+            drawn = batch.end;
+            let mut nodes = 0;
+            let solved_before = totals.solved;
+            for world in &worlds {
+                match world {
+                    World::TimedOut { nodes: world_nodes } => nodes += world_nodes,
+                    World::Solved {
+                        values,
+                        nodes: world_nodes,
+                    } => {
+                        totals.add(values, known);
+                        nodes += world_nodes;
+                    }
+                }
+            }
+            clock.record_iterations(totals.solved - solved_before, nodes);
+            clock.report(|| best(&totals));
+        }
+        clock.report_now(|| best(&totals));
+
+        // Out of time before the first world was solved
+        let best_index = totals.best(self.aggregation).unwrap_or(0);
+        let cards = candidates
+            .iter()
+            .enumerate()
+            .map(|(index, card)| totals.report(index, *card))
+            .collect();
+        Ok(SearchReport {
+            best_card: candidates[best_index],
+            cards,
+            // Iterations are the worlds solved, determinizations also count
+            // those which timed out
+            statistics: clock.statistics(drawn),
+        })
+    }
+}
+
+impl Player for Pimc {
+    fn play_a_card(&mut self, known: &KnownGameState) -> Result<Card, AnalysisError> {
+        Ok(self.analyse(known)?.best_card)
+    }
+
+    fn new_deal(&mut self) {
+        self.decisions = 0;
+    }
+
+    fn seed(&self) -> Option<u64> {
+        Some(self.seed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::business::{
+        analysis::search_control::CancellationToken, game_engine::seed::TarotRng, GameType,
+    };
+
+    fn known_state(tricks: usize) -> KnownGameState {
+        let mut state = GameState::random_init_with_rng(&mut TarotRng::seed_from_u64(32)).unwrap();
+        state
+            .shared_state
+            .bid(1, Some(GameType::GardeSans { chelem: false }))
+            .unwrap();
+        while state.shared_state.played_tricks.len() < tricks {
+            let player = state.shared_state.next_to_play().unwrap();
+            let card = state.legal_moves(player)[0];
+            state.play_card(player, &card).unwrap();
+        }
+        let player = state.shared_state.next_to_play().unwrap();
+        KnownGameState::from_omniscient(&state, player)
+    }
+
+    #[test]
+    fn test_choice_follows_the_solver_on_every_world() {
+        let known = known_state(14);
+        let report = Pimc::new(6, 32).analyse(&known).unwrap();
+        assert_eq!(report.statistics.iterations, 6);
+
+        // Same worlds as the search, solved one by one
+        let sign = if known.shared_state.taker == Some(known.player_index) {
+            1.0
+        } else {
+            -1.0
+        };
+        let decision_seed = derive_seed(32, 0);
+        let mut totals: Vec<(Card, f64, usize)> = vec![];
+        for sample in 0..6 {
+            let mut rng = rng_from_seed(derive_seed(decision_seed, sample));
+            let world = known
+                .possible_random_full_state_v2_with_rng(&mut rng)
+                .unwrap();
+            let values = DoubleDummySolver::new().evaluate_moves(&world).unwrap();
+            totals.resize(values.len(), (values[0].0, 0.0, 0));
+            for (total, (card, value)) in totals.iter_mut().zip(values) {
+                let (_, made) = RewardFunction::WinLoss
+                    .estimated_taker_reward(&world, value as f64)
+                    .unwrap();
+                let won = made == (sign > 0.0);
+                *total = (card, total.1 + sign * value as f64, total.2 + won as usize);
+            }
+        }
+        // Lower cards first on ties, as in the search
+        let best = totals
+            .iter()
+            .rev()
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .unwrap();
+        assert_eq!(report.best_card, best.0);
+        for (card, (_, _, wins)) in report.cards.iter().zip(&totals) {
+            assert_eq!(card.visits, 6);
+            assert_eq!(card.win_probability, Some(*wins as f64 / 6.0));
+        }
+    }
+
+    #[test]
+    fn test_timed_out_worlds_are_dropped() {
+        // Far too early for the solver to finish a world in time
+        let known = known_state(0);
+        let allowed = known
+            .player_state
+            .sorted_cards_allowed(&known.shared_state.current_or_new_trick());
+        let control = SearchControl::new().with_time_limit(Duration::from_millis(50));
+        let mut pimc = Pimc::new(4, 32).with_control(control);
+        let report = pimc.analyse(&known).unwrap();
+        assert_eq!(report.best_card, allowed[0]);
+        assert_eq!(report.statistics.iterations, 0);
+        assert!(report.statistics.determinizations <= 4);
+        assert!(report.statistics.elapsed < Duration::from_secs(5));
+        assert!(report.cards.iter().all(|card| card.visits == 0));
+
+        // Limits come from the same control as the other searches
+        let token = CancellationToken::new();
+        token.cancel();
+        let control = SearchControl::new().with_cancellation(token);
+        let report = Pimc::new(4, 32)
+            .with_control(control)
+            .analyse(&known_state(14))
+            .unwrap();
+        assert_eq!(report.statistics.determinizations, 0);
+
+        // A cancellation also stops the solver in the middle of a world
+        let token = CancellationToken::new();
+        let canceller = token.clone();
+        let cancelling = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            canceller.cancel();
+        });
+        let control = SearchControl::new().with_cancellation(token);
+        let report = Pimc::new(4, 32)
+            .with_control(control)
+            .analyse(&known)
+            .unwrap();
+        cancelling.join().unwrap();
+        assert_eq!(report.statistics.iterations, 0);
+        assert!(report.statistics.elapsed < Duration::from_secs(5));
+    }
+}
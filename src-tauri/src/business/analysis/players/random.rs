@@ -1,23 +1,37 @@
-use rand::rngs::ThreadRng;
-use rand::seq::IteratorRandom;
-use std::collections::HashSet;
+use rand::seq::IndexedRandom;
 
 use crate::business::analysis::analysis_error::AnalysisError;
+use crate::business::game_engine::seed::{rng_from_seed, TarotRng};
 use crate::business::{Card, KnownGameState, Player};
 
-struct Random {
-    rng: ThreadRng,
+pub struct Random {
+    seed: u64,
+    rng: TarotRng,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: rng_from_seed(seed),
+        }
+    }
 }
 
 impl Player for Random {
     fn play_a_card(&mut self, known: &KnownGameState) -> Result<Card, AnalysisError> {
-        let allowed: HashSet<&Card> = known.player_state.cards_allowed(
-            &known
-                .shared_state
-                .current_trick
-                .ok_or(AnalysisError::NoCardToPlay)?,
-        );
-        let chosen = allowed.iter().choose(&mut self.rng);
-        chosen.map(|card| **card).ok_or(AnalysisError::NoCardToPlay)
+        let allowed: Vec<Card> = known
+            .player_state
+            .sorted_cards_allowed(&known.shared_state.current_or_new_trick());
+        let chosen = allowed.choose(&mut self.rng);
+        chosen.copied().ok_or(AnalysisError::NoCardToPlay)
+    }
+
+    fn new_deal(&mut self) {
+        self.rng = rng_from_seed(self.seed);
+    }
+
+    fn seed(&self) -> Option<u64> {
+        Some(self.seed)
     }
 }
@@ -0,0 +1,213 @@
+use std::sync::Arc;
+
+use rand::{seq::IndexedRandom, Rng, RngCore};
+
+use crate::business::{
+    analysis::{analysis_error::AnalysisError, players::expert::follow_trick},
+    game_engine::{game_state::GameState, trick::Trick},
+    Card, Color,
+};
+
+// How playouts choose cards, the deal being fully known to them
+pub trait PlayoutPolicy: Send + Sync {
+    fn choose_card(
+        &self,
+        state: &GameState,
+        player: u8,
+        legal_moves: &[Card],
+        rng: &mut dyn RngCore,
+    ) -> Result<Card, AnalysisError>;
+}
+
+// Any legal card, the historical playout
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UniformPolicy;
+
+impl PlayoutPolicy for UniformPolicy {
+    fn choose_card(
+        &self,
+        _state: &GameState,
+        _player: u8,
+        legal_moves: &[Card],
+        rng: &mut dyn RngCore,
+    ) -> Result<Card, AnalysisError> {
+        legal_moves
+            .choose(rng)
+            .copied()
+            .ok_or(AnalysisError::NoCardToPlay)
+    }
+}
+
+// A few rules of thumb: the Petit is saved when the trick is safe, points go
+// to tricks won by the camp and tricks are won with the smallest card
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RuleBasedPolicy;
+
+impl PlayoutPolicy for RuleBasedPolicy {
+    fn choose_card(
+        &self,
+        state: &GameState,
+        player: u8,
+        legal_moves: &[Card],
+        _rng: &mut dyn RngCore,
+    ) -> Result<Card, AnalysisError> {
+        let trick = state.shared_state.current_or_new_trick();
+        if legal_moves.len() == 1 || trick.cards.iter().all(Option::is_none) {
+            return cheapest(legal_moves).ok_or(AnalysisError::NoCardToPlay);
+        }
+        follow_trick(&trick, state.shared_state.taker, player, legal_moves).map(|(card, _)| card)
+    }
+}
+
+// Uniform with probability `epsilon`, greedy otherwise
+#[derive(Clone)]
+pub struct EpsilonGreedyPolicy {
+    pub epsilon: f64,
+    greedy: Arc<dyn PlayoutPolicy>,
+}
+
+impl EpsilonGreedyPolicy {
+    pub fn new(epsilon: f64, greedy: impl PlayoutPolicy + 'static) -> Self {
+        Self {
+            epsilon,
+            greedy: Arc::new(greedy),
+        }
+    }
+}
+
+impl Default for EpsilonGreedyPolicy {
+    fn default() -> Self {
+        Self::new(0.1, RuleBasedPolicy)
+    }
+}
+
+impl PlayoutPolicy for EpsilonGreedyPolicy {
+    fn choose_card(
+        &self,
+        state: &GameState,
+        player: u8,
+        legal_moves: &[Card],
+        rng: &mut dyn RngCore,
+    ) -> Result<Card, AnalysisError> {
+        if rng.random::<f64>() < self.epsilon {
+            UniformPolicy.choose_card(state, player, legal_moves, rng)
+        } else {
+            self.greedy.choose_card(state, player, legal_moves, rng)
+        }
+    }
+}
+
+// Oudlers are the most valuable cards to keep, then honours and trumps
+fn keep_value(card: &Card) -> (u8, usize, bool, u8) {
+    let oudler = match card.color {
+        Color::Trump if card.value == 1 => 3,
+        Color::Trump if card.value == 21 => 2,
+        Color::Excuse => 1,
+        _ => 0,
+    };
+    (
+        oudler,
+        card.points(),
+        card.color == Color::Trump,
+        card.value,
+    )
+}
+
+pub fn cheapest(cards: &[Card]) -> Option<Card> {
+    cards.iter().min_by_key(|card| keep_value(card)).copied()
+}
+
+// Richest card which is not an oudler, colours before trumps
+pub fn most_points(cards: &[Card]) -> Option<Card> {
+    cards
+        .iter()
+        .filter(|card| !card.is_oudler())
+        .max_by_key(|card| {
+            (
+                card.points(),
+                card.color != Color::Trump,
+                std::cmp::Reverse(card.value),
+            )
+        })
+        .copied()
+}
+
+pub fn winner_after(trick: &Trick, player: u8, card: &Card) -> Option<u8> {
+    let mut after = *trick;
+    after.play_card(player, card).ok()?;
+    after.winner().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::business::{
+        analysis::simulate_random_playout::simulate_playout, game_engine::seed::TarotRng, GameType,
+    };
+
+    fn cards(notations: &[&str]) -> Vec<Card> {
+        notations
+            .iter()
+            .map(|notation| Card::from_str(notation).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_rule_based_choices() {
+        let mut rng = TarotRng::seed_from_u64(39);
+        let mut state = GameState::random_init_with_rng(&mut rng).unwrap();
+        state
+            .shared_state
+            .bid(0, Some(GameType::Garde { chelem: false }))
+            .unwrap();
+        let mut trick = Trick::new(1);
+        trick.cards[1] = Some(Card::from_str("2H").unwrap());
+        trick.cards[2] = Some(Card::from_str("KH").unwrap());
+        trick.cards[0] = Some(Card::from_str("T5").unwrap());
+        state.shared_state.current_trick = Some(trick);
+        let policy = RuleBasedPolicy;
+
+        // The taker wins, the defender takes the king back as cheaply as possible
+        let legal = cards(&["T1", "T3", "T21", "3D", "QD"]);
+        let card = policy.choose_card(&state, 3, &legal, &mut rng).unwrap();
+        assert_eq!(card, Card::from_str("T21").unwrap());
+        let legal = cards(&["T6", "T12"]);
+        let card = policy.choose_card(&state, 3, &legal, &mut rng).unwrap();
+        assert_eq!(card, Card::from_str("T6").unwrap());
+
+        // The defence wins, the Petit is saved or points are given
+        trick.cards[0] = Some(Card::from_str("4H").unwrap());
+        state.shared_state.current_trick = Some(trick);
+        let legal = cards(&["T1", "T3"]);
+        let card = policy.choose_card(&state, 3, &legal, &mut rng).unwrap();
+        assert_eq!(card, Card::from_str("T1").unwrap());
+        let legal = cards(&["3H", "QH"]);
+        let card = policy.choose_card(&state, 3, &legal, &mut rng).unwrap();
+        assert_eq!(card, Card::from_str("QH").unwrap());
+    }
+
+    #[test]
+    fn test_policies_finish_deals() {
+        let mut rng = TarotRng::seed_from_u64(39);
+        let policies: Vec<Box<dyn PlayoutPolicy>> = vec![
+            Box::new(UniformPolicy),
+            Box::new(RuleBasedPolicy),
+            Box::new(EpsilonGreedyPolicy::default()),
+        ];
+        for policy in policies {
+            for _ in 0..10 {
+                let mut state = GameState::random_init_with_rng(&mut rng).unwrap();
+                state
+                    .shared_state
+                    .bid(2, Some(GameType::GardeContre { chelem: false }))
+                    .unwrap();
+                simulate_playout(&mut state, policy.as_ref(), &mut rng).unwrap();
+                assert!(state.shared_state.finished());
+            }
+        }
+    }
+}
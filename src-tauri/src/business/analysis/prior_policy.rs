@@ -0,0 +1,14 @@
+use crate::business::{
+    analysis::analysis_error::AnalysisError, game_engine::game_state::GameState, Card,
+};
+
+// Probabilities of the legal cards, in the same order, telling a search which
+// cards deserve its visits first
+pub trait PriorPolicy: Send + Sync {
+    fn priors(
+        &self,
+        state: &GameState,
+        player: u8,
+        legal_moves: &[Card],
+    ) -> Result<Vec<f64>, AnalysisError>;
+}
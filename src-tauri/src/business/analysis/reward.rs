@@ -0,0 +1,223 @@
+use crate::business::{
+    analysis::analysis_error::AnalysisError,
+    game_engine::{
+        engine_error::EngineError,
+        game_state::GameState,
+        score::{contract_target, marque, oudlers_won},
+    },
+    Card, Color,
+};
+
+pub const TOTAL_POINTS: f64 = 182.0;
+// Typical spreads of the marque and of the margin in half points, wider
+// results are clamped. Scaling by the extremes would squeeze most deals
+// around 0.5
+const MARQUE_SPREAD: f64 = 100.0;
+const MARGIN_SPREAD: f64 = 80.0;
+
+// Every reward lies between 0 and 1 so that the exploration constant of the
+// searches means the same whatever is optimised
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RewardFunction {
+    // Card points of the taker
+    #[default]
+    Points,
+    // Card points above or below the contract target
+    ContractMargin,
+    // Only whether the contract is made
+    WinLoss,
+    // Final marque, bonuses included
+    Marque,
+}
+
+impl RewardFunction {
+    pub fn taker_reward(&self, finished: &GameState) -> Result<f64, AnalysisError> {
+        let score = finished.final_score().map_err(AnalysisError::Engine)?;
+        let reward = match self {
+            RewardFunction::Points => score.taker_points as f64 / TOTAL_POINTS,
+            RewardFunction::ContractMargin => {
+                let margin = score.taker_points as f64 - contract_target(score.oudlers) as f64;
+                0.5 + margin / (2.0 * MARGIN_SPREAD)
+            }
+            RewardFunction::WinLoss => {
+                if score.contract_won {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            RewardFunction::Marque => 0.5 + score.marque as f64 / (2.0 * MARQUE_SPREAD),
+        };
+        Ok(reward.clamp(0.0, 1.0))
+    }
+
+    // The reward of a deal left unfinished, from an estimate of the taker's
+    // final points. Oudlers already won, held by the taker or in a kitty going
+    // to the taker set the target, the Petit only when won. The marque leaves
+    // the Petit au bout and the chelem out.
+    pub fn estimated_taker_reward(
+        &self,
+        state: &GameState,
+        taker_points: f64,
+    ) -> Result<(f64, bool), AnalysisError> {
+        let shared_state = &state.shared_state;
+        let taker = shared_state
+            .taker
+            .ok_or(AnalysisError::Engine(EngineError::NoTaker))?;
+        let game_type = shared_state
+            .game_type
+            .ok_or(AnalysisError::Engine(EngineError::NoTaker))?;
+        let kept =
+            |card: &&Card| card.is_oudler() && !(card.color == Color::Trump && card.value == 1);
+        let mut oudlers = oudlers_won(&shared_state.played_tricks, taker)
+            + state.players_state[taker as usize]
+                .hand
+                .iter()
+                .filter(kept)
+                .count();
+        if game_type.kitty_goes_to_taker() {
+            oudlers += state.kitty.iter().filter(|card| card.is_oudler()).count();
+        }
+        let target = contract_target(oudlers) as f64;
+        let contract_won = taker_points >= target;
+        let reward = match self {
+            RewardFunction::Points => taker_points / TOTAL_POINTS,
+            RewardFunction::ContractMargin => 0.5 + (taker_points - target) / (2.0 * MARGIN_SPREAD),
+            RewardFunction::WinLoss => contract_won as u8 as f64,
+            RewardFunction::Marque => {
+                let difference = ((taker_points - target).abs() / 2.0).ceil() as usize;
+                let handfuls = shared_state
+                    .declared_handfuls
+                    .iter()
+                    .flatten()
+                    .map(|declared| declared.handful.points())
+                    .sum();
+                let marque = marque(game_type, contract_won, difference, handfuls) as f64;
+                0.5 + marque / (2.0 * MARQUE_SPREAD)
+            }
+        };
+        Ok((reward.clamp(0.0, 1.0), contract_won))
+    }
+
+    // What a deal is worth to the camp of `player`, defenders share the opposite
+    // of the taker's reward
+    pub fn reward(&self, finished: &GameState, player: u8) -> Result<f64, AnalysisError> {
+        let taker_reward = self.taker_reward(finished)?;
+        Ok(camp_reward(
+            taker_reward,
+            player,
+            finished.shared_state.taker,
+        ))
+    }
+}
+
+pub fn camp_won(finished: &GameState, player: u8) -> Result<bool, AnalysisError> {
+    let contract_won = finished
+        .final_score()
+        .map_err(AnalysisError::Engine)?
+        .contract_won;
+    Ok(contract_won == (finished.shared_state.taker == Some(player)))
+}
+
+pub fn camp_reward(taker_reward: f64, player: u8, taker: Option<u8>) -> f64 {
+    if taker == Some(player) {
+        taker_reward
+    } else {
+        1.0 - taker_reward
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::business::{
+        analysis::simulate_random_playout::simulate_random_playout_with_rng,
+        game_engine::seed::TarotRng, GameType,
+    };
+
+    #[test]
+    fn test_rewards_are_normalised_and_opposed() {
+        let mut rng = TarotRng::seed_from_u64(34);
+        for _ in 0..20 {
+            let mut state = GameState::random_init_with_rng(&mut rng).unwrap();
+            state
+                .shared_state
+                .bid(0, Some(GameType::GardeContre { chelem: false }))
+                .unwrap();
+            simulate_random_playout_with_rng(&mut state, &mut rng).unwrap();
+            for function in [
+                RewardFunction::Points,
+                RewardFunction::ContractMargin,
+                RewardFunction::WinLoss,
+                RewardFunction::Marque,
+            ] {
+                let taker = function.reward(&state, 0).unwrap();
+                assert!((0.0..=1.0).contains(&taker));
+                for defender in 1..4 {
+                    assert_eq!(function.reward(&state, defender).unwrap(), 1.0 - taker);
+                }
+            }
+            let score = state.final_score().unwrap();
+            assert_eq!(
+                RewardFunction::WinLoss.reward(&state, 0).unwrap(),
+                score.contract_won as u8 as f64
+            );
+            // Exact once every card is down, bonuses aside
+            for function in [
+                RewardFunction::Points,
+                RewardFunction::ContractMargin,
+                RewardFunction::WinLoss,
+            ] {
+                assert_eq!(
+                    function
+                        .estimated_taker_reward(&state, score.taker_points as f64)
+                        .unwrap(),
+                    (function.taker_reward(&state).unwrap(), score.contract_won)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_spreads_are_comparable() {
+        let mut rng = TarotRng::seed_from_u64(34);
+        let functions = [
+            RewardFunction::Points,
+            RewardFunction::ContractMargin,
+            RewardFunction::Marque,
+        ];
+        let mut rewards: Vec<Vec<f64>> = vec![vec![]; functions.len()];
+        for _ in 0..200 {
+            let mut state = GameState::random_init_with_rng(&mut rng).unwrap();
+            state
+                .shared_state
+                .bid(0, Some(GameType::Garde { chelem: false }))
+                .unwrap();
+            simulate_random_playout_with_rng(&mut state, &mut rng).unwrap();
+            for (function, rewards) in functions.iter().zip(rewards.iter_mut()) {
+                rewards.push(function.taker_reward(&state).unwrap());
+            }
+        }
+        let deviations: Vec<f64> = rewards
+            .iter()
+            .map(|rewards| {
+                let mean = rewards.iter().sum::<f64>() / rewards.len() as f64;
+                let variance =
+                    rewards.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / rewards.len() as f64;
+                variance.sqrt()
+            })
+            .collect();
+        // Within a factor 2 of the card points, where scaling by the extremes
+        // gave the marque a spread ten times narrower
+        for deviation in &deviations[1..] {
+            assert!((0.5 * deviations[0]..=2.0 * deviations[0]).contains(deviation));
+        }
+        let saturated = rewards[2]
+            .iter()
+            .filter(|reward| **reward == 0.0 || **reward == 1.0)
+            .count();
+        assert!(saturated < rewards[2].len() * 3 / 4);
+    }
+}
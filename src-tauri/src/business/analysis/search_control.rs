@@ -0,0 +1,250 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use std::time::{Duration, Instant};
+
+use crate::business::{
+    analysis::search_report::SearchStatistics, game_engine::shared_game_state::SharedGameState,
+    Card,
+};
+
+// Shared between the search and whoever may want to stop it, clones observe
+// the same flag
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SearchProgress {
+    pub iterations: usize,
+    pub nodes: usize,
+    pub elapsed: Duration,
+    pub best_card: Option<Card>,
+    pub best_visits: usize,
+    pub best_mean_reward: f64,
+}
+
+pub type ProgressCallback = Arc<dyn Fn(&SearchProgress) + Send + Sync>;
+
+// Limits of an anytime search, on top of its iteration count. Nodes are the
+// cards played by the search, in its tree and in its playouts, or the
+// positions visited by the solver for PIMC
+#[derive(Clone, Default)]
+pub struct SearchControl {
+    pub time_limit: Option<Duration>,
+    pub node_budget: Option<usize>,
+    pub cancellation: Option<CancellationToken>,
+    progress: Option<(usize, ProgressCallback)>,
+}
+
+impl SearchControl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_time_limit(mut self, time_limit: Duration) -> Self {
+        self.time_limit = Some(time_limit);
+        self
+    }
+
+    pub fn with_node_budget(mut self, node_budget: usize) -> Self {
+        self.node_budget = Some(node_budget);
+        self
+    }
+
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = Some(cancellation);
+        self
+    }
+
+    // The callback is called every `every` iterations and once at the end
+    pub fn with_progress(
+        mut self,
+        every: usize,
+        callback: impl Fn(&SearchProgress) + Send + Sync + 'static,
+    ) -> Self {
+        self.progress = Some((every.max(1), Arc::new(callback)));
+        self
+    }
+
+    pub fn start(&self) -> SearchClock<'_> {
+        let started = Instant::now();
+        SearchClock {
+            control: self,
+            started,
+            deadline: self.time_limit.map(|limit| started + limit),
+            iterations: 0,
+            nodes: 0,
+            last_report: 0,
+        }
+    }
+}
+
+pub struct SearchClock<'a> {
+    control: &'a SearchControl,
+    started: Instant,
+    deadline: Option<Instant>,
+    iterations: usize,
+    nodes: usize,
+    last_report: usize,
+}
+
+impl SearchClock<'_> {
+    pub fn should_stop(&self) -> bool {
+        self.control
+            .cancellation
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+            || self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+            || self
+                .control
+                .node_budget
+                .is_some_and(|budget| self.nodes >= budget)
+    }
+
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    pub fn iterations(&self) -> usize {
+        self.iterations
+    }
+
+    pub fn nodes(&self) -> usize {
+        self.nodes
+    }
+
+    pub fn statistics(&self, determinizations: usize) -> SearchStatistics {
+        SearchStatistics {
+            iterations: self.iterations,
+            nodes: self.nodes,
+            elapsed: self.started.elapsed(),
+            determinizations,
+        }
+    }
+
+    pub fn record_iterations(&mut self, iterations: usize, nodes: usize) {
+        self.iterations += iterations;
+        self.nodes += nodes;
+    }
+
+    // `best` gives the current best card with its visits and mean reward, it
+    // is only computed when a report is due
+    pub fn report(&mut self, best: impl FnOnce() -> Option<(Card, usize, f64)>) {
+        if let Some((every, _)) = &self.control.progress {
+            if self.iterations >= self.last_report + every {
+                self.report_now(best);
+            }
+        }
+    }
+
+    pub fn report_now(&mut self, best: impl FnOnce() -> Option<(Card, usize, f64)>) {
+        let Some((_, callback)) = &self.control.progress else {
+            return;
+        };
+        self.last_report = self.iterations;
+        let best = best();
+        callback(&SearchProgress {
+            iterations: self.iterations,
+            nodes: self.nodes,
+            elapsed: self.started.elapsed(),
+            best_card: best.map(|(card, _, _)| card),
+            best_visits: best.map(|(_, visits, _)| visits).unwrap_or(0),
+            best_mean_reward: best.map(|(_, _, mean)| mean).unwrap_or(0.0),
+        });
+    }
+}
+
+// Cards still to be played before the end of the deal
+pub fn remaining_plays(shared_state: &SharedGameState) -> usize {
+    let on_table = shared_state
+        .current_trick
+        .map(|trick| trick.cards.iter().flatten().count())
+        .unwrap_or(0);
+    (18 - shared_state.played_tricks.len()) * 4 - on_table
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::business::{
+        analysis::players::{mcts::MCTS, monte_carlo::MonteCarlo},
+        game_engine::{game_state::GameState, seed::TarotRng},
+        GameType, KnownGameState, Player,
+    };
+
+    fn known_state() -> KnownGameState {
+        let mut rng = TarotRng::seed_from_u64(35);
+        let mut state = GameState::random_init_with_rng(&mut rng).unwrap();
+        state
+            .shared_state
+            .bid(0, Some(GameType::GardeSans { chelem: false }))
+            .unwrap();
+        let player = state.shared_state.next_to_play().unwrap();
+        KnownGameState::from_omniscient(&state, player)
+    }
+
+    #[test]
+    fn test_searches_stop_on_limits() {
+        let known = known_state();
+        let allowed = known
+            .player_state
+            .sorted_cards_allowed(&known.shared_state.current_or_new_trick());
+        let reports: Arc<Mutex<Vec<SearchProgress>>> = Arc::default();
+        let sink = reports.clone();
+        let budget = 10 * remaining_plays(&known.shared_state);
+        let control = SearchControl::new()
+            .with_node_budget(budget)
+            .with_progress(4, move |progress| sink.lock().unwrap().push(*progress));
+        let mut mcts = MCTS::new(10_000, 1.41, 1).with_control(control);
+        assert!(allowed.contains(&mcts.play_a_card(&known).unwrap()));
+        let last = *reports.lock().unwrap().last().unwrap();
+        assert_eq!(last.iterations, 10);
+        assert_eq!(last.nodes, budget);
+        assert!(last.best_card.is_some_and(|card| allowed.contains(&card)));
+        assert_eq!(reports.lock().unwrap().len(), 3);
+
+        // Cut playouts play fewer cards, the budget lets more iterations run
+        let sink = reports.clone();
+        let control = SearchControl::new()
+            .with_node_budget(budget)
+            .with_progress(4, move |progress| sink.lock().unwrap().push(*progress));
+        let mut mcts = MCTS::new(10_000, 1.41, 1)
+            .with_playout_depth(8)
+            .with_control(control);
+        mcts.play_a_card(&known).unwrap();
+        let last = *reports.lock().unwrap().last().unwrap();
+        assert!(last.iterations > 10);
+        // One iteration at most goes past the budget
+        assert!((budget..budget + remaining_plays(&known.shared_state)).contains(&last.nodes));
+
+        // A cancelled search still answers with a legal card
+        let token = CancellationToken::new();
+        token.cancel();
+        let control = SearchControl::new().with_cancellation(token);
+        let mut monte_carlo = MonteCarlo::new(10_000, 1).with_control(control.clone());
+        assert!(allowed.contains(&monte_carlo.play_a_card(&known).unwrap()));
+        let mut mcts = MCTS::new(10_000, 1.41, 1).with_control(control);
+        assert!(allowed.contains(&mcts.play_a_card(&known).unwrap()));
+    }
+}
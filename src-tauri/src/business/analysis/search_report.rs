@@ -0,0 +1,135 @@
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::business::{analysis::analysis_error::AnalysisError, Card, KnownGameState};
+
+// Normal quantile of a two sided 95% interval
+const CONFIDENCE_QUANTILE: f64 = 1.96;
+
+// What a search found about one legal card, rewards are seen from the camp of
+// the player to play
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CardReport {
+    pub card: Card,
+    pub visits: usize,
+    pub mean_reward: f64,
+    pub confidence_interval: (f64, f64),
+    // Share of the simulations where the camp of the player won the contract,
+    // unknown for cards left unvisited
+    pub win_probability: Option<f64>,
+    // The card itself followed by the most expected answers
+    pub principal_variation: Vec<Card>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize)]
+pub struct SearchStatistics {
+    pub iterations: usize,
+    pub nodes: usize,
+    pub elapsed: Duration,
+    // Deals sampled to stand for the hidden cards
+    pub determinizations: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SearchReport {
+    pub best_card: Card,
+    // One report per legal card, in the order of the cards
+    pub cards: Vec<CardReport>,
+    pub statistics: SearchStatistics,
+}
+
+impl SearchReport {
+    pub fn card(&self, card: &Card) -> Option<&CardReport> {
+        self.cards.iter().find(|report| report.card == *card)
+    }
+}
+
+// Searches which can explain their choice, playing the best card of the report
+pub trait SearchAnalysis {
+    fn analyse(&mut self, game_state: &KnownGameState) -> Result<SearchReport, AnalysisError>;
+}
+
+impl CardReport {
+    // A card the search had no time to look at
+    pub fn unvisited(card: Card) -> Self {
+        Self {
+            card,
+            visits: 0,
+            mean_reward: 0.0,
+            confidence_interval: (0.0, 1.0),
+            win_probability: None,
+            principal_variation: vec![card],
+        }
+    }
+}
+
+// Normal approximation around the mean of rewards lying between 0 and 1
+pub fn confidence_interval(count: usize, total: f64, total_squared: f64) -> (f64, f64) {
+    if count == 0 {
+        return (0.0, 1.0);
+    }
+    let count = count as f64;
+    let mean = total / count;
+    let variance = (total_squared / count - mean * mean).max(0.0);
+    let half_width = CONFIDENCE_QUANTILE * (variance / count).sqrt();
+    ((mean - half_width).max(0.0), (mean + half_width).min(1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::business::{
+        analysis::players::{mcts::MCTS, monte_carlo::MonteCarlo, pimc::Pimc},
+        game_engine::{game_state::GameState, seed::TarotRng},
+        GameType,
+    };
+
+    #[test]
+    fn test_reports_cover_every_legal_card() {
+        let mut rng = TarotRng::seed_from_u64(38);
+        let mut state = GameState::random_init_with_rng(&mut rng).unwrap();
+        state
+            .shared_state
+            .bid(3, Some(GameType::Garde { chelem: false }))
+            .unwrap();
+        // Late in the deal so that the solver answers quickly
+        while state.shared_state.played_tricks.len() < 12 {
+            let player = state.shared_state.next_to_play().unwrap();
+            let card = state.legal_moves(player)[0];
+            state.play_card(player, &card).unwrap();
+        }
+        let player = state.shared_state.next_to_play().unwrap();
+        let known = KnownGameState::from_omniscient(&state, player);
+        let allowed = known
+            .player_state
+            .sorted_cards_allowed(&known.shared_state.current_or_new_trick());
+        let analysts: Vec<Box<dyn SearchAnalysis>> = vec![
+            Box::new(MCTS::new(200, 1.41, 1)),
+            Box::new(MonteCarlo::new(20, 1)),
+            Box::new(Pimc::new(4, 1)),
+        ];
+        for mut analyst in analysts {
+            let report = analyst.analyse(&known).unwrap();
+            let cards: Vec<Card> = report.cards.iter().map(|report| report.card).collect();
+            assert_eq!(cards, allowed);
+            assert!(allowed.contains(&report.best_card));
+            if allowed.len() > 1 {
+                assert!(report.statistics.determinizations > 0);
+            }
+            for card in &report.cards {
+                let (low, high) = card.confidence_interval;
+                assert!(low <= high);
+                if card.visits > 0 {
+                    assert!(low <= card.mean_reward && card.mean_reward <= high);
+                }
+                assert_eq!(card.principal_variation[0], card.card);
+                assert!(card
+                    .win_probability
+                    .is_none_or(|probability| (0.0..=1.0).contains(&probability)));
+            }
+        }
+    }
+}
@@ -1,21 +1,61 @@
-use rand::{rng, seq::IteratorRandom};
+use rand::{rng, seq::IndexedRandom, Rng, RngCore};
 
 use crate::business::{
-    analysis::analysis_error::AnalysisError, game_engine::game_state::GameState,
+    analysis::{analysis_error::AnalysisError, playout_policy::PlayoutPolicy},
+    game_engine::game_state::GameState,
 };
 
 pub fn simulate_random_playout(full: &mut GameState) -> Result<usize, AnalysisError> {
-    let mut rng = rng();
+    simulate_random_playout_with_rng(full, &mut rng())
+}
+
+pub fn simulate_random_playout_with_rng<R: Rng + ?Sized>(
+    full: &mut GameState,
+    rng: &mut R,
+) -> Result<usize, AnalysisError> {
     while let Some(player) = full.shared_state.next_to_play() {
-        let card = full
-            .cards_allowed(player)
-            .iter()
-            .choose(&mut rng)
-            .cloned()
-            .cloned()
+        let card = *full
+            .legal_moves(player)
+            .choose(rng)
             .ok_or(AnalysisError::NoCardToPlay)?;
         full.play_card(player, &card)
             .map_err(|e| AnalysisError::Engine(e))?;
     }
     Ok(full.shared_state.current_score())
 }
+
+pub fn simulate_playout(
+    full: &mut GameState,
+    policy: &dyn PlayoutPolicy,
+    rng: &mut dyn RngCore,
+) -> Result<usize, AnalysisError> {
+    while let Some(player) = full.shared_state.next_to_play() {
+        let legal_moves = full.legal_moves(player);
+        let card = policy.choose_card(full, player, &legal_moves, rng)?;
+        full.play_card(player, &card)
+            .map_err(AnalysisError::Engine)?;
+    }
+    Ok(full.shared_state.current_score())
+}
+
+// Plays `depth` cards then finishes the trick, so that the position left is
+// between two tricks
+pub fn simulate_playout_to_depth(
+    full: &mut GameState,
+    policy: &dyn PlayoutPolicy,
+    depth: usize,
+    rng: &mut dyn RngCore,
+) -> Result<(), AnalysisError> {
+    let mut played = 0;
+    while let Some(player) = full.shared_state.next_to_play() {
+        if played >= depth && full.shared_state.current_trick.is_none() {
+            break;
+        }
+        let legal_moves = full.legal_moves(player);
+        let card = policy.choose_card(full, player, &legal_moves, rng)?;
+        full.play_card(player, &card)
+            .map_err(AnalysisError::Engine)?;
+        played += 1;
+    }
+    Ok(())
+}
@@ -0,0 +1,129 @@
+#[derive(Debug, Clone)]
+struct Entry<V> {
+    hash: u64,
+    value: V,
+}
+
+#[derive(Debug, Clone)]
+pub struct TranspositionTable<V> {
+    entries: Vec<Option<Entry<V>>>,
+    mask: usize,
+    len: usize,
+}
+
+impl<V> TranspositionTable<V> {
+    pub fn with_capacity(capacity: usize) -> Self {
+        let size = capacity.max(1).next_power_of_two();
+        Self {
+            entries: (0..size).map(|_| None).collect(),
+            mask: size - 1,
+            len: 0,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.iter_mut().for_each(|entry| *entry = None);
+        self.len = 0;
+    }
+
+    fn slot(&self, hash: u64) -> usize {
+        (hash as usize) & self.mask
+    }
+
+    pub fn get(&self, hash: u64) -> Option<&V> {
+        self.entries[self.slot(hash)]
+            .as_ref()
+            .filter(|entry| entry.hash == hash)
+            .map(|entry| &entry.value)
+    }
+
+    pub fn get_mut(&mut self, hash: u64) -> Option<&mut V> {
+        let slot = self.slot(hash);
+        self.entries[slot]
+            .as_mut()
+            .filter(|entry| entry.hash == hash)
+            .map(|entry| &mut entry.value)
+    }
+
+    pub fn insert(&mut self, hash: u64, value: V) {
+        self.insert_if(hash, value, |_| true);
+    }
+
+    // `replace` is only asked about entries of other positions sharing the
+    // same slot, entries of the same position are always updated
+    pub fn insert_if(&mut self, hash: u64, value: V, replace: impl FnOnce(&V) -> bool) {
+        let slot = self.slot(hash);
+        match &self.entries[slot] {
+            None => self.len += 1,
+            Some(entry) if entry.hash != hash && !replace(&entry.value) => return,
+            Some(_) => (),
+        }
+        self.entries[slot] = Some(Entry { hash, value });
+    }
+
+    pub fn get_or_insert_with(&mut self, hash: u64, default: impl FnOnce() -> V) -> &mut V {
+        if self.get(hash).is_none() {
+            self.insert(hash, default());
+        }
+        self.get_mut(hash).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store_and_probe() {
+        let mut table = TranspositionTable::with_capacity(5);
+        assert_eq!(table.capacity(), 8);
+        assert!(table.is_empty());
+        table.insert(3, "a");
+        table.insert(12, "b");
+        assert_eq!(table.len(), 2);
+        assert_eq!(table.get(3), Some(&"a"));
+        assert_eq!(table.get(12), Some(&"b"));
+        // Same slot, other position
+        assert_eq!(table.get(11), None);
+        *table.get_mut(3).unwrap() = "c";
+        assert_eq!(table.get(3), Some(&"c"));
+        assert_eq!(*table.get_or_insert_with(5, || "d"), "d");
+        assert_eq!(*table.get_or_insert_with(5, || "e"), "d");
+        table.clear();
+        assert!(table.is_empty());
+        assert_eq!(table.get(3), None);
+    }
+
+    #[test]
+    fn test_replacement() {
+        let mut table = TranspositionTable::with_capacity(4);
+        table.insert_if(1, 10, |_| false);
+        // The same position is always updated
+        table.insert_if(1, 5, |_| false);
+        assert_eq!(table.get(1), Some(&5));
+        // Another position in the slot only replaces it when allowed
+        table.insert_if(5, 20, |previous| *previous > 5);
+        assert_eq!(table.get(1), Some(&5));
+        assert_eq!(table.get(5), None);
+        table.insert_if(5, 20, |previous| *previous <= 5);
+        assert_eq!(table.get(1), None);
+        assert_eq!(table.get(5), Some(&20));
+        assert_eq!(table.len(), 1);
+        // Unconditional inserts always replace
+        table.insert(9, 30);
+        assert_eq!(table.get(9), Some(&30));
+        assert_eq!(table.get(5), None);
+    }
+}
@@ -1,11 +1,17 @@
 use super::analysis::analysis_error::AnalysisError;
+use super::encoding::encoding_error::EncodingError;
 use super::game_engine::engine_error::EngineError;
+use super::game_record::record_error::RecordError;
 
 #[derive(Debug)]
 pub enum BusinessError {
     Analysis(AnalysisError),
     Engine(EngineError),
+    Record(RecordError),
+    Encoding(EncodingError),
     EveryonePassed,
+    InvalidSetup(String),
+    AdvisorBusy,
 }
 
 impl From<AnalysisError> for BusinessError {
@@ -20,14 +26,30 @@ impl From<EngineError> for BusinessError {
     }
 }
 
+impl From<RecordError> for BusinessError {
+    fn from(err: RecordError) -> Self {
+        BusinessError::Record(err)
+    }
+}
+
+impl From<EncodingError> for BusinessError {
+    fn from(err: EncodingError) -> Self {
+        BusinessError::Encoding(err)
+    }
+}
+
 impl std::fmt::Display for BusinessError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             BusinessError::Analysis(error) => write!(f, "{}", error),
             BusinessError::Engine(error) => write!(f, "{}", error),
+            BusinessError::Record(error) => write!(f, "{}", error),
+            BusinessError::Encoding(error) => write!(f, "{}", error),
             BusinessError::EveryonePassed => {
                 write!(f, "Could not start the game as everyone passed")
             }
+            BusinessError::InvalidSetup(arg) => write!(f, "Could not set up the game: {}", arg),
+            BusinessError::AdvisorBusy => write!(f, "The advisor is already searching"),
         }
     }
 }
@@ -0,0 +1,443 @@
+use std::collections::HashSet;
+
+use serde::Serialize;
+
+use super::{
+    analysis::{
+        bidding::{SimulatedBidding, SimulationBidder},
+        network::Network,
+        players::mcts::player::MCTS,
+    },
+    business_error::BusinessError,
+    game_engine::{
+        engine_error::EngineError, game_state::GameState, observation::Observation, score::Score,
+    },
+    player::Player,
+    Card, Color, GameType, KnownGameState,
+};
+
+// Exploration constant of the advisor's search
+const EXPLORATION: f64 = 1.41;
+// Cards played out before the network evaluates the rest of the deal
+const NETWORK_PLAYOUT_DEPTH: usize = 8;
+
+// What the advisor would do in the user's place
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum Suggestion {
+    Bid(Option<GameType>),
+    Aside([Card; 6]),
+    Card(Card),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Announcements {
+    pub chelem: bool,
+    pub handful: Option<Vec<Card>>,
+}
+
+// Cards still to come that the user does not hold, and what every player
+// showed they lack
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Tracker {
+    pub trumps_out: Vec<Card>,
+    pub honours_out: Vec<Card>,
+    pub voids: [Vec<Color>; 4],
+    pub taker_points: usize,
+}
+
+// Searches for the cards, guided and evaluated by the network if any,
+// simulates deals for the bids
+pub fn search_advisor(
+    iterations: usize,
+    bidding_samples: usize,
+    seed: u64,
+    network: Option<Network>,
+) -> SimulationBidder<MCTS> {
+    let mut search = MCTS::new(iterations, EXPLORATION, seed);
+    if let Some(network) = network {
+        search = search
+            .with_priors(network.clone())
+            .with_evaluator(network)
+            .with_playout_depth(NETWORK_PLAYOUT_DEPTH);
+    }
+    SimulationBidder::new(search, SimulatedBidding::new(bidding_samples, seed))
+}
+
+// Follows a deal played with real cards from the user's seat, the other
+// players' cards being typed in as they are put down
+pub struct Companion {
+    hand: HashSet<Card>,
+    seat: u8,
+    dealer: u8,
+    known: KnownGameState,
+    observations: Vec<Observation>,
+    // Lent out while a consultation searches
+    advisor: Option<Box<dyn Player + Send>>,
+    seed: Option<u64>,
+}
+
+// The advisor with the deal as known when it was consulted, searching
+// without holding the companion
+pub struct Consultation {
+    known: KnownGameState,
+    seat: u8,
+    advisor: Box<dyn Player + Send>,
+}
+
+impl Consultation {
+    // The user's decision the deal is waiting for
+    pub fn suggest(&mut self) -> Result<Suggestion, BusinessError> {
+        let known = &self.known;
+        let shared_state = &known.shared_state;
+        if known.next_bidder() == Some(self.seat) && known.kitty.is_none() {
+            return Ok(Suggestion::Bid(self.advisor.bid(known)?));
+        }
+        if shared_state.taker == Some(self.seat) && known.player_state.hand.len() == 24 {
+            return Ok(Suggestion::Aside(self.advisor.chose_aside(known)?));
+        }
+        let kitty_missing = shared_state.kitty_should_be_revealed() && known.kitty.is_none();
+        if shared_state.taker.is_some()
+            && !known.auction_in_progress()
+            && !kitty_missing
+            && shared_state.next_to_play() == Some(self.seat)
+        {
+            return Ok(Suggestion::Card(self.advisor.play_a_card(known)?));
+        }
+        Err(EngineError::OutOfOrderPlay.into())
+    }
+
+    // Asked before the first card, the chelem only to the taker
+    pub fn announcements(&self) -> Result<Announcements, BusinessError> {
+        let chelem = self.known.shared_state.taker == Some(self.seat)
+            && self.advisor.declare_chelem(&self.known)?;
+        let handful = self.advisor.declare_handful(&self.known)?.map(|cards| {
+            let mut cards: Vec<Card> = cards.into_iter().collect();
+            cards.sort_by_key(Card::index);
+            cards
+        });
+        Ok(Announcements { chelem, handful })
+    }
+}
+
+impl Companion {
+    pub fn new(
+        hand: HashSet<Card>,
+        seat: u8,
+        dealer: u8,
+        mut advisor: Box<dyn Player + Send>,
+    ) -> Result<Self, BusinessError> {
+        if hand.len() != 18 {
+            return Err(BusinessError::InvalidSetup(format!(
+                "A hand holds 18 cards, not {}",
+                hand.len()
+            )));
+        }
+        if seat > 3 || dealer > 3 {
+            return Err(BusinessError::InvalidSetup(String::from(
+                "Seats go from 0 to 3",
+            )));
+        }
+        advisor.new_deal();
+        Ok(Self {
+            known: KnownGameState::initialize(hand.clone(), seat, None, dealer),
+            hand,
+            seat,
+            dealer,
+            observations: vec![],
+            seed: advisor.seed(),
+            advisor: Some(advisor),
+        })
+    }
+
+    // The advisor's seed, which replays its suggestions
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    pub fn known(&self) -> &KnownGameState {
+        &self.known
+    }
+
+    pub fn observations(&self) -> &[Observation] {
+        &self.observations
+    }
+
+    pub fn observe(&mut self, observation: Observation) -> Result<(), BusinessError> {
+        self.known.observe(&observation)?;
+        self.observations.push(observation);
+        Ok(())
+    }
+
+    // Forgets the last observation, for cards typed in by mistake
+    pub fn undo(&mut self) -> Result<Option<Observation>, BusinessError> {
+        let Some(last) = self.observations.pop() else {
+            return Ok(None);
+        };
+        let mut known = KnownGameState::initialize(self.hand.clone(), self.seat, None, self.dealer);
+        for observation in &self.observations {
+            known.observe(observation)?;
+        }
+        self.known = known;
+        Ok(Some(last))
+    }
+
+    // Lends the advisor out, until dismissed
+    pub fn consult(&mut self) -> Result<Consultation, BusinessError> {
+        Ok(Consultation {
+            known: self.known.clone(),
+            seat: self.seat,
+            advisor: self.advisor.take().ok_or(BusinessError::AdvisorBusy)?,
+        })
+    }
+
+    // Takes the advisor back, unless another one replaced it meanwhile
+    pub fn dismiss(&mut self, consultation: Consultation) {
+        self.advisor.get_or_insert(consultation.advisor);
+    }
+
+    pub fn suggest(&mut self) -> Result<Suggestion, BusinessError> {
+        let mut consultation = self.consult()?;
+        let suggestion = consultation.suggest();
+        self.dismiss(consultation);
+        suggestion
+    }
+
+    pub fn announcements(&mut self) -> Result<Announcements, BusinessError> {
+        let consultation = self.consult()?;
+        let announcements = consultation.announcements();
+        self.dismiss(consultation);
+        announcements
+    }
+
+    pub fn tracker(&self) -> Result<Tracker, BusinessError> {
+        let mut unseen: Vec<Card> = self
+            .known
+            .shared_state
+            .cards_left_to_play()
+            .into_iter()
+            .filter(|card| !self.known.player_state.hand.contains(card))
+            .collect();
+        unseen.sort_by_key(Card::index);
+        let mut voids: [Vec<Color>; 4] = Default::default();
+        for (player, void) in voids.iter_mut().enumerate() {
+            if player != self.seat as usize {
+                *void = self
+                    .known
+                    .get_known_constraints(player)?
+                    .voided_colors
+                    .into_iter()
+                    .collect();
+                void.sort();
+            }
+        }
+        Ok(Tracker {
+            trumps_out: unseen
+                .iter()
+                .filter(|card| card.color == Color::Trump)
+                .copied()
+                .collect(),
+            honours_out: unseen
+                .iter()
+                .filter(|card| card.is_oudler() || (card.color != Color::Trump && card.value == 14))
+                .copied()
+                .collect(),
+            voids,
+            taker_points: self.known.shared_state.current_score(),
+        })
+    }
+
+    // Once every card is down, the six never played are the kitty or the écart
+    pub fn score(&self) -> Result<Score, BusinessError> {
+        let shared_state = &self.known.shared_state;
+        if !shared_state.finished() {
+            return Err(EngineError::UnfinishedHand.into());
+        }
+        let mut kitty: Vec<Card> = shared_state.cards_left_to_play().into_iter().collect();
+        kitty.sort_by_key(Card::index);
+        let kitty: [Card; 6] = kitty.try_into().map_err(|_| {
+            EngineError::RustError(String::from("Six cards should be left unplayed"))
+        })?;
+        let mut state = GameState::initialize(Default::default(), kitty, self.dealer);
+        state.shared_state = shared_state.clone();
+        Ok(state.final_score()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{seq::IndexedRandom, SeedableRng};
+
+    use super::*;
+    use crate::business::{analysis::players::random::Random, game_engine::seed::TarotRng};
+
+    #[test]
+    fn test_companion_follows_a_deal_to_its_score() {
+        let mut rng = TarotRng::seed_from_u64(47);
+        let mut state = GameState::random_init_with_rng(&mut rng).unwrap();
+        let seat = 2;
+        let dealer = state.shared_state.dealer;
+        let mut companion = Companion::new(
+            state.players_state[seat as usize].hand.clone(),
+            seat,
+            dealer,
+            Box::new(Random::new(47)),
+        )
+        .unwrap();
+        assert!(companion
+            .observe(Observation::Bid {
+                player: (dealer + 2) % 4,
+                bid: None,
+            })
+            .is_err());
+        for offset in 1..=4 {
+            let player = (dealer + offset) % 4;
+            let bid = (offset == 1).then_some(GameType::GardeSans { chelem: false });
+            if player == seat {
+                assert!(matches!(companion.suggest(), Ok(Suggestion::Bid(_))));
+            }
+            state.shared_state.bid(player, bid).unwrap();
+            companion.observe(Observation::Bid { player, bid }).unwrap();
+        }
+        while let Some(player) = state.shared_state.next_to_play() {
+            let card = *state.legal_moves(player).choose(&mut rng).unwrap();
+            if player == seat {
+                let Suggestion::Card(suggested) = companion.suggest().unwrap() else {
+                    panic!("A card is expected from the user");
+                };
+                assert!(state.legal_moves(seat).contains(&suggested));
+            }
+            state.play_card(player, &card).unwrap();
+            companion
+                .observe(Observation::CardPlayed { player, card })
+                .unwrap();
+            let tracker = companion.tracker().unwrap();
+            assert!(tracker
+                .trumps_out
+                .iter()
+                .all(|card| !state.players_state[seat as usize].hand.contains(card)));
+        }
+        let last = companion.observations().last().cloned();
+        assert_eq!(companion.undo().unwrap(), last);
+        assert!(companion.score().is_err());
+        companion.observe(last.unwrap()).unwrap();
+        assert_eq!(companion.score().unwrap(), state.final_score().unwrap());
+        assert!(companion
+            .tracker()
+            .unwrap()
+            .trumps_out
+            .iter()
+            .all(|card| state.kitty.contains(card)));
+    }
+
+    #[test]
+    fn test_consultations_leave_the_companion_free() {
+        let state = GameState::random_init_with_rng(&mut TarotRng::seed_from_u64(47)).unwrap();
+        let dealer = state.shared_state.dealer;
+        let seat = (dealer + 1) % 4;
+        let mut companion = Companion::new(
+            state.players_state[seat as usize].hand.clone(),
+            seat,
+            dealer,
+            Box::new(Random::new(47)),
+        )
+        .unwrap();
+        let mut consultation = companion.consult().unwrap();
+        assert!(matches!(
+            companion.suggest(),
+            Err(BusinessError::AdvisorBusy)
+        ));
+        assert_eq!(companion.seed(), Some(47));
+        let bid = Observation::Bid {
+            player: seat,
+            bid: None,
+        };
+        companion.observe(bid).unwrap();
+        // The consultation answers for the deal as it was
+        assert!(matches!(consultation.suggest(), Ok(Suggestion::Bid(_))));
+        companion.dismiss(consultation);
+        assert!(matches!(
+            companion.suggest(),
+            Err(BusinessError::Engine(EngineError::OutOfOrderPlay))
+        ));
+    }
+
+    #[test]
+    fn test_network_guides_the_advisor() {
+        let mut state = GameState::random_init_with_rng(&mut TarotRng::seed_from_u64(47)).unwrap();
+        let dealer = state.shared_state.dealer;
+        let seat = (dealer + 1) % 4;
+        let advisor = search_advisor(50, 2, 47, Some(Network::new(&[8], 47)));
+        let mut companion = Companion::new(
+            state.players_state[seat as usize].hand.clone(),
+            seat,
+            dealer,
+            Box::new(advisor),
+        )
+        .unwrap();
+        for offset in 1..=4 {
+            let player = (dealer + offset) % 4;
+            let bid = (player == seat).then_some(GameType::GardeSans { chelem: false });
+            state.bid(player, bid).unwrap();
+            companion.observe(Observation::Bid { player, bid }).unwrap();
+        }
+        let Ok(Suggestion::Card(card)) = companion.suggest() else {
+            panic!("The taker leads the first trick");
+        };
+        assert!(state.legal_moves(seat).contains(&card));
+    }
+
+    #[test]
+    fn test_companion_suggests_the_ecart_once_the_kitty_is_seen() {
+        let mut rng = TarotRng::seed_from_u64(47);
+        let mut state = GameState::random_init_with_rng(&mut rng).unwrap();
+        let dealer = state.shared_state.dealer;
+        let seat = (dealer + 1) % 4;
+        let defender = (dealer + 2) % 4;
+        let mut companions = [seat, defender].map(|player| {
+            Companion::new(
+                state.players_state[player as usize].hand.clone(),
+                player,
+                dealer,
+                Box::new(Random::new(47)),
+            )
+            .unwrap()
+        });
+        for offset in 1..=4 {
+            let player = (dealer + offset) % 4;
+            let bid = (player == seat).then_some(GameType::Garde { chelem: false });
+            state.shared_state.bid(player, bid).unwrap();
+            for companion in &mut companions {
+                companion.observe(Observation::Bid { player, bid }).unwrap();
+            }
+        }
+        let [taker, defender] = &mut companions;
+        // Nothing to suggest before the kitty is turned over
+        assert!(taker.suggest().is_err());
+        for companion in [&mut *taker, &mut *defender] {
+            companion
+                .observe(Observation::KittyRevealed(state.kitty))
+                .unwrap();
+            assert!(companion
+                .observe(Observation::KittyRevealed(state.kitty))
+                .is_err());
+        }
+        let revealed = state.kitty;
+        state.take_kitty().unwrap();
+        let Suggestion::Aside(aside) = taker.suggest().unwrap() else {
+            panic!("The écart is expected from the taker");
+        };
+        assert!(state.players_state[seat as usize]
+            .check_aside(&aside)
+            .is_ok());
+        assert!(defender.observe(Observation::Aside(aside)).is_err());
+        taker.observe(Observation::Aside(aside)).unwrap();
+        state.discard(aside).unwrap();
+        assert_eq!(taker.known().kitty, Some(aside));
+        assert_eq!(defender.known().kitty, Some(revealed));
+        // The taker leads once the écart is set aside
+        let Suggestion::Card(card) = taker.suggest().unwrap() else {
+            panic!("A card is expected from the taker");
+        };
+        assert!(state.legal_moves(seat).contains(&card));
+    }
+}
@@ -0,0 +1,37 @@
+// Minimal unsigned big integer, only supporting the operations needed to
+// convert between mixed radix digits and base 62.
+#[derive(Debug, Clone, Default)]
+pub struct BigNumber {
+    limbs: Vec<u32>,
+}
+
+impl BigNumber {
+    pub fn mul_add(&mut self, multiplier: u32, addend: u32) {
+        let mut carry = addend as u64;
+        for limb in self.limbs.iter_mut() {
+            let value = (*limb as u64) * (multiplier as u64) + carry;
+            *limb = value as u32;
+            carry = value >> 32;
+        }
+        if carry > 0 {
+            self.limbs.push(carry as u32);
+        }
+    }
+
+    pub fn div_rem(&mut self, divisor: u32) -> u32 {
+        let mut remainder: u64 = 0;
+        for limb in self.limbs.iter_mut().rev() {
+            let value = (remainder << 32) | (*limb as u64);
+            *limb = (value / divisor as u64) as u32;
+            remainder = value % divisor as u64;
+        }
+        while self.limbs.last() == Some(&0) {
+            self.limbs.pop();
+        }
+        remainder as u32
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+}
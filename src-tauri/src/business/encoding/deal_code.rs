@@ -0,0 +1,164 @@
+use std::collections::HashSet;
+
+use super::{big_number::BigNumber, checksum, encoding_error::EncodingError};
+use crate::business::{
+    game_engine::{card::NUMBER_OF_CARDS, game_state::GameState},
+    Card,
+};
+
+const ALPHABET: &[u8; 62] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+// 78 base 5 digits for the location of each card, the dealer and a 16 bits
+// checksum fit in 34 base 62 characters
+pub const DEAL_CODE_LENGTH: usize = 34;
+const KITTY_LOCATION: u8 = 4;
+
+fn deal_locations(state: &GameState) -> Result<[u8; NUMBER_OF_CARDS], EncodingError> {
+    if !state.shared_state.played_tricks.is_empty() || state.shared_state.current_trick.is_some() {
+        return Err(EncodingError::InvalidDeal(String::from(
+            "Only a deal that has not been played yet can be encoded",
+        )));
+    }
+    let mut locations: [Option<u8>; NUMBER_OF_CARDS] = [None; NUMBER_OF_CARDS];
+    let mut place = |card: &Card, location: u8| {
+        let slot = &mut locations[card.index()];
+        if slot.is_some() {
+            return Err(EncodingError::InvalidDeal(format!(
+                "{} is dealt twice",
+                card
+            )));
+        }
+        *slot = Some(location);
+        Ok(())
+    };
+    for (seat, player_state) in state.players_state.iter().enumerate() {
+        if player_state.hand.len() != 18 {
+            return Err(EncodingError::InvalidDeal(format!(
+                "Player {} does not have 18 cards",
+                seat
+            )));
+        }
+        for card in &player_state.hand {
+            place(card, seat as u8)?;
+        }
+    }
+    for card in &state.kitty {
+        place(card, KITTY_LOCATION)?;
+    }
+    let mut result = [0; NUMBER_OF_CARDS];
+    for (index, location) in locations.iter().enumerate() {
+        result[index] = location.ok_or(EncodingError::InvalidDeal(String::from(
+            "Every card has to be dealt",
+        )))?;
+    }
+    Ok(result)
+}
+
+pub fn encode_deal(state: &GameState) -> Result<String, EncodingError> {
+    let mut digits = deal_locations(state)?.to_vec();
+    digits.push(state.shared_state.dealer);
+    let mut value = BigNumber::default();
+    for location in &digits[..NUMBER_OF_CARDS] {
+        value.mul_add(5, *location as u32);
+    }
+    value.mul_add(4, state.shared_state.dealer as u32);
+    value.mul_add(1 << 16, checksum(&digits) as u32);
+    let mut code: Vec<u8> = (0..DEAL_CODE_LENGTH)
+        .map(|_| ALPHABET[value.div_rem(62) as usize])
+        .collect();
+    code.reverse();
+    String::from_utf8(code).map_err(|e| EncodingError::InvalidDeal(e.to_string()))
+}
+
+pub fn decode_deal(code: &str) -> Result<GameState, EncodingError> {
+    let code = code.trim();
+    if code.chars().count() != DEAL_CODE_LENGTH {
+        return Err(EncodingError::InvalidLength {
+            expected: DEAL_CODE_LENGTH,
+            found: code.chars().count(),
+        });
+    }
+    let mut value = BigNumber::default();
+    for c in code.chars() {
+        let digit = ALPHABET
+            .iter()
+            .position(|allowed| *allowed as char == c)
+            .ok_or(EncodingError::InvalidCharacter(c))?;
+        value.mul_add(62, digit as u32);
+    }
+    let expected_checksum = value.div_rem(1 << 16) as u16;
+    let dealer = value.div_rem(4) as u8;
+    let mut digits = vec![0; NUMBER_OF_CARDS + 1];
+    for index in (0..NUMBER_OF_CARDS).rev() {
+        digits[index] = value.div_rem(5) as u8;
+    }
+    digits[NUMBER_OF_CARDS] = dealer;
+    if !value.is_zero() || checksum(&digits) != expected_checksum {
+        return Err(EncodingError::ChecksumMismatch);
+    }
+    let mut hands: [HashSet<Card>; 4] = Default::default();
+    let mut kitty: Vec<Card> = vec![];
+    for (index, location) in digits[..NUMBER_OF_CARDS].iter().enumerate() {
+        let card = Card::from_index(index)?;
+        match *location {
+            KITTY_LOCATION => kitty.push(card),
+            seat => {
+                hands[seat as usize].insert(card);
+            }
+        }
+    }
+    if hands.iter().any(|hand| hand.len() != 18) {
+        return Err(EncodingError::InvalidDeal(String::from(
+            "Every player should have 18 cards",
+        )));
+    }
+    let kitty: [Card; 6] = kitty
+        .try_into()
+        .map_err(|_| EncodingError::InvalidDeal(String::from("The kitty should have 6 cards")))?;
+    Ok(GameState::initialize(hands, kitty, dealer))
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::business::game_engine::seed::TarotRng;
+
+    #[test]
+    fn test_round_trip() {
+        let mut rng = TarotRng::seed_from_u64(28);
+        for _ in 0..50 {
+            let state = GameState::random_init_with_rng(&mut rng).unwrap();
+            let code = encode_deal(&state).unwrap();
+            assert_eq!(code.len(), DEAL_CODE_LENGTH);
+            let decoded = decode_deal(&code).unwrap();
+            assert_eq!(decoded.shared_state.dealer, state.shared_state.dealer);
+            for seat in 0..4 {
+                assert_eq!(
+                    decoded.players_state[seat].hand,
+                    state.players_state[seat].hand
+                );
+            }
+            let kitty: HashSet<Card> = state.kitty.into_iter().collect();
+            assert_eq!(decoded.kitty.into_iter().collect::<HashSet<Card>>(), kitty);
+        }
+    }
+
+    #[test]
+    fn test_detects_corruption() {
+        let state = GameState::random_init_with_rng(&mut TarotRng::seed_from_u64(28)).unwrap();
+        let code = encode_deal(&state).unwrap();
+        let mut corrupted: Vec<char> = code.chars().collect();
+        corrupted[10] = if corrupted[10] == 'a' { 'b' } else { 'a' };
+        let corrupted: String = corrupted.into_iter().collect();
+        assert!(decode_deal(&corrupted).is_err());
+        assert!(matches!(
+            decode_deal(&code[1..]),
+            Err(EncodingError::InvalidLength { .. })
+        ));
+        assert!(matches!(
+            decode_deal(&code.replacen(&code[..1], "-", 1)),
+            Err(EncodingError::InvalidCharacter('-'))
+        ));
+    }
+}
@@ -0,0 +1,44 @@
+use crate::business::game_engine::engine_error::EngineError;
+
+#[derive(Debug)]
+pub enum EncodingError {
+    InvalidLength { expected: usize, found: usize },
+    InvalidCharacter(char),
+    ChecksumMismatch,
+    UnsupportedVersion(u8),
+    InvalidDeal(String),
+    InvalidPosition(String),
+    Engine(EngineError),
+}
+
+impl From<EngineError> for EncodingError {
+    fn from(err: EngineError) -> Self {
+        EncodingError::Engine(err)
+    }
+}
+
+impl std::fmt::Display for EncodingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EncodingError::InvalidLength { expected, found } => write!(
+                f,
+                "Encoded data should have length {} but has length {}",
+                expected, found
+            ),
+            EncodingError::InvalidCharacter(arg) => {
+                write!(f, "Character {} is not allowed in a deal code", arg)
+            }
+            EncodingError::ChecksumMismatch => {
+                write!(f, "Checksum does not match, the data is corrupted",)
+            }
+            EncodingError::UnsupportedVersion(arg) => {
+                write!(f, "Encoding version {} is not supported", arg)
+            }
+            EncodingError::InvalidDeal(arg) => write!(f, "Invalid deal: {}", arg),
+            EncodingError::InvalidPosition(arg) => write!(f, "Invalid position: {}", arg),
+            EncodingError::Engine(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for EncodingError {}
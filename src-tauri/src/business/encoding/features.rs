@@ -0,0 +1,211 @@
+use super::encoding_error::EncodingError;
+use crate::business::{
+    analysis::reward::TOTAL_POINTS, game_engine::card::NUMBER_OF_CARDS, Card, Color, GameType,
+    KnownGameState,
+};
+
+// Layout of the features of a position seen by one player, as f32 values.
+// Seats are relative to that player: seat 0 is the player, 1 the next one to
+// play, 2 the one opposite and 3 the previous one. Card planes hold 78 values
+// by card index, 1 for a card in the plane and 0 otherwise.
+// - 0 to 77: the player's hand
+// - 78 to 389: cards played in finished tricks, one plane per seat
+// - 390 to 701: cards of the current trick, one plane per seat
+// - 702 to 779: the écart for the taker once set aside, the revealed kitty
+//   otherwise, left empty while unknown
+// - 780 to 1091: cards shown in a handful, one plane per seat
+// - 1092 to 1111: colors each seat has shown it lacks, in the order spade,
+//   heart, diamond, club, trump
+// - 1112 to 1115: highest trump each seat may still hold, divided by 21
+// - 1116 to 1119: cards left in the hand of each seat, divided by 18
+// - 1120 to 1124: contract, one-hot from Petit to Garde contre, then 1 if a
+//   chelem was announced
+// - 1125 to 1128: seat of the taker, one-hot, all 0 before the auction ends
+// - 1129 to 1132: seat of the dealer, one-hot
+// - 1133: tricks played, divided by 18
+// - 1134: card points already won by the taker, divided by the total
+// The version changes with any change of the layout.
+pub const FEATURES_VERSION: u32 = 1;
+pub const FEATURES_SIZE: usize = PROGRESS_OFFSET + 2;
+const HAND_OFFSET: usize = 0;
+const PLAYED_OFFSET: usize = HAND_OFFSET + NUMBER_OF_CARDS;
+const TRICK_OFFSET: usize = PLAYED_OFFSET + 4 * NUMBER_OF_CARDS;
+const KITTY_OFFSET: usize = TRICK_OFFSET + 4 * NUMBER_OF_CARDS;
+const HANDFULS_OFFSET: usize = KITTY_OFFSET + NUMBER_OF_CARDS;
+const VOIDS_OFFSET: usize = HANDFULS_OFFSET + 4 * NUMBER_OF_CARDS;
+const CEILINGS_OFFSET: usize = VOIDS_OFFSET + 4 * COLORS.len();
+const CARDS_LEFT_OFFSET: usize = CEILINGS_OFFSET + 4;
+const CONTRACT_OFFSET: usize = CARDS_LEFT_OFFSET + 4;
+const TAKER_OFFSET: usize = CONTRACT_OFFSET + 5;
+const DEALER_OFFSET: usize = TAKER_OFFSET + 4;
+const PROGRESS_OFFSET: usize = DEALER_OFFSET + 4;
+const COLORS: [Color; 5] = [
+    Color::Spade,
+    Color::Heart,
+    Color::Diamond,
+    Color::Club,
+    Color::Trump,
+];
+
+fn relative_seat(known: &KnownGameState, player: u8) -> usize {
+    ((player + 4 - known.player_index) % 4) as usize
+}
+
+fn set_cards<'a>(features: &mut [f32], offset: usize, cards: impl IntoIterator<Item = &'a Card>) {
+    for card in cards {
+        features[offset + card.index()] = 1.0;
+    }
+}
+
+fn contract_index(game_type: &GameType) -> usize {
+    match game_type {
+        GameType::Petit { chelem: _ } => 0,
+        GameType::Garde { chelem: _ } => 1,
+        GameType::GardeSans { chelem: _ } => 2,
+        GameType::GardeContre { chelem: _ } => 3,
+    }
+}
+
+pub fn encode_features(known: &KnownGameState) -> Result<Vec<f32>, EncodingError> {
+    let shared_state = &known.shared_state;
+    let mut features = vec![0.0; FEATURES_SIZE];
+    set_cards(&mut features, HAND_OFFSET, &known.player_state.hand);
+    for trick in &shared_state.played_tricks {
+        for (player, card) in trick.cards.iter().enumerate() {
+            let seat = relative_seat(known, player as u8);
+            set_cards(
+                &mut features,
+                PLAYED_OFFSET + seat * NUMBER_OF_CARDS,
+                [card],
+            );
+        }
+    }
+    if let Some(trick) = &shared_state.current_trick {
+        for (player, card) in trick.cards.iter().enumerate() {
+            let seat = relative_seat(known, player as u8);
+            set_cards(&mut features, TRICK_OFFSET + seat * NUMBER_OF_CARDS, card);
+        }
+    }
+    if let Some(kitty) = &known.kitty {
+        set_cards(&mut features, KITTY_OFFSET, kitty);
+    }
+    for player in 0..4 {
+        let seat = relative_seat(known, player);
+        if let Some(declared) = &shared_state.declared_handfuls[player as usize] {
+            set_cards(
+                &mut features,
+                HANDFULS_OFFSET + seat * NUMBER_OF_CARDS,
+                &declared.cards,
+            );
+        }
+        let constraint = known.get_known_constraints(player as usize)?;
+        for (index, color) in COLORS.iter().enumerate() {
+            if constraint.voided_colors.contains(color) {
+                features[VOIDS_OFFSET + seat * COLORS.len() + index] = 1.0;
+            }
+        }
+        features[CEILINGS_OFFSET + seat] = constraint.highest_trump as f32 / 21.0;
+        features[CARDS_LEFT_OFFSET + seat] = constraint.number_cards as f32 / 18.0;
+    }
+    if let Some(game_type) = &shared_state.game_type {
+        features[CONTRACT_OFFSET + contract_index(game_type)] = 1.0;
+        if game_type.chelem_announced() {
+            features[CONTRACT_OFFSET + 4] = 1.0;
+        }
+    }
+    if let Some(taker) = shared_state.taker {
+        features[TAKER_OFFSET + relative_seat(known, taker)] = 1.0;
+    }
+    features[DEALER_OFFSET + relative_seat(known, shared_state.dealer)] = 1.0;
+    features[PROGRESS_OFFSET] = shared_state.played_tricks.len() as f32 / 18.0;
+    features[PROGRESS_OFFSET + 1] = shared_state.current_score() as f32 / TOTAL_POINTS as f32;
+    Ok(features)
+}
+
+// Features of several positions one after the other, a row-major matrix with
+// FEATURES_SIZE columns
+pub fn encode_features_batch(positions: &[KnownGameState]) -> Result<Vec<f32>, EncodingError> {
+    let mut batch = Vec::with_capacity(positions.len() * FEATURES_SIZE);
+    for known in positions {
+        batch.extend(encode_features(known)?);
+    }
+    Ok(batch)
+}
+
+// 1 by card index for the cards the player may put down now, all 0 when it is
+// not the player's turn to play a card
+pub fn legal_move_mask(known: &KnownGameState) -> Vec<f32> {
+    let mut mask = vec![0.0; NUMBER_OF_CARDS];
+    let shared_state = &known.shared_state;
+    let playing = shared_state.taker.is_some()
+        && known.player_state.hand.len() <= 18
+        && shared_state.next_to_play() == Some(known.player_index);
+    if playing {
+        let allowed = known
+            .player_state
+            .sorted_cards_allowed(&shared_state.current_or_new_trick());
+        set_cards(&mut mask, 0, &allowed);
+    }
+    mask
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::business::game_engine::{game_state::GameState, seed::TarotRng};
+
+    #[test]
+    fn test_features_follow_the_layout() {
+        assert_eq!(FEATURES_SIZE, 1135);
+        let taker = 1;
+        let state = GameState::played_at_random(
+            &mut TarotRng::seed_from_u64(49),
+            taker,
+            GameType::GardeSans { chelem: false },
+            30,
+        );
+        for player in 0..4 {
+            let known = KnownGameState::from_omniscient(&state, player);
+            let features = encode_features(&known).unwrap();
+            assert_eq!(features.len(), FEATURES_SIZE);
+            let plane_sum =
+                |offset: usize| -> f32 { features[offset..offset + NUMBER_OF_CARDS].iter().sum() };
+            assert_eq!(plane_sum(HAND_OFFSET), known.player_state.hand.len() as f32);
+            // Every card played is in exactly one plane of one seat
+            let played: f32 = (0..8)
+                .map(|plane| plane_sum(PLAYED_OFFSET + plane * NUMBER_OF_CARDS))
+                .sum();
+            assert_eq!(played, 30.0);
+            // Seats are counted from the player
+            let seat = relative_seat(&known, taker);
+            assert_eq!(features[TAKER_OFFSET + seat], 1.0);
+            assert_eq!(features[CONTRACT_OFFSET + 2], 1.0);
+            let current = state.shared_state.current_trick.unwrap();
+            let leader_card = current.cards[current.leader as usize].unwrap();
+            let leader_seat = relative_seat(&known, current.leader);
+            assert_eq!(
+                features[TRICK_OFFSET + leader_seat * NUMBER_OF_CARDS + leader_card.index()],
+                1.0
+            );
+
+            let mask = legal_move_mask(&known);
+            let expected: Vec<usize> = if state.shared_state.next_to_play() == Some(player) {
+                state.legal_moves(player).iter().map(Card::index).collect()
+            } else {
+                vec![]
+            };
+            let masked: Vec<usize> = (0..NUMBER_OF_CARDS).filter(|i| mask[*i] == 1.0).collect();
+            assert_eq!(masked, expected);
+        }
+        let positions: Vec<KnownGameState> = (0..4)
+            .map(|player| KnownGameState::from_omniscient(&state, player))
+            .collect();
+        let batch = encode_features_batch(&positions).unwrap();
+        assert_eq!(
+            batch[FEATURES_SIZE..2 * FEATURES_SIZE],
+            encode_features(&positions[1]).unwrap()[..]
+        );
+    }
+}
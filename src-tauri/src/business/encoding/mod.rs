@@ -0,0 +1,21 @@
+mod big_number;
+pub mod deal_code;
+pub mod encoding_error;
+pub mod features;
+pub mod position;
+
+pub use deal_code::{decode_deal, encode_deal};
+pub use features::{encode_features, encode_features_batch, legal_move_mask};
+pub use position::{
+    decode_game_state, decode_known_game_state, encode_game_state, encode_known_game_state,
+};
+
+fn checksum(bytes: &[u8]) -> u16 {
+    // FNV-1a folded on 16 bits
+    let mut hash: u32 = 0x811c9dc5;
+    for byte in bytes {
+        hash ^= *byte as u32;
+        hash = hash.wrapping_mul(0x01000193);
+    }
+    ((hash >> 16) ^ (hash & 0xffff)) as u16
+}
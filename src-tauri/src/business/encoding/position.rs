@@ -0,0 +1,607 @@
+use std::collections::HashSet;
+
+use super::{checksum, encoding_error::EncodingError};
+use crate::business::{
+    game_engine::{
+        card::NUMBER_OF_CARDS,
+        game_state::{trumps_in, GameState},
+        handfuls::{DeclaredHandfuls, Handfuls},
+        player_game_state::PlayerGameState,
+        shared_game_state::SharedGameState,
+        trick::Trick,
+    },
+    Card, Color, GameType, KnownGameState,
+};
+
+// Layout of an encoded position:
+// - byte 0: encoding version
+// - byte 1: dealer
+// - byte 2: taker, 0xff if nobody took
+// - byte 3: contract (0 to 3 from Petit to Garde contre, +4 if a chelem was
+//   announced), 0xff if there is no contract
+// - byte 4: seat of the player whose knowledge is encoded, 0xff for a full state
+// - byte 5: number of bids heard, +8 in a full state once the écart is set
+//   aside
+// - bytes 6 to 9: bids heard, from the player after the dealer, coded as the
+//   contract, 0xff for a pass or a bid not heard yet
+// - bytes 10 to 87: location of each card, by card index. 0 to 3 for the hand
+//   of a player, 4 for the kitty, 5 for an unknown location and 6 + 4 * trick
+//   + seat for a played card. The high bit is set for cards shown in a handful.
+//   In the knowledge of a player, 4 only marks the trumps shown with the écart
+// - bytes 88 to 97: bitmap of the kitty as turned over, by card index. In the
+//   knowledge of the taker, the écart once set aside
+// - bytes 98 and 99: checksum of the previous bytes, big endian
+pub const POSITION_VERSION: u8 = 2;
+pub const POSITION_SIZE: usize = 100;
+const AUCTION_OFFSET: usize = 6;
+const HEADER_SIZE: usize = AUCTION_OFFSET + 4;
+const KITTY_BITMAP_OFFSET: usize = HEADER_SIZE + NUMBER_OF_CARDS;
+const CHECKSUM_OFFSET: usize = KITTY_BITMAP_OFFSET + 10;
+const NO_VALUE: u8 = 0xff;
+const SET_ASIDE_FLAG: u8 = 8;
+const KITTY: u8 = 4;
+const UNKNOWN: u8 = 5;
+const FIRST_PLAYED: u8 = 6;
+const LAST_PLAYED: u8 = FIRST_PLAYED + 18 * 4 - 1;
+const HANDFUL_FLAG: u8 = 0x80;
+
+fn invalid(message: &str) -> EncodingError {
+    EncodingError::InvalidPosition(String::from(message))
+}
+
+fn contract_code(game_type: &GameType) -> u8 {
+    let base = match game_type {
+        GameType::Petit { chelem: _ } => 0,
+        GameType::Garde { chelem: _ } => 1,
+        GameType::GardeSans { chelem: _ } => 2,
+        GameType::GardeContre { chelem: _ } => 3,
+    };
+    base + if game_type.chelem_announced() { 4 } else { 0 }
+}
+
+fn contract_from_code(code: u8) -> Result<Option<GameType>, EncodingError> {
+    let base = match code & 3 {
+        _ if code == NO_VALUE => return Ok(None),
+        _ if code > 7 => return Err(invalid("Unknown contract")),
+        0 => GameType::Petit { chelem: false },
+        1 => GameType::Garde { chelem: false },
+        2 => GameType::GardeSans { chelem: false },
+        _ => GameType::GardeContre { chelem: false },
+    };
+    Ok(Some(base.with_chelem(code & 4 != 0)))
+}
+
+fn set_location(
+    bytes: &mut [u8; POSITION_SIZE],
+    card: &Card,
+    location: u8,
+) -> Result<(), EncodingError> {
+    let byte = &mut bytes[HEADER_SIZE + card.index()];
+    if *byte != UNKNOWN {
+        return Err(EncodingError::InvalidPosition(format!(
+            "{} is at two places at once",
+            card
+        )));
+    }
+    *byte = location;
+    Ok(())
+}
+
+fn encode_shared(
+    shared_state: &SharedGameState,
+    auction: &[Option<GameType>],
+    perspective: Option<u8>,
+) -> Result<[u8; POSITION_SIZE], EncodingError> {
+    if auction.len() > 4 {
+        return Err(invalid("Every player only bids once"));
+    }
+    let mut bytes = [0; POSITION_SIZE];
+    bytes[0] = POSITION_VERSION;
+    bytes[1] = shared_state.dealer;
+    bytes[2] = shared_state.taker.unwrap_or(NO_VALUE);
+    bytes[3] = shared_state
+        .game_type
+        .as_ref()
+        .map(contract_code)
+        .unwrap_or(NO_VALUE);
+    bytes[4] = perspective.unwrap_or(NO_VALUE);
+    bytes[5] = auction.len() as u8;
+    bytes[AUCTION_OFFSET..HEADER_SIZE].fill(NO_VALUE);
+    for (index, bid) in auction.iter().enumerate() {
+        bytes[AUCTION_OFFSET + index] = bid.as_ref().map(contract_code).unwrap_or(NO_VALUE);
+    }
+    bytes[HEADER_SIZE..KITTY_BITMAP_OFFSET].fill(UNKNOWN);
+    let current_trick = shared_state.current_trick.map(|trick| trick.cards);
+    let tricks = shared_state
+        .played_tricks
+        .iter()
+        .map(|trick| trick.cards.map(Some))
+        .chain(current_trick);
+    for (trick_index, cards) in tricks.enumerate() {
+        for (seat, card) in cards.iter().enumerate() {
+            if let Some(card) = card {
+                set_location(
+                    &mut bytes,
+                    card,
+                    FIRST_PLAYED + (trick_index * 4 + seat) as u8,
+                )?;
+            }
+        }
+    }
+    Ok(bytes)
+}
+
+fn encode_handfuls(bytes: &mut [u8; POSITION_SIZE], shared_state: &SharedGameState) {
+    for (seat, declared) in shared_state.declared_handfuls.iter().enumerate() {
+        for card in declared.iter().flat_map(|declared| declared.cards.iter()) {
+            let byte = &mut bytes[HEADER_SIZE + card.index()];
+            if *byte == UNKNOWN {
+                *byte = seat as u8;
+            }
+            *byte |= HANDFUL_FLAG;
+        }
+    }
+}
+
+fn set_in_kitty_bitmap(bytes: &mut [u8; POSITION_SIZE], cards: &[Card]) {
+    for card in cards {
+        bytes[KITTY_BITMAP_OFFSET + card.index() / 8] |= 1 << (card.index() % 8);
+    }
+}
+
+fn seal(mut bytes: [u8; POSITION_SIZE]) -> [u8; POSITION_SIZE] {
+    let sum = checksum(&bytes[..CHECKSUM_OFFSET]);
+    bytes[CHECKSUM_OFFSET..].copy_from_slice(&sum.to_be_bytes());
+    bytes
+}
+
+pub fn encode_game_state(state: &GameState) -> Result<[u8; POSITION_SIZE], EncodingError> {
+    let mut bytes = encode_shared(&state.shared_state, &state.auction, None)?;
+    for (seat, player_state) in state.players_state.iter().enumerate() {
+        for card in &player_state.hand {
+            set_location(&mut bytes, card, seat as u8)?;
+        }
+    }
+    // Cards of the kitty the taker holds until setting the écart aside
+    let taken = state.revealed_kitty.is_none()
+        && state
+            .shared_state
+            .taker
+            .is_some_and(|taker| state.players_state[taker as usize].hand.len() > 18);
+    if !taken {
+        for card in &state.kitty {
+            set_location(&mut bytes, card, KITTY)?;
+        }
+    }
+    if state.shared_state.kitty_should_be_revealed() {
+        set_in_kitty_bitmap(&mut bytes, &state.revealed_kitty.unwrap_or(state.kitty));
+    }
+    if state.revealed_kitty.is_some() {
+        bytes[5] |= SET_ASIDE_FLAG;
+    }
+    encode_handfuls(&mut bytes, &state.shared_state);
+    Ok(seal(bytes))
+}
+
+pub fn encode_known_game_state(
+    state: &KnownGameState,
+) -> Result<[u8; POSITION_SIZE], EncodingError> {
+    let mut bytes = encode_shared(
+        &state.shared_state,
+        &state.auction,
+        Some(state.player_index),
+    )?;
+    for card in &state.player_state.hand {
+        set_location(&mut bytes, card, state.player_index)?;
+    }
+    for card in &state.shared_state.trumps_aside {
+        set_location(&mut bytes, card, KITTY)?;
+    }
+    if let Some(kitty) = state.kitty {
+        set_in_kitty_bitmap(&mut bytes, &kitty);
+    }
+    encode_handfuls(&mut bytes, &state.shared_state);
+    Ok(seal(bytes))
+}
+
+struct DecodedPosition {
+    shared_state: SharedGameState,
+    auction: Vec<Option<GameType>>,
+    set_aside: bool,
+    perspective: Option<u8>,
+    locations: [u8; NUMBER_OF_CARDS],
+    kitty: Vec<Card>,
+}
+
+fn decode_shared(bytes: &[u8]) -> Result<DecodedPosition, EncodingError> {
+    if bytes.len() != POSITION_SIZE {
+        return Err(EncodingError::InvalidLength {
+            expected: POSITION_SIZE,
+            found: bytes.len(),
+        });
+    }
+    if bytes[0] != POSITION_VERSION {
+        return Err(EncodingError::UnsupportedVersion(bytes[0]));
+    }
+    if checksum(&bytes[..CHECKSUM_OFFSET]).to_be_bytes() != bytes[CHECKSUM_OFFSET..] {
+        return Err(EncodingError::ChecksumMismatch);
+    }
+    let dealer = bytes[1];
+    let taker = match bytes[2] {
+        NO_VALUE => None,
+        seat if seat < 4 => Some(seat),
+        _ => return Err(invalid("Unknown taker")),
+    };
+    let perspective = match bytes[4] {
+        NO_VALUE => None,
+        seat if seat < 4 => Some(seat),
+        _ => return Err(invalid("Unknown player")),
+    };
+    if dealer > 3 {
+        return Err(invalid("Unknown dealer"));
+    }
+    let bids = (bytes[5] & !SET_ASIDE_FLAG) as usize;
+    if bids > 4 {
+        return Err(invalid("Every player only bids once"));
+    }
+    let auction = bytes[AUCTION_OFFSET..AUCTION_OFFSET + bids]
+        .iter()
+        .map(|code| contract_from_code(*code))
+        .collect::<Result<Vec<_>, _>>()?;
+    if bytes[AUCTION_OFFSET + bids..HEADER_SIZE]
+        .iter()
+        .any(|code| *code != NO_VALUE)
+    {
+        return Err(invalid("Bids are recorded past the auction"));
+    }
+    let mut shared_state = SharedGameState::initialize(dealer);
+    shared_state.taker = taker;
+    shared_state.game_type = contract_from_code(bytes[3])?;
+
+    let mut locations = [0; NUMBER_OF_CARDS];
+    let mut played: Vec<[Option<Card>; 4]> = vec![[None; 4]; 18];
+    let mut handfuls: [HashSet<Card>; 4] = Default::default();
+    let mut kitty = vec![];
+    for (index, location) in locations.iter_mut().enumerate() {
+        let byte = bytes[HEADER_SIZE + index];
+        let card = Card::from_index(index)?;
+        *location = byte & !HANDFUL_FLAG;
+        let owner = match *location {
+            seat @ 0..=3 => Some(seat),
+            KITTY | UNKNOWN => None,
+            code @ FIRST_PLAYED..=LAST_PLAYED => {
+                let slot = (code - FIRST_PLAYED) as usize;
+                played[slot / 4][slot % 4] = Some(card);
+                Some((slot % 4) as u8)
+            }
+            _ => return Err(invalid("Unknown card location")),
+        };
+        if byte & HANDFUL_FLAG != 0 {
+            let owner = owner.ok_or(invalid("Handful card without owner"))?;
+            handfuls[owner as usize].insert(card);
+        }
+        if bytes[KITTY_BITMAP_OFFSET + index / 8] & (1 << (index % 8)) != 0 {
+            kitty.push(card);
+        }
+    }
+
+    let complete_tricks = played
+        .iter()
+        .take_while(|cards| cards.iter().all(Option::is_some))
+        .count();
+    for cards in &played[..complete_tricks] {
+        let trick = Trick {
+            cards: *cards,
+            leader: shared_state.player_to_lead(),
+        };
+        shared_state.played_tricks.push(trick.into_played()?);
+    }
+    if let Some(cards) = played.get(complete_tricks) {
+        if cards.iter().any(Option::is_some) {
+            let trick = Trick {
+                cards: *cards,
+                leader: shared_state.player_to_lead(),
+            };
+            let played_in_order = (0..4)
+                .map(|offset| trick.cards[((trick.leader + offset) % 4) as usize].is_some())
+                .skip_while(|is_played| *is_played)
+                .all(|is_played| !is_played);
+            if !played_in_order {
+                return Err(invalid(
+                    "Cards of the current trick were not played in order",
+                ));
+            }
+            shared_state.current_trick = Some(trick);
+        }
+    }
+    if played[(complete_tricks + 1).min(18)..]
+        .iter()
+        .flatten()
+        .any(Option::is_some)
+    {
+        return Err(invalid("Tricks are missing cards"));
+    }
+
+    for (seat, cards) in handfuls.into_iter().enumerate() {
+        if cards.is_empty() {
+            continue;
+        }
+        let handful = match cards.len() {
+            10 => Handfuls::Simple,
+            13 => Handfuls::Double,
+            15 => Handfuls::Triple,
+            _ => return Err(invalid("Handful with an invalid number of cards")),
+        };
+        shared_state.declared_handfuls[seat] = Some(DeclaredHandfuls { handful, cards });
+    }
+
+    Ok(DecodedPosition {
+        shared_state,
+        auction,
+        set_aside: bytes[5] & SET_ASIDE_FLAG != 0,
+        perspective,
+        locations,
+        kitty,
+    })
+}
+
+// Cards a seat holds once the écart is set aside
+fn cards_in_hand(shared_state: &SharedGameState, seat: u8) -> usize {
+    let in_current_trick = shared_state
+        .current_trick
+        .is_some_and(|trick| trick.cards[seat as usize].is_some());
+    18 - shared_state.played_tricks.len() - usize::from(in_current_trick)
+}
+
+pub fn decode_game_state(bytes: &[u8]) -> Result<GameState, EncodingError> {
+    let decoded = decode_shared(bytes)?;
+    if decoded.perspective.is_some() {
+        return Err(invalid(
+            "Position only contains the knowledge of one player",
+        ));
+    }
+    let mut hands: [HashSet<Card>; 4] = Default::default();
+    let mut kitty = vec![];
+    for (index, location) in decoded.locations.iter().enumerate() {
+        let card = Card::from_index(index)?;
+        match *location {
+            seat @ 0..=3 => {
+                hands[seat as usize].insert(card);
+            }
+            KITTY => kitty.push(card),
+            UNKNOWN => return Err(invalid("Full positions can not contain unknown cards")),
+            _ => (),
+        }
+    }
+    let mut shared_state = decoded.shared_state;
+    // The taker holds the kitty until setting the écart aside
+    let taken = kitty.is_empty() && !decoded.set_aside;
+    let kitty: [Card; 6] = if taken { decoded.kitty.clone() } else { kitty }
+        .try_into()
+        .map_err(|_| invalid("The kitty should have 6 cards"))?;
+    let revealed_kitty: Option<[Card; 6]> = if decoded.set_aside {
+        Some(
+            decoded
+                .kitty
+                .try_into()
+                .map_err(|_| invalid("The kitty turned over should have 6 cards"))?,
+        )
+    } else if decoded.kitty.is_empty() || decoded.kitty == kitty {
+        None
+    } else {
+        return Err(invalid("The kitty turned over is not the kitty"));
+    };
+    let taker = shared_state.taker;
+    if taken
+        && !taker.is_some_and(|taker| {
+            kitty
+                .iter()
+                .all(|card| hands[taker as usize].contains(card))
+        })
+    {
+        return Err(invalid("The taker should hold the kitty"));
+    }
+    let expected = |seat: u8| {
+        cards_in_hand(&shared_state, seat) + if taken && taker == Some(seat) { 6 } else { 0 }
+    };
+    if (0..4).any(|seat| hands[seat as usize].len() != expected(seat)) {
+        return Err(invalid("Hands do not match the tricks played"));
+    }
+    if decoded.set_aside {
+        shared_state.trumps_aside = trumps_in(&kitty);
+    }
+    Ok(GameState {
+        players_state: hands.map(|hand| PlayerGameState { hand }),
+        kitty,
+        revealed_kitty,
+        shared_state,
+        auction: decoded.auction,
+    })
+}
+
+pub fn decode_known_game_state(bytes: &[u8]) -> Result<KnownGameState, EncodingError> {
+    let decoded = decode_shared(bytes)?;
+    let player_index = decoded
+        .perspective
+        .ok_or(invalid("Position does not belong to a player"))?;
+    let mut hand = HashSet::new();
+    let mut trumps_aside = vec![];
+    for (index, location) in decoded.locations.iter().enumerate() {
+        let card = Card::from_index(index)?;
+        match *location {
+            seat if seat == player_index => {
+                hand.insert(card);
+            }
+            seat @ 0..=3
+                if !decoded.shared_state.declared_handfuls[seat as usize]
+                    .as_ref()
+                    .is_some_and(|declared| declared.cards.contains(&card)) =>
+            {
+                return Err(invalid("Hidden cards can not be located"));
+            }
+            KITTY if card.color == Color::Trump => trumps_aside.push(card),
+            KITTY => return Err(invalid("Hidden cards can not be located")),
+            _ => (),
+        }
+    }
+    let mut shared_state = decoded.shared_state;
+    shared_state.trumps_aside = trumps_aside;
+    let kitty = match decoded.kitty.len() {
+        0 => None,
+        6 => Some(decoded.kitty.try_into().unwrap()),
+        _ => return Err(invalid("The kitty should have 6 cards")),
+    };
+    Ok(KnownGameState {
+        player_state: PlayerGameState { hand },
+        player_index,
+        kitty,
+        shared_state,
+        auction: decoded.auction,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{seq::IteratorRandom, SeedableRng};
+
+    use super::*;
+    use crate::business::{analysis::ecart::legal_asides, game_engine::seed::TarotRng};
+
+    fn mid_game_state() -> GameState {
+        let mut rng = TarotRng::seed_from_u64(28);
+        let mut state = GameState::random_init_with_rng(&mut rng).unwrap();
+        let taker = (state.shared_state.dealer + 1) % 4;
+        state
+            .bid(taker, Some(GameType::GardeSans { chelem: false }))
+            .unwrap();
+        for offset in 1..4 {
+            state.bid((taker + offset) % 4, None).unwrap();
+        }
+        for _ in 0..37 {
+            let player = state.shared_state.next_to_play().unwrap();
+            let card = **state.cards_allowed(player).iter().choose(&mut rng).unwrap();
+            state.play_card(player, &card).unwrap();
+        }
+        state
+    }
+
+    #[test]
+    fn test_game_state_round_trip() {
+        let state = mid_game_state();
+        let decoded = decode_game_state(&encode_game_state(&state).unwrap()).unwrap();
+        for seat in 0..4 {
+            assert_eq!(
+                decoded.players_state[seat].hand,
+                state.players_state[seat].hand
+            );
+        }
+        assert_eq!(decoded.auction, state.auction);
+        let shared_state = &decoded.shared_state;
+        assert_eq!(shared_state.taker, state.shared_state.taker);
+        assert_eq!(shared_state.game_type, state.shared_state.game_type);
+        assert_eq!(shared_state.played_tricks.len(), 9);
+        for (decoded, original) in shared_state
+            .played_tricks
+            .iter()
+            .zip(&state.shared_state.played_tricks)
+        {
+            assert_eq!(decoded.cards, original.cards);
+            assert_eq!(decoded.winner, original.winner);
+            assert_eq!(decoded.leader, original.leader);
+        }
+        assert_eq!(
+            shared_state.current_trick.map(|trick| trick.cards),
+            state.shared_state.current_trick.map(|trick| trick.cards)
+        );
+    }
+
+    #[test]
+    fn test_known_game_state_round_trip_and_corruption() {
+        let state = mid_game_state();
+        let known = KnownGameState::from_omniscient(&state, 2);
+        let mut bytes = encode_known_game_state(&known).unwrap();
+        let decoded = decode_known_game_state(&bytes).unwrap();
+        assert_eq!(decoded.player_index, 2);
+        assert_eq!(decoded.player_state.hand, known.player_state.hand);
+        assert_eq!(decoded.auction, known.auction);
+        assert!(decode_game_state(&bytes).is_err());
+        bytes[40] ^= 1;
+        assert!(matches!(
+            decode_known_game_state(&bytes),
+            Err(EncodingError::ChecksumMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_rejects_hands_not_matching_the_tricks() {
+        let state = mid_game_state();
+        let bytes = encode_game_state(&state).unwrap();
+        // A card moved from one hand to another keeps every card in one place
+        let card = state.players_state[0]
+            .hand
+            .iter()
+            .min_by_key(|card| card.index())
+            .unwrap();
+        let mut moved = bytes;
+        moved[HEADER_SIZE + card.index()] = 1;
+        assert!(matches!(
+            decode_game_state(&seal(moved)),
+            Err(EncodingError::InvalidPosition(_))
+        ));
+        // So does a card of a hand swapped with one of the kitty
+        let mut swapped = bytes;
+        swapped[HEADER_SIZE + card.index()] = KITTY;
+        swapped[HEADER_SIZE + state.kitty[0].index()] = 0;
+        assert!(decode_game_state(&seal(swapped)).is_ok());
+        swapped[HEADER_SIZE + state.kitty[0].index()] = 2;
+        assert!(decode_game_state(&seal(swapped)).is_err());
+    }
+
+    #[test]
+    fn test_kitty_and_ecart_round_trip() {
+        // Player 0 takes the T17 to T21 and the Excuse from the kitty, and
+        // must set aside three trumps
+        let mut hand: Vec<Card> = (2..=16)
+            .map(|value| Card::new(Color::Trump, value).unwrap())
+            .collect();
+        hand.extend((1..=3).map(|value| Card::new(Color::Spade, value).unwrap()));
+        let mut state = GameState::with_first_hand(hand);
+        state
+            .bid(0, Some(GameType::Garde { chelem: false }))
+            .unwrap();
+        state.bid(1, None).unwrap();
+        let during_auction = decode_game_state(&encode_game_state(&state).unwrap()).unwrap();
+        assert_eq!(during_auction.auction, state.auction);
+        for player in 2..4 {
+            state.bid(player, None).unwrap();
+        }
+        let dealt = state.kitty;
+        state.take_kitty().unwrap();
+        let taken = decode_game_state(&encode_game_state(&state).unwrap()).unwrap();
+        assert_eq!(taken.players_state[0].hand.len(), 24);
+        assert_eq!(taken.kitty, dealt);
+        assert_eq!(taken.revealed_kitty, None);
+
+        let aside = legal_asides(&state.players_state[0].hand)[0];
+        state.discard(aside).unwrap();
+        let decoded = decode_game_state(&encode_game_state(&state).unwrap()).unwrap();
+        assert_eq!(decoded.kitty, aside);
+        assert_eq!(decoded.revealed_kitty, Some(dealt));
+        assert_eq!(
+            decoded.shared_state.trumps_aside,
+            state.shared_state.trumps_aside
+        );
+        assert_eq!(decoded.auction, state.auction);
+        for player in 0..4 {
+            let known = KnownGameState::from_omniscient(&state, player);
+            let decoded =
+                decode_known_game_state(&encode_known_game_state(&known).unwrap()).unwrap();
+            assert_eq!(decoded.kitty, known.kitty);
+            assert_eq!(
+                decoded.shared_state.trumps_aside,
+                state.shared_state.trumps_aside
+            );
+            assert_eq!(decoded.auction, state.auction);
+        }
+    }
+}
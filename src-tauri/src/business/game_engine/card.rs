@@ -1,7 +1,8 @@
 use super::engine_error::EngineError;
-use std::{collections::HashSet, fmt};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, fmt, str::FromStr};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum Color {
     Spade,
     Heart,
@@ -17,12 +18,30 @@ impl fmt::Display for Color {
     }
 }
 
-#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+pub const NUMBER_OF_CARDS: usize = 78;
+
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "UncheckedCard")]
 pub struct Card {
     pub color: Color,
     pub value: u8,
 }
 
+// Deserialized cards go through `Card::new`
+#[derive(Deserialize)]
+struct UncheckedCard {
+    color: Color,
+    value: u8,
+}
+
+impl TryFrom<UncheckedCard> for Card {
+    type Error = EngineError;
+
+    fn try_from(card: UncheckedCard) -> Result<Self, Self::Error> {
+        Card::new(card.color, card.value)
+    }
+}
+
 impl Card {
     pub fn new(color: Color, value: u8) -> Result<Card, EngineError> {
         let res = Card { color, value };
@@ -50,6 +69,59 @@ impl Card {
         }
     }
 
+    pub fn index(&self) -> usize {
+        let value = self.value as usize;
+        match self.color {
+            Color::Spade => value - 1,
+            Color::Heart => 13 + value,
+            Color::Diamond => 27 + value,
+            Color::Club => 41 + value,
+            Color::Trump => 55 + value,
+            Color::Excuse => 77,
+        }
+    }
+
+    pub fn from_index(index: usize) -> Result<Card, EngineError> {
+        let (color, value) = match index {
+            0..=13 => (Color::Spade, index + 1),
+            14..=27 => (Color::Heart, index - 13),
+            28..=41 => (Color::Diamond, index - 27),
+            42..=55 => (Color::Club, index - 41),
+            56..=76 => (Color::Trump, index - 55),
+            77 => (Color::Excuse, 0),
+            _ => return Err(EngineError::InvalidCardValue(index as u8)),
+        };
+        Card::new(color, value as u8)
+    }
+
+    pub fn is_oudler(&self) -> bool {
+        self.color == Color::Excuse
+            || (self.color == Color::Trump && (self.value == 1 || self.value == 21))
+    }
+
+    pub fn notation(&self) -> String {
+        match self.color {
+            Color::Excuse => String::from("EX"),
+            Color::Trump => format!("T{}", self.value),
+            color => {
+                let rank = match self.value {
+                    14 => String::from("K"),
+                    13 => String::from("Q"),
+                    12 => String::from("C"),
+                    11 => String::from("J"),
+                    other => other.to_string(),
+                };
+                let suit = match color {
+                    Color::Spade => 'S',
+                    Color::Heart => 'H',
+                    Color::Diamond => 'D',
+                    _ => 'C',
+                };
+                format!("{}{}", rank, suit)
+            }
+        }
+    }
+
     pub fn points(self: &Self) -> usize {
         match self {
             Card {
@@ -424,3 +496,41 @@ impl fmt::Display for Card {
         }
     }
 }
+
+impl FromStr for Card {
+    type Err = EngineError;
+
+    fn from_str(notation: &str) -> Result<Self, Self::Err> {
+        let invalid = || EngineError::InvalidCardNotation(String::from(notation));
+        let upper = notation.trim().to_uppercase();
+        if upper == "EX" {
+            return Ok(Card {
+                color: Color::Excuse,
+                value: 0,
+            });
+        }
+        if let Some(value) = upper.strip_prefix('T') {
+            let value: u8 = value.parse().map_err(|_| invalid())?;
+            return Card::new(Color::Trump, value).map_err(|_| invalid());
+        }
+        let mut chars = upper.chars();
+        let color = match chars.next_back().ok_or_else(invalid)? {
+            'S' => Color::Spade,
+            'H' => Color::Heart,
+            'D' => Color::Diamond,
+            'C' => Color::Club,
+            _ => return Err(invalid()),
+        };
+        let value = match chars.as_str() {
+            "K" => 14,
+            "Q" => 13,
+            "C" => 12,
+            "J" => 11,
+            number => number.parse().map_err(|_| invalid())?,
+        };
+        if value > 10 && chars.as_str().parse::<u8>().is_ok() {
+            return Err(invalid());
+        }
+        Card::new(color, value).map_err(|_| invalid())
+    }
+}
@@ -0,0 +1,602 @@
+use std::collections::{BTreeMap, HashMap};
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use super::{card::Card, engine_error::EngineError};
+
+// Locations are the four seats, then the kitty
+pub const KITTY: usize = 4;
+pub const LOCATIONS: usize = 5;
+
+// One bit per location a card may go to
+pub type LocationSet = u8;
+type Capacities = [u8; LOCATIONS];
+
+// Hidden cards with the locations each may go to, and the number of hidden
+// cards every location holds. Cards allowed in the same locations form a
+// class, deals are counted class by class once and then drawn from the counts.
+#[derive(Debug, Clone)]
+pub struct DealConstraints {
+    counter: DealCounter,
+    capacities: Capacities,
+    total: u128,
+}
+
+impl DealConstraints {
+    pub fn new(
+        cards: &[(Card, LocationSet)],
+        capacities: [usize; LOCATIONS],
+    ) -> Result<Self, EngineError> {
+        let mut classes: BTreeMap<LocationSet, Vec<Card>> = BTreeMap::new();
+        for (card, locations) in cards {
+            classes.entry(*locations).or_default().push(*card);
+        }
+        for class in classes.values_mut() {
+            class.sort_by_key(Card::index);
+        }
+        // Small classes first keeps few capacities to count from, the last
+        // class has a single way to fill what is left
+        let mut classes: Vec<(LocationSet, Vec<Card>)> = classes.into_iter().collect();
+        classes.sort_by_key(|(allowed, cards)| (cards.len(), *allowed));
+        let mut bounded: Capacities = [0; LOCATIONS];
+        for (location, capacity) in capacities.iter().enumerate() {
+            bounded[location] = u8::try_from(*capacity).map_err(|_| {
+                EngineError::HandGenerationNotPossible("Too many cards in a hand".into())
+            })?;
+        }
+        if capacities.iter().sum::<usize>() != cards.len() {
+            return Err(EngineError::HandGenerationNotPossible(format!(
+                "{} hidden cards for {} places",
+                cards.len(),
+                capacities.iter().sum::<usize>()
+            )));
+        }
+        let mut counter = DealCounter {
+            classes,
+            counted: HashMap::new(),
+        };
+        let total = counter.count_from(0, 0, counter.size(0), bounded)?;
+        Ok(Self {
+            counter,
+            capacities: bounded,
+            total,
+        })
+    }
+
+    // Number of deals meeting the constraints
+    pub fn count(&self) -> u128 {
+        self.total
+    }
+
+    // Every deal meeting the constraints is equally likely
+    pub fn sample<R: Rng + ?Sized>(
+        &self,
+        rng: &mut R,
+    ) -> Result<[Vec<Card>; LOCATIONS], EngineError> {
+        if self.total == 0 {
+            return Err(EngineError::HandGenerationNotPossible(
+                "No deal meets the constraints".into(),
+            ));
+        }
+        let counter = &self.counter;
+        let mut capacities = self.capacities;
+        let mut locations: [Vec<Card>; LOCATIONS] = Default::default();
+        for (index, (_, cards)) in counter.classes.iter().enumerate() {
+            // Which cards of the class go where is uniform once their numbers
+            // per location are drawn
+            let mut shuffled = cards.clone();
+            shuffled.shuffle(rng);
+            let mut shuffled = shuffled.into_iter();
+            let mut left = cards.len() as u8;
+            for location in 0..LOCATIONS {
+                // Numbers are drawn by the number of deals they lead to
+                let mut drawn =
+                    rng.random_range(0..counter.counted(index, location, left, &capacities));
+                let mut chosen = None;
+                for taken in 0..=counter.most(index, location, left, &capacities) {
+                    let mut rest = capacities;
+                    rest[location] -= taken;
+                    let ways = binomial(left as usize, taken as usize)
+                        * counter.counted(index, location + 1, left - taken, &rest);
+                    if drawn < ways {
+                        chosen = Some(taken);
+                        break;
+                    }
+                    drawn -= ways;
+                }
+                let taken = chosen.ok_or(EngineError::RustError("Deal count mismatch".into()))?;
+                locations[location].extend(shuffled.by_ref().take(taken as usize));
+                capacities[location] -= taken;
+                left -= taken;
+            }
+        }
+        Ok(locations)
+    }
+
+    // Deals are drawn with a probability proportional to the product of the
+    // weights of each card at its location
+    pub fn sample_weighted<R: Rng + ?Sized>(
+        &self,
+        weight: impl Fn(&Card, usize) -> f64,
+        rng: &mut R,
+    ) -> Result<[Vec<Card>; LOCATIONS], EngineError> {
+        let cards: Vec<(Card, LocationSet)> = self
+            .counter
+            .classes
+            .iter()
+            .flat_map(|(allowed, cards)| cards.iter().map(|card| (*card, *allowed)))
+            .collect();
+        let weights: Vec<[f64; LOCATIONS]> = cards
+            .iter()
+            .map(|(card, allowed)| {
+                std::array::from_fn(|location| {
+                    if allowed & (1 << location) != 0 {
+                        weight(card, location).max(0.0)
+                    } else {
+                        0.0
+                    }
+                })
+            })
+            .collect();
+        let mut table = WeightTable::new(&weights);
+        let mut capacities = self.capacities;
+        if table.total(0, capacities) <= 0.0 {
+            return Err(EngineError::HandGenerationNotPossible(
+                "No deal of positive weight meets the constraints".into(),
+            ));
+        }
+        let mut locations: [Vec<Card>; LOCATIONS] = Default::default();
+        for (index, (card, _)) in cards.iter().enumerate() {
+            let options: Vec<(usize, f64)> = (0..LOCATIONS)
+                .filter(|location| capacities[*location] > 0 && weights[index][*location] > 0.0)
+                .map(|location| {
+                    let mut rest = capacities;
+                    rest[location] -= 1;
+                    (
+                        location,
+                        weights[index][location] * table.total(index + 1, rest),
+                    )
+                })
+                .collect();
+            let total: f64 = options.iter().map(|(_, weight)| weight).sum();
+            let mut drawn = rng.random::<f64>() * total;
+            let mut chosen = None;
+            for (location, weight) in &options {
+                if *weight > 0.0 {
+                    chosen = Some(*location);
+                    if drawn < *weight {
+                        break;
+                    }
+                    drawn -= weight;
+                }
+            }
+            let location = chosen.ok_or(EngineError::RustError("Deal weight mismatch".into()))?;
+            locations[location].push(*card);
+            capacities[location] -= 1;
+        }
+        Ok(locations)
+    }
+}
+
+// Deals where the cards left of class `index` go to locations from
+// `location` on, and the classes after it fill the capacities left
+#[derive(Debug, Clone)]
+struct DealCounter {
+    classes: Vec<(LocationSet, Vec<Card>)>,
+    counted: HashMap<(usize, usize, u8, Capacities), u128>,
+}
+
+impl DealCounter {
+    fn size(&self, index: usize) -> u8 {
+        self.classes
+            .get(index)
+            .map_or(0, |(_, cards)| cards.len() as u8)
+    }
+
+    // Counts and remembers every position the draws can go through
+    fn count_from(
+        &mut self,
+        index: usize,
+        location: usize,
+        left: u8,
+        capacities: Capacities,
+    ) -> Result<u128, EngineError> {
+        if let Some(counted) = self.settled(index, location, left, &capacities) {
+            return match counted {
+                Settled::Count(count) => Ok(count),
+                Settled::NextClass => {
+                    self.count_from(index + 1, 0, self.size(index + 1), capacities)
+                }
+            };
+        }
+        if let Some(counted) = self.counted.get(&(index, location, left, capacities)) {
+            return Ok(*counted);
+        }
+        let mut total: u128 = 0;
+        for taken in 0..=self.most(index, location, left, &capacities) {
+            let mut rest = capacities;
+            rest[location] -= taken;
+            let ways = binomial(left as usize, taken as usize)
+                .checked_mul(self.count_from(index, location + 1, left - taken, rest)?)
+                .ok_or(EngineError::RustError("Too many deals to count".into()))?;
+            total = total
+                .checked_add(ways)
+                .ok_or(EngineError::RustError("Too many deals to count".into()))?;
+        }
+        self.counted
+            .insert((index, location, left, capacities), total);
+        Ok(total)
+    }
+
+    // Same count once `count_from` went through the position
+    fn counted(&self, index: usize, location: usize, left: u8, capacities: &Capacities) -> u128 {
+        match self.settled(index, location, left, capacities) {
+            Some(Settled::Count(count)) => count,
+            Some(Settled::NextClass) => {
+                self.counted(index + 1, 0, self.size(index + 1), capacities)
+            }
+            None => self
+                .counted
+                .get(&(index, location, left, *capacities))
+                .copied()
+                .unwrap_or(0),
+        }
+    }
+
+    // Positions counted without looking further
+    fn settled(
+        &self,
+        index: usize,
+        location: usize,
+        left: u8,
+        capacities: &Capacities,
+    ) -> Option<Settled> {
+        if index >= self.classes.len() {
+            return Some(Settled::Count((*capacities == [0; LOCATIONS]) as u128));
+        }
+        if location == LOCATIONS {
+            return Some(if left > 0 {
+                Settled::Count(0)
+            } else {
+                Settled::NextClass
+            });
+        }
+        (index + 1 == self.classes.len())
+            .then(|| Settled::Count(self.fill(index, location, left, capacities)))
+    }
+
+    // The last class has to fill exactly what the other classes left
+    fn fill(&self, index: usize, location: usize, left: u8, capacities: &Capacities) -> u128 {
+        let allowed = self.classes[index].0;
+        let fits = capacities.iter().enumerate().all(|(other, capacity)| {
+            *capacity == 0 || (other >= location && allowed & (1 << other) != 0)
+        });
+        if !fits
+            || capacities
+                .iter()
+                .map(|capacity| *capacity as usize)
+                .sum::<usize>()
+                != left as usize
+        {
+            return 0;
+        }
+        let mut ways: u128 = 1;
+        let mut rest = left as usize;
+        for capacity in &capacities[location..] {
+            ways *= binomial(rest, *capacity as usize);
+            rest -= *capacity as usize;
+        }
+        ways
+    }
+
+    // Cards of the class the location may take
+    fn most(&self, index: usize, location: usize, left: u8, capacities: &Capacities) -> u8 {
+        if self.classes[index].0 & (1 << location) != 0 {
+            left.min(capacities[location])
+        } else {
+            0
+        }
+    }
+}
+
+enum Settled {
+    Count(u128),
+    NextClass,
+}
+
+fn binomial(n: usize, k: usize) -> u128 {
+    if k > n {
+        return 0;
+    }
+    let k = k.min(n - k);
+    // Exact at every step: the product of i consecutive integers divides by i!
+    (0..k).fold(1, |result: u128, i| {
+        result * (n - i) as u128 / (i + 1) as u128
+    })
+}
+
+// Total weight of the deals completing the cards from `index`, card by card
+struct WeightTable<'a> {
+    weights: &'a [[f64; LOCATIONS]],
+    totals: HashMap<(usize, Capacities), f64>,
+}
+
+impl<'a> WeightTable<'a> {
+    fn new(weights: &'a [[f64; LOCATIONS]]) -> Self {
+        Self {
+            weights,
+            totals: HashMap::new(),
+        }
+    }
+
+    fn total(&mut self, index: usize, capacities: Capacities) -> f64 {
+        let Some(card_weights) = self.weights.get(index) else {
+            return (capacities == [0; LOCATIONS]) as u8 as f64;
+        };
+        if let Some(total) = self.totals.get(&(index, capacities)) {
+            return *total;
+        }
+        let mut total = 0.0;
+        for location in 0..LOCATIONS {
+            if capacities[location] > 0 && card_weights[location] > 0.0 {
+                let mut rest = capacities;
+                rest[location] -= 1;
+                total += card_weights[location] * self.total(index + 1, rest);
+            }
+        }
+        self.totals.insert((index, capacities), total);
+        total
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::business::{
+        game_engine::{game_state::GameState, seed::TarotRng},
+        Color, GameType, KnownGameState,
+    };
+
+    fn first_cards(count: usize) -> Vec<Card> {
+        let mut cards: Vec<Card> = Card::all_possibles().into_iter().collect();
+        cards.sort_by_key(Card::index);
+        cards.truncate(count);
+        cards
+    }
+
+    // Every deal meeting the constraints, as the location of each card
+    fn enumerate(
+        cards: &[(Card, LocationSet)],
+        capacities: &[usize; LOCATIONS],
+    ) -> Vec<Vec<usize>> {
+        let mut deals = vec![vec![]];
+        for (_, allowed) in cards {
+            deals = deals
+                .into_iter()
+                .flat_map(|deal: Vec<usize>| {
+                    (0..LOCATIONS)
+                        .filter(|location| allowed & (1 << location) != 0)
+                        .map(move |location| {
+                            let mut deal = deal.clone();
+                            deal.push(location);
+                            deal
+                        })
+                })
+                .collect();
+        }
+        deals
+            .into_iter()
+            .filter(|deal| {
+                (0..LOCATIONS).all(|location| {
+                    deal.iter().filter(|held| **held == location).count() == capacities[location]
+                })
+            })
+            .collect()
+    }
+
+    fn locate(cards: &[(Card, LocationSet)], deal: &[Vec<Card>; LOCATIONS]) -> Vec<usize> {
+        cards
+            .iter()
+            .map(|(card, _)| {
+                (0..LOCATIONS)
+                    .find(|location| deal[*location].contains(card))
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    fn random_constraints(
+        rng: &mut TarotRng,
+        size: usize,
+    ) -> (Vec<(Card, LocationSet)>, [usize; LOCATIONS]) {
+        let cards: Vec<(Card, LocationSet)> = first_cards(size)
+            .into_iter()
+            .map(|card| (card, rng.random_range(1..1 << LOCATIONS)))
+            .collect();
+        let mut capacities = [0; LOCATIONS];
+        for _ in 0..size {
+            capacities[rng.random_range(0..LOCATIONS)] += 1;
+        }
+        (cards, capacities)
+    }
+
+    // Pearson statistic, against a bound the uniform draw passes with a
+    // probability well above 99.9%
+    fn assert_fits(observed: &[usize], expected: &[f64]) {
+        let statistic: f64 = observed
+            .iter()
+            .zip(expected)
+            .map(|(observed, expected)| (*observed as f64 - expected).powi(2) / expected)
+            .sum();
+        let freedom = (observed.len() - 1) as f64;
+        assert!(
+            statistic < freedom + 5.0 * (2.0 * freedom).sqrt(),
+            "chi squared {} with {} degrees of freedom",
+            statistic,
+            freedom
+        );
+    }
+
+    #[test]
+    fn test_counts_match_enumeration() {
+        let mut rng = TarotRng::seed_from_u64(45);
+        for size in 0..=7 {
+            for _ in 0..10 {
+                let (cards, capacities) = random_constraints(&mut rng, size);
+                let constraints = DealConstraints::new(&cards, capacities).unwrap();
+                assert_eq!(
+                    constraints.count(),
+                    enumerate(&cards, &capacities).len() as u128
+                );
+            }
+        }
+        // Every card hidden from a player at the start of a deal
+        let cards: Vec<(Card, LocationSet)> = first_cards(60)
+            .into_iter()
+            .map(|card| (card, 0b11110))
+            .collect();
+        let constraints = DealConstraints::new(&cards, [0, 18, 18, 18, 6]).unwrap();
+        assert!(constraints.count() > 1 << 100);
+    }
+
+    #[test]
+    fn test_sampling_is_uniform() {
+        let mut rng = TarotRng::seed_from_u64(45);
+        // Two suits voided here and there, a short kitty
+        let cards: Vec<(Card, LocationSet)> = first_cards(7)
+            .into_iter()
+            .zip([
+                0b10110, 0b10110, 0b00111, 0b00111, 0b11001, 0b11111, 0b10011,
+            ])
+            .collect();
+        let capacities = [2, 2, 1, 0, 2];
+        let deals = enumerate(&cards, &capacities);
+        assert!(deals.len() > 20);
+        let constraints = DealConstraints::new(&cards, capacities).unwrap();
+        assert_eq!(constraints.count(), deals.len() as u128);
+        let index: HashMap<&Vec<usize>, usize> = deals.iter().zip(0..).collect();
+        let draws = 200 * deals.len();
+        let mut observed = vec![0; deals.len()];
+        for _ in 0..draws {
+            let deal = locate(&cards, &constraints.sample(&mut rng).unwrap());
+            observed[index[&deal]] += 1;
+        }
+        assert_fits(&observed, &vec![200.0; deals.len()]);
+    }
+
+    #[test]
+    fn test_weighted_sampling_follows_the_weights() {
+        let mut rng = TarotRng::seed_from_u64(45);
+        let (cards, capacities) = (
+            first_cards(6)
+                .into_iter()
+                .map(|card| (card, 0b10111))
+                .collect::<Vec<_>>(),
+            [2, 1, 2, 0, 1],
+        );
+        let weight =
+            |card: &Card, location: usize| 1.0 + ((card.index() + 2 * location) % 4) as f64;
+        let deals = enumerate(&cards, &capacities);
+        let weights: Vec<f64> = deals
+            .iter()
+            .map(|deal| {
+                cards
+                    .iter()
+                    .zip(deal)
+                    .map(|((card, _), location)| weight(card, *location))
+                    .product()
+            })
+            .collect();
+        let total: f64 = weights.iter().sum();
+        let constraints = DealConstraints::new(&cards, capacities).unwrap();
+        let index: HashMap<&Vec<usize>, usize> = deals.iter().zip(0..).collect();
+        let draws = 100 * deals.len();
+        let mut observed = vec![0; deals.len()];
+        for _ in 0..draws {
+            let deal = locate(
+                &cards,
+                &constraints.sample_weighted(weight, &mut rng).unwrap(),
+            );
+            observed[index[&deal]] += 1;
+        }
+        let expected: Vec<f64> = weights
+            .iter()
+            .map(|weight| weight / total * draws as f64)
+            .collect();
+        assert_fits(&observed, &expected);
+    }
+
+    #[test]
+    fn test_impossible_constraints_are_reported() {
+        let cards: Vec<(Card, LocationSet)> = first_cards(3)
+            .into_iter()
+            .map(|card| (card, 0b00001))
+            .collect();
+        let constraints = DealConstraints::new(&cards, [2, 1, 0, 0, 0]).unwrap();
+        assert_eq!(constraints.count(), 0);
+        let mut rng = TarotRng::seed_from_u64(45);
+        assert!(matches!(
+            constraints.sample(&mut rng),
+            Err(EngineError::HandGenerationNotPossible(_))
+        ));
+        assert!(DealConstraints::new(&cards, [2, 2, 0, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn test_samples_agree_with_the_known_state() {
+        let mut rng = TarotRng::seed_from_u64(45);
+        for _ in 0..4 {
+            let state = GameState::played_at_random(
+                &mut rng,
+                2,
+                GameType::GardeContre { chelem: false },
+                30,
+            );
+            for observer in 0..4 {
+                let known = KnownGameState::from_omniscient(&state, observer);
+                assert!(known.deal_constraints().unwrap().count() > 0);
+                for _ in 0..10 {
+                    let deal = known
+                        .possible_random_full_state_v2_with_rng(&mut rng)
+                        .unwrap();
+                    assert_eq!(
+                        deal.players_state[observer as usize].hand,
+                        state.players_state[observer as usize].hand
+                    );
+                    for player in 0..4 {
+                        let hand = &deal.players_state[player].hand;
+                        let constraint = known.get_known_constraints(player).unwrap();
+                        assert_eq!(hand.len(), constraint.number_cards);
+                        assert!(hand.iter().all(|card| {
+                            !constraint.voided_colors.contains(&card.color)
+                                && (card.color != Color::Trump
+                                    || card.value <= constraint.highest_trump)
+                        }));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_revealed_kitty_may_stay_with_the_taker() {
+        let mut state = GameState::with_first_hand(first_cards(18));
+        state
+            .shared_state
+            .bid(0, Some(GameType::Garde { chelem: false }))
+            .unwrap();
+        let defender = KnownGameState::from_omniscient(&state, 1);
+        let constraints =
+            [0, 1, 2, 3].map(|player| defender.get_known_constraints(player).unwrap());
+        let left_to_play = defender.shared_state.cards_left_to_play();
+        let taker_cards = defender.possible_cards(&constraints, &left_to_play, 0);
+        let partner_cards = defender.possible_cards(&constraints, &left_to_play, 2);
+        for card in &state.kitty {
+            assert!(taker_cards.contains(card));
+            assert!(!partner_cards.contains(card));
+        }
+    }
+}
@@ -12,6 +12,13 @@ pub enum EngineError {
     FinishedHand,
     RustError(String),
     HandGenerationNotPossible(String),
+    Serialization(String),
+    UnsupportedSchemaVersion(u32),
+    InvalidCardNotation(String),
+    NoTaker,
+    InvalidAside,
+    InvalidAnnouncement,
+    InconsistentObservation(String),
 }
 
 impl std::fmt::Display for EngineError {
@@ -53,6 +60,25 @@ impl std::fmt::Display for EngineError {
             EngineError::HandGenerationNotPossible(arg) => {
                 write!(f, "Could not generate a hand: {}", arg)
             }
+            EngineError::Serialization(arg) => {
+                write!(f, "Could not (de)serialize game data: {}", arg)
+            }
+            EngineError::UnsupportedSchemaVersion(arg) => {
+                write!(f, "Schema version {} is not supported", arg)
+            }
+            EngineError::InvalidCardNotation(arg) => {
+                write!(f, "{} is not a valid card", arg)
+            }
+            EngineError::NoTaker => write!(f, "This operation requires a taker",),
+            EngineError::InvalidAside => {
+                write!(f, "These cards can not be set aside",)
+            }
+            EngineError::InvalidAnnouncement => {
+                write!(f, "This announcement is not allowed",)
+            }
+            EngineError::InconsistentObservation(arg) => {
+                write!(f, "Observation contradicts what is known: {}", arg)
+            }
         }
     }
 }
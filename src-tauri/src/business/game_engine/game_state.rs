@@ -1,17 +1,30 @@
 use std::collections::HashSet;
 
-use rand::seq::SliceRandom;
+use rand::{seq::SliceRandom, Rng};
+use serde::{Deserialize, Serialize};
 
 use super::{
-    card::Card, engine_error::EngineError, player_game_state::PlayerGameState,
+    card::{Card, Color},
+    engine_error::EngineError,
+    game_type::GameType,
+    handfuls::{DeclaredHandfuls, Handfuls},
+    player_game_state::PlayerGameState,
+    score::Score,
     shared_game_state::SharedGameState,
 };
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GameState {
     pub players_state: [PlayerGameState; 4],
+    // The cards out of the hands, the écart once the taker set it aside
     pub kitty: [Card; 6],
+    // The kitty as turned over, kept once the écart replaces it
+    #[serde(default)]
+    pub revealed_kitty: Option<[Card; 6]>,
     pub shared_state: SharedGameState,
+    // Bids made so far, from the player after the dealer
+    #[serde(default)]
+    pub auction: Vec<Option<GameType>>,
 }
 
 impl GameState {
@@ -19,14 +32,21 @@ impl GameState {
         Self {
             players_state: hands.map(|hand| PlayerGameState { hand }),
             kitty,
+            revealed_kitty: None,
             shared_state: SharedGameState::initialize(dealer),
+            auction: vec![],
         }
     }
 
     pub fn random_init() -> Result<GameState, EngineError> {
-        let mut rng = rand::rng();
+        Self::random_init_with_rng(&mut rand::rng())
+    }
+
+    pub fn random_init_with_rng<R: Rng + ?Sized>(rng: &mut R) -> Result<GameState, EngineError> {
+        // Sorting first makes the deal only depend on the random generator
         let mut cards: Vec<Card> = Card::all_possibles().into_iter().collect();
-        cards.shuffle(&mut rng);
+        cards.sort_by_key(Card::index);
+        cards.shuffle(rng);
         let hands: [HashSet<Card>; 4] = [
             cards.drain(0..18).collect(),
             cards.drain(0..18).collect(),
@@ -38,19 +58,31 @@ impl GameState {
             .collect::<Vec<Card>>()
             .try_into()
             .map_err(|_| EngineError::RustError(String::from("Could not unwrap")))?;
-        Ok(Self::initialize(hands, kitty, rand::random_range(0..4)))
+        Ok(Self::initialize(hands, kitty, rng.random_range(0..4)))
+    }
+
+    // Every player bids once, in turn
+    pub fn bid(&mut self, player: u8, bid: Option<GameType>) -> Result<(), EngineError> {
+        let next = (self.shared_state.dealer as usize + 1 + self.auction.len()) % 4;
+        if self.auction.len() == 4 || player as usize != next {
+            return Err(EngineError::OutOfOrderPlay);
+        }
+        self.shared_state.bid(player, bid)?;
+        self.auction.push(bid);
+        Ok(())
     }
 
     pub fn play_card(&mut self, player_index: u8, card: &Card) -> Result<(), EngineError> {
-        let mut current_trick = self
-            .shared_state
-            .current_trick
-            .unwrap_or(self.shared_state.new_trick());
+        if self.shared_state.finished() {
+            return Err(EngineError::FinishedHand);
+        }
+        let mut current_trick = self.shared_state.current_or_new_trick();
         self.players_state[player_index as usize].play_a_card(
             &mut current_trick,
             player_index,
             card,
         )?;
+        self.shared_state.current_trick = Some(current_trick);
         if current_trick.next_to_play().is_none() {
             self.shared_state.finish_trick()?;
         }
@@ -58,9 +90,186 @@ impl GameState {
     }
 
     pub fn cards_allowed(&self, player: u8) -> HashSet<&Card> {
-        self.shared_state
-            .current_trick
-            .map(|trick| self.players_state[player as usize].cards_allowed(&trick))
-            .unwrap_or(HashSet::new())
+        if self.shared_state.finished() {
+            return HashSet::new();
+        }
+        self.players_state[player as usize].cards_allowed(&self.shared_state.current_or_new_trick())
+    }
+
+    pub fn legal_moves(&self, player: u8) -> Vec<Card> {
+        let mut moves: Vec<Card> = self.cards_allowed(player).into_iter().cloned().collect();
+        moves.sort_by_key(Card::index);
+        moves
+    }
+
+    pub fn take_kitty(&mut self) -> Result<(), EngineError> {
+        let taker = self.shared_state.taker.ok_or(EngineError::NoTaker)?;
+        let hand = &mut self.players_state[taker as usize].hand;
+        if !self.shared_state.kitty_should_be_revealed() || hand.len() != 18 {
+            return Err(EngineError::InvalidAside);
+        }
+        hand.extend(self.kitty);
+        Ok(())
+    }
+
+    pub fn discard(&mut self, aside: [Card; 6]) -> Result<(), EngineError> {
+        let taker = self.shared_state.taker.ok_or(EngineError::NoTaker)?;
+        let player_state = &mut self.players_state[taker as usize];
+        player_state.check_aside(&aside)?;
+        player_state.chose_aside(aside)?;
+        self.shared_state.trumps_aside = trumps_in(&aside);
+        self.revealed_kitty = Some(self.kitty);
+        self.kitty = aside;
+        Ok(())
+    }
+
+    pub fn declare_handful(&mut self, player: u8, cards: HashSet<Card>) -> Result<(), EngineError> {
+        let handful = Handfuls::from_size(cards.len()).ok_or(EngineError::InvalidAnnouncement)?;
+        let hand = &self.players_state[player as usize].hand;
+        let trumps_in_hand = hand
+            .iter()
+            .filter(|card| card.color == Color::Trump)
+            .count();
+        let valid_cards = cards.iter().all(|card| {
+            hand.contains(card)
+                && (card.color == Color::Trump
+                    || (card.color == Color::Excuse && trumps_in_hand < cards.len()))
+        });
+        let already_played = !self.shared_state.played_tricks.is_empty()
+            || self
+                .shared_state
+                .current_trick
+                .is_some_and(|trick| trick.cards[player as usize].is_some());
+        if !valid_cards
+            || already_played
+            || self.shared_state.declared_handfuls[player as usize].is_some()
+        {
+            return Err(EngineError::InvalidAnnouncement);
+        }
+        self.shared_state.declared_handfuls[player as usize] =
+            Some(DeclaredHandfuls { handful, cards });
+        Ok(())
+    }
+
+    pub fn final_score(&self) -> Result<Score, EngineError> {
+        Score::compute(self)
+    }
+}
+
+// Trumps of an écart, by index
+pub fn trumps_in(aside: &[Card; 6]) -> Vec<Card> {
+    let mut trumps: Vec<Card> = aside
+        .iter()
+        .filter(|card| card.color == Color::Trump)
+        .copied()
+        .collect();
+    trumps.sort_by_key(Card::index);
+    trumps
+}
+
+#[cfg(test)]
+impl GameState {
+    // Player 0 gets the given cards, the others the rest in order, player 3
+    // deals
+    pub fn with_first_hand(first_hand: Vec<Card>) -> Self {
+        let mut others: Vec<Card> = Card::all_possibles()
+            .into_iter()
+            .filter(|card| !first_hand.contains(card))
+            .collect();
+        others.sort_by_key(Card::index);
+        let kitty: [Card; 6] = others.split_off(54).try_into().unwrap();
+        let mut hands: Vec<HashSet<Card>> = vec![first_hand.into_iter().collect()];
+        hands.extend(
+            others
+                .chunks(18)
+                .map(|chunk| chunk.iter().copied().collect()),
+        );
+        Self::initialize(hands.try_into().unwrap(), kitty, 3)
+    }
+
+    // A random deal the taker alone bids, played at random for the given
+    // number of cards
+    pub fn played_at_random<R: Rng + ?Sized>(
+        rng: &mut R,
+        taker: u8,
+        contract: GameType,
+        cards: usize,
+    ) -> Self {
+        let mut state = Self::random_init_with_rng(rng).unwrap();
+        for offset in 1..=4 {
+            let player = (state.shared_state.dealer + offset) % 4;
+            state
+                .bid(player, (player == taker).then_some(contract))
+                .unwrap();
+        }
+        state.play_at_random(rng, cards);
+        state
+    }
+
+    // Plays random legal cards until the given number is down or the deal is
+    // over, returns them in order
+    pub fn play_at_random<R: Rng + ?Sized>(&mut self, rng: &mut R, cards: usize) -> Vec<Card> {
+        use rand::seq::IndexedRandom;
+
+        let mut plays = vec![];
+        while let Some(player) = self.shared_state.next_to_play() {
+            if plays.len() == cards {
+                break;
+            }
+            let card = *self.legal_moves(player).choose(rng).unwrap();
+            self.play_card(player, &card).unwrap();
+            plays.push(card);
+        }
+        plays
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::business::{analysis::ecart::legal_asides, game_engine::seed::TarotRng};
+
+    #[test]
+    fn test_kitty_is_taken_then_set_aside() {
+        let mut state = GameState::random_init_with_rng(&mut TarotRng::seed_from_u64(27)).unwrap();
+        assert!(matches!(state.take_kitty(), Err(EngineError::NoTaker)));
+        let taker = (state.shared_state.dealer + 1) % 4;
+        let mut garde_sans = state.clone();
+        garde_sans
+            .shared_state
+            .bid(taker, Some(GameType::GardeSans { chelem: false }))
+            .unwrap();
+        assert!(garde_sans.take_kitty().is_err());
+
+        state
+            .shared_state
+            .bid(taker, Some(GameType::Garde { chelem: false }))
+            .unwrap();
+        let dealt = state.kitty;
+        state.take_kitty().unwrap();
+        assert!(state.take_kitty().is_err());
+        let hand = &state.players_state[taker as usize].hand;
+        assert_eq!(hand.len(), 24);
+        assert!(dealt.iter().all(|card| hand.contains(card)));
+
+        // Neither a king nor an oudler may be set aside
+        let mut aside = legal_asides(hand)[0];
+        let honour = hand
+            .iter()
+            .find(|card| card.is_oudler() || (card.color != Color::Trump && card.value == 14))
+            .unwrap();
+        let mut illegal = aside;
+        illegal[0] = *honour;
+        assert!(matches!(
+            state.clone().discard(illegal),
+            Err(EngineError::InvalidAside)
+        ));
+        aside.sort_by_key(Card::index);
+        state.discard(aside).unwrap();
+        assert_eq!(state.players_state[taker as usize].hand.len(), 18);
+        assert_eq!(state.kitty, aside);
+        assert_eq!(state.revealed_kitty, Some(dealt));
     }
 }
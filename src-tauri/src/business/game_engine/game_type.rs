@@ -1,6 +1,7 @@
+use serde::{Deserialize, Serialize};
 use std::fmt;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 
 pub enum GameType {
     Petit { chelem: bool },
@@ -12,8 +13,30 @@ pub enum GameType {
 impl GameType {
     pub fn kitty_should_be_revealed(&self) -> bool {
         match self {
-            GameType::Petit { chelem: _ } | GameType::Garde { chelem: _ } => false,
-            GameType::GardeSans { chelem: _ } | GameType::GardeContre { chelem: _ } => true,
+            GameType::Petit { chelem: _ } | GameType::Garde { chelem: _ } => true,
+            GameType::GardeSans { chelem: _ } | GameType::GardeContre { chelem: _ } => false,
+        }
+    }
+
+    pub fn kitty_goes_to_taker(&self) -> bool {
+        !matches!(self, GameType::GardeContre { chelem: _ })
+    }
+
+    pub fn chelem_announced(&self) -> bool {
+        match self {
+            GameType::Petit { chelem }
+            | GameType::Garde { chelem }
+            | GameType::GardeSans { chelem }
+            | GameType::GardeContre { chelem } => *chelem,
+        }
+    }
+
+    pub fn with_chelem(&self, chelem: bool) -> GameType {
+        match self {
+            GameType::Petit { chelem: _ } => GameType::Petit { chelem },
+            GameType::Garde { chelem: _ } => GameType::Garde { chelem },
+            GameType::GardeSans { chelem: _ } => GameType::GardeSans { chelem },
+            GameType::GardeContre { chelem: _ } => GameType::GardeContre { chelem },
         }
     }
 
@@ -1,8 +1,9 @@
+use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 
 use crate::business::Card;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum Handfuls {
     Simple,
     Double,
@@ -10,6 +11,15 @@ pub enum Handfuls {
 }
 
 impl Handfuls {
+    pub fn from_size(size: usize) -> Option<Self> {
+        match size {
+            10 => Some(Handfuls::Simple),
+            13 => Some(Handfuls::Double),
+            15 => Some(Handfuls::Triple),
+            _ => None,
+        }
+    }
+
     pub fn points(&self) -> usize {
         match self {
             Handfuls::Simple => 20,
@@ -27,7 +37,7 @@ impl Handfuls {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DeclaredHandfuls {
     pub handful: Handfuls,
     pub cards: HashSet<Card>,
@@ -1,20 +1,31 @@
-use rand::seq::IndexedRandom;
 use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 
 use crate::business::Color;
 
 use super::{
-    card::Card, engine_error::EngineError, game_state::GameState,
-    player_game_state::PlayerGameState, shared_game_state::SharedGameState,
+    card::Card,
+    deal_sampler::{DealConstraints, LocationSet, KITTY, LOCATIONS},
+    engine_error::EngineError,
+    game_state::GameState,
+    game_type::GameType,
+    player_game_state::PlayerGameState,
+    shared_game_state::SharedGameState,
+    trick::PlayedTrick,
 };
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct KnownGameState {
     pub player_state: PlayerGameState,
     pub player_index: u8,
+    // The écart for the taker once set aside, the revealed kitty otherwise
     pub kitty: Option<[Card; 6]>,
     pub shared_state: SharedGameState,
+    // Bids heard so far, from the player after the dealer
+    #[serde(default)]
+    pub auction: Vec<Option<GameType>>,
 }
 
 impl KnownGameState {
@@ -29,6 +40,7 @@ impl KnownGameState {
             player_index,
             kitty,
             shared_state: SharedGameState::initialize(dealer),
+            auction: vec![],
         }
     }
 
@@ -36,20 +48,26 @@ impl KnownGameState {
         Self {
             player_state: state.players_state[player as usize].clone(),
             player_index: player,
-            kitty: if state.shared_state.kitty_should_be_revealed() {
+            // Only the taker knows the écart, the others saw the kitty
+            kitty: if !state.shared_state.kitty_should_be_revealed() {
+                None
+            } else if state.shared_state.taker == Some(player) {
                 Some(state.kitty)
             } else {
-                None
+                Some(state.revealed_kitty.unwrap_or(state.kitty))
             },
             shared_state: state.shared_state.clone(),
+            auction: state.auction.clone(),
         }
     }
 
     pub fn get_known_constraints(&self, player: usize) -> Result<PlayerConstraint, EngineError> {
-        let known_cards = if player == self.player_index as usize {
+        let left_to_play = self.shared_state.cards_left_to_play();
+        let handful = self.shared_state.declared_handfuls[player].as_ref();
+        let known_cards: HashSet<Card> = if player == self.player_index as usize {
             self.player_state.hand.clone()
-        } else if let Some(handful) = &self.shared_state.declared_handfuls[player] {
-            handful.cards.clone()
+        } else if let Some(handful) = handful {
+            handful.cards.intersection(&left_to_play).copied().collect()
         } else {
             HashSet::new()
         };
@@ -66,17 +84,20 @@ impl KnownGameState {
                 0
             };
         let mut highest_trump = 21;
-        let mut voided_colors: HashSet<Color> = HashSet::new();
-        for trick in &self.shared_state.played_tricks {
-            if trick.did_not_have_color(player) {
-                voided_colors.insert(trick.color());
-            }
+        let tricks = self
+            .shared_state
+            .played_tricks
+            .iter()
+            .map(PlayedTrick::as_trick)
+            .chain(self.shared_state.current_trick);
+        for trick in tricks {
             if let Some(highest_trump_trick) = trick.did_not_have_trump_higher(player) {
                 if highest_trump_trick < highest_trump {
                     highest_trump = highest_trump_trick;
                 }
             }
         }
+        let voided_colors = self.shared_state.voided_colors(player);
         Ok(PlayerConstraint {
             number_cards,
             highest_trump,
@@ -86,41 +107,126 @@ impl KnownGameState {
     }
 
     pub fn possible_random_full_state_v2(&self) -> Result<GameState, EngineError> {
+        self.possible_random_full_state_v2_with_rng(&mut rand::rng())
+    }
+
+    // Every deal consistent with what the player knows is equally likely
+    pub fn possible_random_full_state_v2_with_rng<R: Rng + ?Sized>(
+        &self,
+        rng: &mut R,
+    ) -> Result<GameState, EngineError> {
+        let locations = self.deal_constraints()?.sample(rng)?;
+        self.state_from_deal(locations)
+    }
+
+    // Deals drawn in proportion to the product of `weight(card, location)`
+    // over the hidden cards, the kitty being location `KITTY`
+    pub fn weighted_random_full_state_with_rng<R: Rng + ?Sized>(
+        &self,
+        weight: impl Fn(&Card, usize) -> f64,
+        rng: &mut R,
+    ) -> Result<GameState, EngineError> {
+        let locations = self.deal_constraints()?.sample_weighted(weight, rng)?;
+        self.state_from_deal(locations)
+    }
+
+    // Cards the player cannot locate, with the seats and kitty each may be in,
+    // and the number of them every location holds
+    pub fn deal_constraints(&self) -> Result<DealConstraints, EngineError> {
         let constraints_per_player: [PlayerConstraint; 4] = [
             self.get_known_constraints(0)?,
             self.get_known_constraints(1)?,
             self.get_known_constraints(2)?,
-            self.get_known_constraints(2)?,
+            self.get_known_constraints(3)?,
         ];
         let left_to_play = self.shared_state.cards_left_to_play();
         let possible_cards: Vec<HashSet<Card>> = (0..4)
             .map(|i| self.possible_cards(&constraints_per_player, &left_to_play, i))
             .collect();
-        let known_cards: Vec<HashSet<Card>> = constraints_per_player
-            .iter()
-            .map(|constraint| constraint.known_cards.clone())
+        let mut capacities = [0; LOCATIONS];
+        for (player, constraint) in constraints_per_player.iter().enumerate() {
+            capacities[player] = constraint
+                .number_cards
+                .checked_sub(constraint.known_cards.len())
+                .ok_or(EngineError::HandGenerationNotPossible(
+                    "Too many known cards".into(),
+                ))?;
+        }
+        let mut hidden: Vec<Card> = left_to_play
+            .into_iter()
+            .filter(|card| {
+                constraints_per_player
+                    .iter()
+                    .all(|constraint| !constraint.known_cards.contains(card))
+            })
             .collect();
-        let remaining_cards: Vec<usize> = constraints_per_player
-            .iter()
-            .map(|constraint| constraint.number_cards)
+        hidden.sort_by_key(Card::index);
+        let cards: Vec<(Card, LocationSet)> = hidden
+            .into_iter()
+            .map(|card| {
+                let mut locations: LocationSet = (0..4)
+                    .filter(|player| {
+                        *player != self.player_index as usize
+                            && possible_cards[*player].contains(&card)
+                    })
+                    .fold(0, |locations, player| locations | 1 << player);
+                if self.may_be_set_aside(&card) {
+                    locations |= 1 << KITTY;
+                }
+                (card, locations)
+            })
             .collect();
-        let (hands, kitty) = self.generate_hands(possible_cards, known_cards, remaining_cards)?;
-        Ok(GameState::initialize(
-            hands,
-            kitty
-                .iter()
-                .map(|item| *item)
-                .collect::<Vec<Card>>()
-                .try_into()
-                .map_err(|_| {
-                    EngineError::RustError(String::from("Could not create array from set"))
-                })?,
-            self.shared_state.dealer,
-        ))
+        capacities[KITTY] = cards.len().checked_sub(capacities.iter().sum()).ok_or(
+            EngineError::HandGenerationNotPossible("Not enough cards left".into()),
+        )?;
+        DealConstraints::new(&cards, capacities)
+    }
+
+    // Whether the card may be in the écart, or in the kitty left untouched
+    pub fn may_be_set_aside(&self, card: &Card) -> bool {
+        let taker = self.shared_state.taker;
+        match self.kitty {
+            None => true,
+            // The taker knows the écart
+            Some(kitty) if taker == Some(self.player_index) => kitty.contains(card),
+            // Defenders only saw the kitty, any card the taker could discard
+            // may have been set aside instead, trumps only once shown
+            Some(_) => {
+                !card.is_oudler()
+                    && if card.color == Color::Trump {
+                        self.shared_state.trumps_aside.contains(card)
+                    } else {
+                        card.value != 14
+                    }
+            }
+        }
+    }
+
+    fn state_from_deal(&self, locations: [Vec<Card>; LOCATIONS]) -> Result<GameState, EngineError> {
+        let [first, second, third, fourth, kitty] = locations;
+        let mut hands: [HashSet<Card>; 4] = [first, second, third, fourth]
+            .map(|cards| cards.into_iter().collect::<HashSet<Card>>());
+        for (player, hand) in hands.iter_mut().enumerate() {
+            hand.extend(self.get_known_constraints(player)?.known_cards);
+        }
+        let mut kitty = kitty;
+        kitty.sort_by_key(Card::index);
+        let kitty: [Card; 6] = kitty.try_into().map_err(|_| {
+            EngineError::HandGenerationNotPossible(String::from("The kitty is not complete"))
+        })?;
+        let mut state = GameState::initialize(hands, kitty, self.shared_state.dealer);
+        state.shared_state = self.shared_state.clone();
+        Ok(state)
     }
 
     pub fn possible_random_full_state(&self) -> Result<GameState, EngineError> {
-        let mut rng = rand::rng();
+        self.possible_random_full_state_with_rng(&mut rand::rng())
+    }
+
+    pub fn possible_random_full_state_with_rng<R: Rng + ?Sized>(
+        &self,
+        rng: &mut R,
+    ) -> Result<GameState, EngineError> {
         let mut left_to_play = self.shared_state.cards_left_to_play();
         for card in &self.player_state.hand {
             left_to_play.remove(card);
@@ -131,7 +237,8 @@ impl KnownGameState {
             }
         }
         let mut cards: Vec<Card> = left_to_play.into_iter().collect();
-        cards.shuffle(&mut rng);
+        cards.sort_by_key(Card::index);
+        cards.shuffle(rng);
         let cards_per_player = (0..4)
             .map(|player| {
                 18 - self.shared_state.played_tricks.len()
@@ -149,9 +256,13 @@ impl KnownGameState {
             .collect::<Vec<usize>>();
         let hands: [HashSet<Card>; 4] = (0..4)
             .map(|player| {
-                cards
-                    .drain(0..(cards_per_player[player as usize]))
-                    .collect::<HashSet<Card>>()
+                if player == self.player_index as usize {
+                    self.player_state.hand.clone()
+                } else {
+                    cards
+                        .drain(0..(cards_per_player[player]))
+                        .collect::<HashSet<Card>>()
+                }
             })
             .collect::<Vec<_>>()
             .try_into()
@@ -163,11 +274,9 @@ impl KnownGameState {
                 .try_into()
                 .map_err(|_| EngineError::RustError(String::from("Could not unwrap")))?,
         );
-        Ok(GameState::initialize(
-            hands,
-            kitty,
-            self.shared_state.dealer,
-        ))
+        let mut state = GameState::initialize(hands, kitty, self.shared_state.dealer);
+        state.shared_state = self.shared_state.clone();
+        Ok(state)
     }
 
     pub fn possible_cards(
@@ -178,17 +287,19 @@ impl KnownGameState {
     ) -> HashSet<Card> {
         let mut res: HashSet<Card> = HashSet::new();
         for card in left_to_play {
-            if self
-                .shared_state
-                .taker
-                .is_some_and(|taker| self.player_index != taker)
-                && self.kitty.is_some_and(|kitty| kitty.contains(card))
+            // The taker holds the revealed kitty or has set those cards aside,
+            // which only the taker can tell apart
+            if self.kitty.is_some_and(|kitty| kitty.contains(card))
+                && (self.shared_state.taker == Some(self.player_index)
+                    || self.shared_state.taker != Some(index as u8))
             {
                 continue;
             }
+            // Trumps shown with the écart are in it
             if constraints_per_player[index]
                 .voided_colors
                 .contains(&card.color)
+                || self.shared_state.trumps_aside.contains(card)
             {
                 continue;
             }
@@ -207,64 +318,88 @@ impl KnownGameState {
         }
         res
     }
-
-    fn generate_hands(
-        &self,
-        possible_cards: Vec<HashSet<Card>>,
-        known_cards: Vec<HashSet<Card>>,
-        remaining_cards: Vec<usize>,
-    ) -> Result<([HashSet<Card>; 4], HashSet<Card>), EngineError> {
-        let mut remaining = remaining_cards.clone();
-        let mut rng = rand::rng();
-        let mut hands: Vec<HashSet<Card>> = known_cards.clone();
-        let mut kitty: HashSet<Card> = HashSet::new();
-        for i in 0..4 {
-            if hands[i].len() > remaining[i] {
-                return Err(EngineError::HandGenerationNotPossible(
-                    "Too many known cards".into(),
-                ));
-            }
-            remaining[i] -= hands[i].len();
-        }
-        let mut deck: Vec<Card> = possible_cards
-            .iter()
-            .enumerate()
-            .flat_map(|(p, set)| set.difference(&hands[p]).cloned())
-            .collect();
-        deck.shuffle(&mut rng);
-        while let Some(card) = deck.pop() {
-            let mut candidates: Vec<usize> = (0..4)
-                .filter(|&p| remaining[p] > 0 && possible_cards[p].contains(&card))
-                .collect();
-            if candidates.is_empty() {
-                kitty.insert(card);
-                continue;
-            }
-            candidates.sort_by_key(|&p| remaining[p] - possible_cards[p].len());
-            let best_score = remaining[candidates[0]] - possible_cards[candidates[0]].len();
-            let tightest: Vec<usize> = candidates
-                .into_iter()
-                .filter(|&p| remaining[p] - possible_cards[p].len() == best_score)
-                .collect();
-            let chosen = *tightest.choose(&mut rng).unwrap();
-            hands[chosen].insert(card);
-            remaining[chosen] -= 1;
-        }
-        for i in 0..4 {
-            if remaining[i] != 0 {
-                return Err(EngineError::HandGenerationNotPossible(
-                    "Unfilled hand".into(),
-                ));
-            }
-        }
-        Ok((hands.try_into().unwrap(), kitty))
-    }
 }
 
 #[derive(Debug, Clone)]
-struct PlayerConstraint {
+pub struct PlayerConstraint {
     pub number_cards: usize,
     pub highest_trump: u8,
     pub voided_colors: HashSet<Color>,
     pub known_cards: HashSet<Card>,
 }
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::business::{analysis::ecart::legal_asides, game_engine::seed::TarotRng};
+
+    #[test]
+    fn test_defenders_do_not_see_the_ecart() {
+        let mut state = GameState::random_init_with_rng(&mut TarotRng::seed_from_u64(27)).unwrap();
+        let taker = (state.shared_state.dealer + 1) % 4;
+        state
+            .shared_state
+            .bid(taker, Some(GameType::Garde { chelem: false }))
+            .unwrap();
+        let dealt = state.kitty;
+        state.take_kitty().unwrap();
+        let aside = legal_asides(&state.players_state[taker as usize].hand)[0];
+        assert_ne!(aside, dealt);
+        state.discard(aside).unwrap();
+        for player in 0..4 {
+            let known = KnownGameState::from_omniscient(&state, player);
+            let expected = if player == taker { aside } else { dealt };
+            assert_eq!(known.kitty, Some(expected));
+        }
+    }
+
+    #[test]
+    fn test_only_discardable_cards_may_be_set_aside() {
+        // Player 0 takes the T17 to T21 and the Excuse from the kitty, and
+        // must set aside three trumps
+        let mut hand: Vec<Card> = (2..=16)
+            .map(|value| Card::new(Color::Trump, value).unwrap())
+            .collect();
+        hand.extend((1..=3).map(|value| Card::new(Color::Spade, value).unwrap()));
+        let mut state = GameState::with_first_hand(hand);
+        state
+            .shared_state
+            .bid(0, Some(GameType::Garde { chelem: false }))
+            .unwrap();
+        state.take_kitty().unwrap();
+        let aside = legal_asides(&state.players_state[0].hand)[0];
+        state.discard(aside).unwrap();
+        let shown = state.shared_state.trumps_aside.clone();
+        assert_eq!(shown.len(), 3);
+
+        let defender = KnownGameState::from_omniscient(&state, 3);
+        let may_be_set_aside =
+            |notation: &str| defender.may_be_set_aside(&Card::from_str(notation).unwrap());
+        assert!(shown.iter().all(|card| defender.may_be_set_aside(card)));
+        let hidden = (2..=20)
+            .map(|value| Card::new(Color::Trump, value).unwrap())
+            .find(|card| !shown.contains(card))
+            .unwrap();
+        assert!(!defender.may_be_set_aside(&hidden));
+        assert!(!may_be_set_aside("T21"));
+        assert!(!may_be_set_aside("EX"));
+        assert!(!may_be_set_aside("KS"));
+        assert!(may_be_set_aside("4S"));
+        let mut rng = TarotRng::seed_from_u64(46);
+        for _ in 0..8 {
+            let sampled = defender
+                .possible_random_full_state_v2_with_rng(&mut rng)
+                .unwrap();
+            assert!(shown.iter().all(|card| sampled.kitty.contains(card)));
+        }
+
+        let taker = KnownGameState::from_omniscient(&state, 0);
+        assert!(aside.iter().all(|card| taker.may_be_set_aside(card)));
+        assert!(!taker.may_be_set_aside(&Card::from_str("T21").unwrap()));
+        assert!(!taker.may_be_set_aside(&Card::from_str("4S").unwrap()));
+    }
+}
@@ -1,12 +1,18 @@
 pub mod card;
+pub mod deal_sampler;
 pub mod engine_error;
 pub mod game_state;
 pub mod game_type;
 pub mod handfuls;
 pub mod known_game_state;
+pub mod observation;
 pub mod player_game_state;
+pub mod schema;
+pub mod score;
+pub mod seed;
 pub mod shared_game_state;
 pub mod trick;
+pub mod zobrist;
 
 pub use card::{Card, Color};
 pub use game_type::GameType;
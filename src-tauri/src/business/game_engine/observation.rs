@@ -0,0 +1,586 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use super::{
+    card::{Card, Color},
+    engine_error::EngineError,
+    game_state::trumps_in,
+    game_type::GameType,
+    handfuls::{DeclaredHandfuls, Handfuls},
+    known_game_state::{KnownGameState, PlayerConstraint},
+    player_game_state::PlayerGameState,
+};
+
+// What a player sees of the deal, in the order it happens
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Observation {
+    Bid { player: u8, bid: Option<GameType> },
+    KittyRevealed([Card; 6]),
+    // Only seen by the taker
+    Aside([Card; 6]),
+    // Trumps of the écart, shown to everyone
+    TrumpsAside(Vec<Card>),
+    ChelemDeclared { player: u8 },
+    HandfulShown { player: u8, cards: Vec<Card> },
+    CardPlayed { player: u8, card: Card },
+    TrickWon { winner: u8 },
+}
+
+fn inconsistent(reason: &str) -> EngineError {
+    EngineError::InconsistentObservation(String::from(reason))
+}
+
+impl KnownGameState {
+    // Applies what the player saw, leaving the state untouched when it does
+    // not fit the rules or what the player already knows
+    pub fn observe(&mut self, observation: &Observation) -> Result<(), EngineError> {
+        let mut next = self.clone();
+        match observation {
+            Observation::Bid { player, bid } => next.observe_bid(*player, *bid)?,
+            Observation::KittyRevealed(kitty) => next.observe_kitty(kitty)?,
+            Observation::Aside(aside) => next.observe_aside(aside)?,
+            Observation::TrumpsAside(cards) => {
+                next.observe_trumps_aside(cards)?;
+                next.check_deal_exists()?;
+            }
+            Observation::ChelemDeclared { player } => next.shared_state.declare_chelem(*player)?,
+            Observation::HandfulShown { player, cards } => {
+                next.observe_handful(*player, cards)?;
+                next.check_deal_exists()?;
+            }
+            Observation::CardPlayed { player, card } => {
+                next.observe_card(*player, card)?;
+                next.check_deal_exists()?;
+            }
+            Observation::TrickWon { winner } => next.observe_trick_won(*winner)?,
+        }
+        *self = next;
+        Ok(())
+    }
+
+    pub fn next_bidder(&self) -> Option<u8> {
+        (self.auction.len() < 4)
+            .then(|| ((self.shared_state.dealer as usize + 1 + self.auction.len()) % 4) as u8)
+    }
+
+    // Bids are only followed from the first one, states built otherwise have
+    // an empty auction
+    pub fn auction_in_progress(&self) -> bool {
+        !self.auction.is_empty() && self.auction.len() < 4
+    }
+
+    fn play_has_begun(&self) -> bool {
+        !self.shared_state.played_tricks.is_empty()
+            || self
+                .shared_state
+                .current_trick
+                .is_some_and(|trick| trick.cards.iter().any(Option::is_some))
+    }
+
+    fn observe_bid(&mut self, player: u8, bid: Option<GameType>) -> Result<(), EngineError> {
+        if self.play_has_begun() || self.kitty.is_some() {
+            return Err(inconsistent("The auction is over"));
+        }
+        if self.next_bidder() != Some(player) {
+            return Err(EngineError::OutOfOrderPlay);
+        }
+        // Every bid has to be above the previous one
+        if let (Some(bid), Some(current)) = (bid, self.shared_state.game_type) {
+            if bid.with_chelem(false) <= current.with_chelem(false) {
+                return Err(EngineError::InvalidBid);
+            }
+        }
+        self.shared_state.bid(player, bid)?;
+        self.auction.push(bid);
+        Ok(())
+    }
+
+    fn observe_kitty(&mut self, kitty: &[Card; 6]) -> Result<(), EngineError> {
+        let taker = self.shared_state.taker.ok_or(EngineError::NoTaker)?;
+        if self.auction_in_progress() {
+            return Err(inconsistent("The auction is not over"));
+        }
+        if !self.shared_state.kitty_should_be_revealed() {
+            return Err(inconsistent("The contract keeps the kitty hidden"));
+        }
+        if self.kitty.is_some() || self.play_has_begun() {
+            return Err(inconsistent("The kitty was already revealed"));
+        }
+        let distinct: HashSet<&Card> = kitty.iter().collect();
+        if distinct.len() != 6
+            || kitty
+                .iter()
+                .any(|card| self.player_state.hand.contains(card))
+        {
+            return Err(inconsistent("The kitty holds cards of the hand"));
+        }
+        if taker == self.player_index {
+            self.player_state.hand.extend(kitty);
+        }
+        self.kitty = Some(*kitty);
+        Ok(())
+    }
+
+    fn observe_aside(&mut self, aside: &[Card; 6]) -> Result<(), EngineError> {
+        if self.shared_state.taker != Some(self.player_index) {
+            return Err(inconsistent("Only the taker sees the écart"));
+        }
+        if self.kitty.is_none() {
+            return Err(inconsistent("The kitty was not revealed"));
+        }
+        self.player_state.check_aside(aside)?;
+        self.player_state.chose_aside(*aside)?;
+        self.shared_state.trumps_aside = trumps_in(aside);
+        self.kitty = Some(*aside);
+        Ok(())
+    }
+
+    fn observe_trumps_aside(&mut self, cards: &[Card]) -> Result<(), EngineError> {
+        if self.kitty.is_none() || self.play_has_begun() {
+            return Err(inconsistent("Trumps are shown when the écart is set aside"));
+        }
+        let mut shown = cards.to_vec();
+        shown.sort_by_key(Card::index);
+        shown.dedup();
+        if shown.is_empty()
+            || shown.len() != cards.len()
+            || shown
+                .iter()
+                .any(|card| card.color != Color::Trump || card.is_oudler())
+        {
+            return Err(inconsistent(
+                "Only trumps other than the oudlers can be set aside",
+            ));
+        }
+        if self.shared_state.taker == Some(self.player_index) {
+            // The taker knows the écart
+            if self.player_state.hand.len() != 18 || self.shared_state.trumps_aside != shown {
+                return Err(inconsistent("The écart holds other trumps"));
+            }
+        } else if !self.shared_state.trumps_aside.is_empty() {
+            return Err(inconsistent("The trumps of the écart were already shown"));
+        } else if shown
+            .iter()
+            .any(|card| self.player_state.hand.contains(card))
+        {
+            return Err(inconsistent("The écart holds cards of the hand"));
+        }
+        self.shared_state.trumps_aside = shown;
+        Ok(())
+    }
+
+    fn observe_handful(&mut self, player: u8, cards: &[Card]) -> Result<(), EngineError> {
+        let shown: HashSet<Card> = cards.iter().copied().collect();
+        let handful = Handfuls::from_size(cards.len())
+            .filter(|_| shown.len() == cards.len())
+            .ok_or(EngineError::InvalidAnnouncement)?;
+        let already_played = !self.shared_state.played_tricks.is_empty()
+            || self
+                .shared_state
+                .current_trick
+                .is_some_and(|trick| trick.cards[player as usize].is_some());
+        if already_played || self.shared_state.declared_handfuls[player as usize].is_some() {
+            return Err(EngineError::InvalidAnnouncement);
+        }
+        let trumps_shown = shown
+            .iter()
+            .filter(|card| card.color == Color::Trump)
+            .count();
+        if trumps_shown + 1 < cards.len()
+            || shown
+                .iter()
+                .any(|card| card.color != Color::Trump && card.color != Color::Excuse)
+        {
+            return Err(EngineError::InvalidAnnouncement);
+        }
+        if player == self.player_index {
+            let hand = &self.player_state.hand;
+            let trumps_in_hand = hand
+                .iter()
+                .filter(|card| card.color == Color::Trump)
+                .count();
+            // The Excuse only completes a handful the trumps are short of
+            if !shown.iter().all(|card| hand.contains(card))
+                || (trumps_shown < cards.len() && trumps_in_hand >= cards.len())
+            {
+                return Err(EngineError::InvalidAnnouncement);
+            }
+        } else {
+            let held = self.cards_player_may_hold(player)?;
+            if !shown.is_subset(&held) {
+                return Err(inconsistent(
+                    "The handful shows cards the player can not hold",
+                ));
+            }
+        }
+        self.shared_state.declared_handfuls[player as usize] = Some(DeclaredHandfuls {
+            handful,
+            cards: shown,
+        });
+        Ok(())
+    }
+
+    fn observe_card(&mut self, player: u8, card: &Card) -> Result<(), EngineError> {
+        self.shared_state.taker.ok_or(EngineError::NoTaker)?;
+        if self.auction_in_progress() {
+            return Err(inconsistent("The auction is not over"));
+        }
+        if self.shared_state.kitty_should_be_revealed() && self.kitty.is_none() {
+            return Err(inconsistent("The kitty has to be shown first"));
+        }
+        let next = self
+            .shared_state
+            .next_to_play()
+            .ok_or(EngineError::FinishedHand)?;
+        if next != player {
+            return Err(EngineError::OutOfOrderPlay);
+        }
+        let mut trick = self.shared_state.current_or_new_trick();
+        if player == self.player_index {
+            let expected = self.get_known_constraints(player as usize)?.number_cards;
+            if self.player_state.hand.len() != expected {
+                return Err(inconsistent("The taker has not set the écart aside"));
+            }
+            self.player_state.play_a_card(&mut trick, player, card)?;
+        } else {
+            if !self.cards_player_may_hold(player)?.contains(card) {
+                return Err(inconsistent("The player can not hold the card"));
+            }
+            // Cards known to be in the hand may force another play
+            let mut known = PlayerGameState {
+                hand: self.get_known_constraints(player as usize)?.known_cards,
+            };
+            known.hand.insert(*card);
+            known.allowed_to_play(card, &trick)?;
+            trick.play_card(player, card)?;
+        }
+        self.shared_state.current_trick = Some(trick);
+        if trick.next_to_play().is_none() {
+            self.shared_state.finish_trick()?;
+        }
+        Ok(())
+    }
+
+    // Tricks are closed with their fourth card, the winner is only checked
+    fn observe_trick_won(&mut self, winner: u8) -> Result<(), EngineError> {
+        let trick = self
+            .shared_state
+            .played_tricks
+            .last()
+            .ok_or(EngineError::UnfinishedHand)?;
+        if self
+            .shared_state
+            .current_trick
+            .is_some_and(|trick| trick.cards.iter().any(Option::is_some))
+        {
+            return Err(EngineError::UnfinishedHand);
+        }
+        if trick.winner != winner {
+            return Err(EngineError::InconsistentObservation(format!(
+                "Player {} won the trick, not player {}",
+                trick.winner, winner
+            )));
+        }
+        Ok(())
+    }
+
+    fn cards_player_may_hold(&self, player: u8) -> Result<HashSet<Card>, EngineError> {
+        let constraints: [PlayerConstraint; 4] = [
+            self.get_known_constraints(0)?,
+            self.get_known_constraints(1)?,
+            self.get_known_constraints(2)?,
+            self.get_known_constraints(3)?,
+        ];
+        let left_to_play = self.shared_state.cards_left_to_play();
+        let mut cards = self.possible_cards(&constraints, &left_to_play, player as usize);
+        cards.extend(&constraints[player as usize].known_cards);
+        Ok(cards)
+    }
+
+    fn check_deal_exists(&self) -> Result<(), EngineError> {
+        let observer = self.get_known_constraints(self.player_index as usize)?;
+        // Before the écart the taker holds more cards than any deal allows
+        if self.player_state.hand.len() != observer.number_cards {
+            return Ok(());
+        }
+        match self.deal_constraints() {
+            Ok(constraints) if constraints.count() > 0 => Ok(()),
+            _ => Err(inconsistent("No deal fits every observation")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use rand::{seq::IndexedRandom, SeedableRng};
+
+    use super::*;
+    use crate::business::{
+        analysis::{announcements::handful_to_show, ecart::legal_asides},
+        game_engine::{game_state::GameState, seed::TarotRng},
+    };
+
+    struct ObservedDeal {
+        state: GameState,
+        dealt: [HashSet<Card>; 4],
+        // Who sees each observation, everybody when None
+        observations: Vec<(Option<u8>, Observation)>,
+    }
+
+    impl ObservedDeal {
+        // A random deal taken in garde by the player after the dealer, played
+        // up to the given number of tricks
+        fn new(rng: &mut TarotRng, tricks: usize) -> Self {
+            let mut state = GameState::random_init_with_rng(rng).unwrap();
+            let dealt = state.players_state.clone().map(|player| player.hand);
+            let dealer = state.shared_state.dealer;
+            let taker = (dealer + 1) % 4;
+            let mut observations = vec![];
+            for offset in 1..=4 {
+                let player = (dealer + offset) % 4;
+                let bid = (player == taker).then_some(GameType::Garde { chelem: false });
+                state.bid(player, bid).unwrap();
+                observations.push((None, Observation::Bid { player, bid }));
+            }
+            observations.push((None, Observation::KittyRevealed(state.kitty)));
+            state.take_kitty().unwrap();
+            let aside = legal_asides(&state.players_state[taker as usize].hand)[0];
+            state.discard(aside).unwrap();
+            observations.push((Some(taker), Observation::Aside(aside)));
+            let trumps_aside = state.shared_state.trumps_aside.clone();
+            if !trumps_aside.is_empty() {
+                observations.push((None, Observation::TrumpsAside(trumps_aside)));
+            }
+            for player in 0..4 {
+                if let Some(cards) = handful_to_show(&state.players_state[player as usize].hand) {
+                    let mut cards: Vec<Card> = cards.into_iter().collect();
+                    cards.sort_by_key(Card::index);
+                    state
+                        .declare_handful(player, cards.iter().copied().collect())
+                        .unwrap();
+                    observations.push((None, Observation::HandfulShown { player, cards }));
+                }
+            }
+            let mut deal = Self {
+                state,
+                dealt,
+                observations,
+            };
+            while deal.state.shared_state.played_tricks.len() < tricks {
+                let player = deal.state.shared_state.next_to_play().unwrap();
+                let card = *deal.state.legal_moves(player).choose(rng).unwrap();
+                deal.play(player, card);
+            }
+            deal
+        }
+
+        fn play(&mut self, player: u8, card: Card) {
+            self.state.play_card(player, &card).unwrap();
+            self.observations
+                .push((None, Observation::CardPlayed { player, card }));
+            if self.state.shared_state.current_trick.is_none() {
+                let winner = self.state.shared_state.played_tricks.last().unwrap().winner;
+                self.observations
+                    .push((None, Observation::TrickWon { winner }));
+            }
+        }
+
+        fn seen_by(&self, observer: u8) -> KnownGameState {
+            let mut known = KnownGameState::initialize(
+                self.dealt[observer as usize].clone(),
+                observer,
+                None,
+                self.state.shared_state.dealer,
+            );
+            for (seen_by, observation) in &self.observations {
+                if seen_by.is_none_or(|seen_by| seen_by == observer) {
+                    known.observe(observation).unwrap();
+                }
+            }
+            known
+        }
+    }
+
+    #[test]
+    fn test_observations_rebuild_the_view_of_every_player() {
+        let mut rng = TarotRng::seed_from_u64(46);
+        for tricks in [0, 7, 12] {
+            let deal = ObservedDeal::new(&mut rng, tricks);
+            for observer in 0..4 {
+                let known = deal.seen_by(observer);
+                let omniscient = KnownGameState::from_omniscient(&deal.state, observer);
+                assert_eq!(known.player_state.hand, omniscient.player_state.hand);
+                assert_eq!(known.auction, omniscient.auction);
+                for player in 0..4 {
+                    let observed = known.get_known_constraints(player).unwrap();
+                    let expected = omniscient.get_known_constraints(player).unwrap();
+                    assert_eq!(observed.number_cards, expected.number_cards);
+                    assert_eq!(observed.highest_trump, expected.highest_trump);
+                    assert_eq!(observed.voided_colors, expected.voided_colors);
+                    assert_eq!(observed.known_cards, expected.known_cards);
+                }
+                let sampled = known
+                    .possible_random_full_state_v2_with_rng(&mut rng)
+                    .unwrap();
+                assert_eq!(
+                    sampled.players_state[observer as usize].hand,
+                    known.player_state.hand
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_contradicting_observations_are_rejected() {
+        let mut rng = TarotRng::seed_from_u64(46);
+        let mut deal = ObservedDeal::new(&mut rng, 12);
+        let taker = deal.state.shared_state.taker.unwrap();
+        let observer = (taker + 1) % 4;
+        let mut known = deal.seen_by(observer);
+        let before = known.clone();
+        let leader = known.shared_state.next_to_play().unwrap();
+        let card = deal.state.legal_moves(leader)[0];
+        assert!(matches!(
+            known.observe(&Observation::CardPlayed {
+                player: (leader + 1) % 4,
+                card,
+            }),
+            Err(EngineError::OutOfOrderPlay)
+        ));
+        let played = deal.state.shared_state.played_tricks[0].cards[0];
+        let own_card = *known.player_state.hand.iter().next().unwrap();
+        for card in [played, own_card] {
+            if leader != observer {
+                assert!(matches!(
+                    known.observe(&Observation::CardPlayed {
+                        player: leader,
+                        card,
+                    }),
+                    Err(EngineError::InconsistentObservation(_))
+                ));
+            }
+        }
+        let winner = deal.state.shared_state.played_tricks[11].winner;
+        assert!(known
+            .observe(&Observation::TrickWon {
+                winner: (winner + 1) % 4,
+            })
+            .is_err());
+        assert!(known
+            .observe(&Observation::Bid {
+                player: known.shared_state.dealer,
+                bid: Some(GameType::GardeContre { chelem: false }),
+            })
+            .is_err());
+        assert!(known
+            .observe(&Observation::ChelemDeclared { player: taker })
+            .is_err());
+        // Rejected observations leave the view as it was
+        assert_eq!(
+            known.shared_state.play_history(),
+            before.shared_state.play_history()
+        );
+        assert_eq!(known.player_state.hand, before.player_state.hand);
+        assert!(known.observe(&Observation::TrickWon { winner }).is_ok());
+
+        // A player who showed a void can not follow that color any more
+        let voids: Vec<(u8, Color)> = (0..4)
+            .filter(|player| *player != observer)
+            .flat_map(|player| {
+                let constraint = known.get_known_constraints(player as usize).unwrap();
+                constraint
+                    .voided_colors
+                    .into_iter()
+                    .map(move |color| (player, color))
+            })
+            .collect();
+        let (player, color) = voids
+            .into_iter()
+            .find(|(_, color)| *color != Color::Excuse)
+            .expect("Twelve tricks show a void");
+        while deal.state.shared_state.next_to_play() != Some(player) {
+            let next = deal.state.shared_state.next_to_play().unwrap();
+            let card = deal.state.legal_moves(next)[0];
+            deal.play(next, card);
+        }
+        let mut known = deal.seen_by(observer);
+        let cards_left = known.shared_state.cards_left_to_play();
+        let card = cards_left
+            .iter()
+            .find(|card| card.color == color && !known.player_state.hand.contains(card))
+            .expect("Another player holds a card of the color");
+        assert!(matches!(
+            known.observe(&Observation::CardPlayed {
+                player,
+                card: *card,
+            }),
+            Err(EngineError::InconsistentObservation(_))
+        ));
+    }
+
+    #[test]
+    fn test_trumps_aside_are_shown_to_everyone() {
+        // Player 0 takes the T17 to T21 and the Excuse from the kitty, and
+        // must set aside three trumps
+        let mut hand: Vec<Card> = (2..=16)
+            .map(|value| Card::new(Color::Trump, value).unwrap())
+            .collect();
+        hand.extend((1..=3).map(|value| Card::new(Color::Spade, value).unwrap()));
+        let mut state = GameState::with_first_hand(hand);
+        let dealt = state.players_state.clone().map(|player| player.hand);
+        let kitty = state.kitty;
+        state
+            .shared_state
+            .bid(0, Some(GameType::Garde { chelem: false }))
+            .unwrap();
+        state.take_kitty().unwrap();
+        let aside = legal_asides(&state.players_state[0].hand)[0];
+        state.discard(aside).unwrap();
+        let shown = state.shared_state.trumps_aside.clone();
+        let view = |player: u8| {
+            let mut known =
+                KnownGameState::initialize(dealt[player as usize].clone(), player, None, 3);
+            for bidder in 0..4 {
+                let bid = (bidder == 0).then_some(GameType::Garde { chelem: false });
+                known
+                    .observe(&Observation::Bid {
+                        player: bidder,
+                        bid,
+                    })
+                    .unwrap();
+            }
+            known
+        };
+
+        let mut defender = view(1);
+        let trumps_aside = Observation::TrumpsAside(shown.clone());
+        assert!(defender.observe(&trumps_aside).is_err());
+        defender
+            .observe(&Observation::KittyRevealed(kitty))
+            .unwrap();
+        for cards in [
+            vec![Card::from_str("T21").unwrap()],
+            vec![Card::from_str("4S").unwrap()],
+            vec![shown[0], shown[0]],
+        ] {
+            assert!(matches!(
+                defender.observe(&Observation::TrumpsAside(cards)),
+                Err(EngineError::InconsistentObservation(_))
+            ));
+        }
+        defender.observe(&trumps_aside).unwrap();
+        assert_eq!(defender.shared_state.trumps_aside, shown);
+        assert!(defender.observe(&trumps_aside).is_err());
+
+        let mut taker = view(0);
+        taker.observe(&Observation::KittyRevealed(kitty)).unwrap();
+        assert!(taker.observe(&trumps_aside).is_err());
+        taker.observe(&Observation::Aside(aside)).unwrap();
+        assert!(taker
+            .observe(&Observation::TrumpsAside(shown[1..].to_vec()))
+            .is_err());
+        taker.observe(&trumps_aside).unwrap();
+    }
+}
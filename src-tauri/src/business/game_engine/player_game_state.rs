@@ -1,3 +1,4 @@
+use serde::{Deserialize, Serialize};
 use std::{collections::HashSet, fmt};
 
 use super::{
@@ -6,7 +7,7 @@ use super::{
     trick::Trick,
 };
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PlayerGameState {
     pub hand: HashSet<Card>,
 }
@@ -26,10 +27,20 @@ impl PlayerGameState {
             .collect::<HashSet<&Card>>()
     }
 
+    pub fn sorted_cards_allowed(&self, trick: &Trick) -> Vec<Card> {
+        let mut allowed: Vec<Card> = self.cards_allowed(trick).into_iter().cloned().collect();
+        allowed.sort_by_key(Card::index);
+        allowed
+    }
+
     pub fn allowed_to_play(&self, card: &Card, trick: &Trick) -> Result<(), EngineError> {
         if !self.hand.contains(card) {
             return Err(EngineError::DoesNotHaveCard);
         }
+        // The excuse can always be played
+        if card.color == Color::Excuse {
+            return Ok(());
+        }
         let Some(trick_color) = trick.color() else {
             return Ok(());
         };
@@ -42,11 +53,8 @@ impl PlayerGameState {
         {
             return Err(EngineError::HasToTrump);
         }
-        if !self.has_color(trick_color)
-            && card.color == Color::Trump
-            && self.can_overtrump(trick)
-            && !trick.overtrumped_by(card)
-        {
+        // Overtrumping applies both when trumps are led and when trumping in
+        if card.color == Color::Trump && self.can_overtrump(trick) && !trick.overtrumped_by(card) {
             return Err(EngineError::HasToOvertrump);
         }
         Ok(())
@@ -81,6 +89,37 @@ impl PlayerGameState {
             .any(|card| card.color == Color::Trump && card.value > highest_trump)
     }
 
+    // The rules of the écart, for a taker holding the kitty
+    pub fn check_aside(&self, aside: &[Card; 6]) -> Result<(), EngineError> {
+        if self.hand.len() != 24 {
+            return Err(EngineError::InvalidAside);
+        }
+        let distinct: HashSet<&Card> = aside.iter().collect();
+        if distinct.len() != 6 || aside.iter().any(|card| !self.hand.contains(card)) {
+            return Err(EngineError::InvalidAside);
+        }
+        if aside
+            .iter()
+            .any(|card| card.is_oudler() || (card.color != Color::Trump && card.value == 14))
+        {
+            return Err(EngineError::InvalidAside);
+        }
+        // Trumps can only be set aside when there is nothing else left to discard
+        let other_candidates = self
+            .hand
+            .iter()
+            .filter(|card| card.color != Color::Trump && !card.is_oudler() && card.value != 14)
+            .count();
+        let trumps_aside = aside
+            .iter()
+            .filter(|card| card.color == Color::Trump)
+            .count();
+        if trumps_aside > 0 && other_candidates + trumps_aside > 6 {
+            return Err(EngineError::InvalidAside);
+        }
+        Ok(())
+    }
+
     pub fn chose_aside(&mut self, aside: [Card; 6]) -> Result<(), EngineError> {
         for card in aside {
             self.use_card(&card)?;
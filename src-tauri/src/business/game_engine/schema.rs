@@ -0,0 +1,113 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+use super::engine_error::EngineError;
+
+pub const SCHEMA_VERSION: u32 = 1;
+
+type Migration = fn(Value) -> Result<Value, EngineError>;
+
+// MIGRATIONS[i] upgrades a payload written with version i + 1 to version i + 2,
+// so a new entry has to be pushed here every time SCHEMA_VERSION is bumped.
+const MIGRATIONS: [Migration; (SCHEMA_VERSION - 1) as usize] = [];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Versioned<T> {
+    pub schema_version: u32,
+    pub data: T,
+}
+
+impl<T: Serialize + DeserializeOwned> Versioned<T> {
+    pub fn new(data: T) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            data,
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, EngineError> {
+        serde_json::to_string(self).map_err(|e| EngineError::Serialization(e.to_string()))
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, EngineError> {
+        let value: Value =
+            serde_json::from_str(json).map_err(|e| EngineError::Serialization(e.to_string()))?;
+        Self::from_value(value)
+    }
+
+    pub fn from_value(mut value: Value) -> Result<Self, EngineError> {
+        let version = value.get("schema_version").and_then(Value::as_u64).ok_or(
+            EngineError::Serialization(String::from("Missing schema_version field")),
+        )? as u32;
+        let data = value
+            .get_mut("data")
+            .map(Value::take)
+            .ok_or(EngineError::Serialization(String::from(
+                "Missing data field",
+            )))?;
+        let data: T = serde_json::from_value(migrate(data, version)?)
+            .map_err(|e| EngineError::Serialization(e.to_string()))?;
+        Ok(Self::new(data))
+    }
+}
+
+pub fn migrate(mut data: Value, from_version: u32) -> Result<Value, EngineError> {
+    if from_version == 0 || from_version > SCHEMA_VERSION {
+        return Err(EngineError::UnsupportedSchemaVersion(from_version));
+    }
+    for migration in &MIGRATIONS[(from_version - 1) as usize..] {
+        data = migration(data)?;
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::business::game_engine::{game_state::GameState, seed::TarotRng};
+
+    #[test]
+    fn test_game_state_round_trip() {
+        let state = GameState::random_init_with_rng(&mut TarotRng::seed_from_u64(26)).unwrap();
+        let json = Versioned::new(state.clone()).to_json().unwrap();
+        let decoded = Versioned::<GameState>::from_json(&json).unwrap();
+        assert_eq!(decoded.schema_version, SCHEMA_VERSION);
+        assert_eq!(decoded.data.kitty, state.kitty);
+        assert_eq!(decoded.data.shared_state.dealer, state.shared_state.dealer);
+        for i in 0..4 {
+            assert_eq!(
+                decoded.data.players_state[i].hand,
+                state.players_state[i].hand
+            );
+        }
+    }
+
+    #[test]
+    fn test_rejects_unknown_version() {
+        let json = format!(
+            "{{\"schema_version\":{},\"data\":{{\"color\":\"Trump\",\"value\":21}}}}",
+            SCHEMA_VERSION + 1
+        );
+        assert!(matches!(
+            Versioned::<crate::business::Card>::from_json(&json),
+            Err(EngineError::UnsupportedSchemaVersion(_))
+        ));
+    }
+
+    #[test]
+    fn test_rejects_invalid_card() {
+        let json = |value: u8| {
+            format!(
+                "{{\"schema_version\":{},\"data\":{{\"color\":\"Trump\",\"value\":{}}}}}",
+                SCHEMA_VERSION, value
+            )
+        };
+        assert!(Versioned::<crate::business::Card>::from_json(&json(21)).is_ok());
+        assert!(matches!(
+            Versioned::<crate::business::Card>::from_json(&json(99)),
+            Err(EngineError::Serialization(_))
+        ));
+    }
+}
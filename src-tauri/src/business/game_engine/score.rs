@@ -0,0 +1,190 @@
+use serde::{Deserialize, Serialize};
+
+use super::{
+    card::{Card, Color},
+    engine_error::EngineError,
+    game_state::GameState,
+    game_type::GameType,
+    trick::PlayedTrick,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Score {
+    pub taker: u8,
+    pub taker_points: usize,
+    pub oudlers: usize,
+    pub contract_won: bool,
+    pub marque: i32,
+}
+
+impl Score {
+    pub fn compute(state: &GameState) -> Result<Score, EngineError> {
+        let shared_state = &state.shared_state;
+        if !shared_state.finished() {
+            return Err(EngineError::UnfinishedHand);
+        }
+        let taker = shared_state.taker.ok_or(EngineError::NoTaker)?;
+        let game_type = shared_state.game_type.ok_or(EngineError::NoTaker)?;
+        let mut taker_points = shared_state.current_score();
+        let mut oudlers = oudlers_won(&shared_state.played_tricks, taker);
+        if game_type.kitty_goes_to_taker() {
+            taker_points += state.kitty.iter().map(Card::points).sum::<usize>();
+            oudlers += state.kitty.iter().filter(|card| card.is_oudler()).count();
+        }
+        let target = contract_target(oudlers);
+        let contract_won = taker_points >= target;
+        let multiplier = game_type.hand_points_multiplier() as i32;
+        // Points are counted in half points, the difference is rounded up
+        let difference = taker_points.abs_diff(target).div_ceil(2);
+        let handfuls = shared_state
+            .declared_handfuls
+            .iter()
+            .flatten()
+            .map(|declared| declared.handful.points())
+            .sum();
+        let mut marque = marque(game_type, contract_won, difference, handfuls);
+        if let Some(last_trick) = shared_state.played_tricks.last() {
+            if last_trick
+                .cards
+                .iter()
+                .any(|card| card.color == Color::Trump && card.value == 1)
+            {
+                marque += if last_trick.winner == taker { 10 } else { -10 } * multiplier;
+            }
+        }
+        let tricks_won = shared_state
+            .played_tricks
+            .iter()
+            .filter(|trick| trick.winner == taker)
+            .count();
+        marque += match (tricks_won, game_type.chelem_announced()) {
+            (18, true) => 400,
+            (18, false) => 200,
+            (_, true) | (0, false) => -200,
+            _ => 0,
+        };
+        Ok(Score {
+            taker,
+            taker_points,
+            oudlers,
+            contract_won,
+            marque,
+        })
+    }
+
+    pub fn per_player(&self) -> [i32; 4] {
+        let mut result = [-self.marque; 4];
+        result[self.taker as usize] = 3 * self.marque;
+        result
+    }
+}
+
+// Oudlers in the taker's tricks, the Excuse staying with whoever played it
+pub fn oudlers_won(played_tricks: &[PlayedTrick], taker: u8) -> usize {
+    let mut oudlers = 0;
+    for trick in played_tricks {
+        for (player, card) in trick.cards.iter().enumerate() {
+            let kept_by_taker = if card.color == Color::Excuse {
+                player as u8 == taker
+            } else {
+                trick.winner == taker
+            };
+            if card.is_oudler() && kept_by_taker {
+                oudlers += 1;
+            }
+        }
+    }
+    oudlers
+}
+
+// Marque of a contract made or lost by `difference` points, before the Petit
+// au bout and the chelem
+pub fn marque(game_type: GameType, contract_won: bool, difference: usize, handfuls: usize) -> i32 {
+    let sign = if contract_won { 1 } else { -1 };
+    let multiplier = game_type.hand_points_multiplier() as i32;
+    sign * ((25 + difference as i32) * multiplier + handfuls as i32)
+}
+
+pub fn contract_target(oudlers: usize) -> usize {
+    match oudlers {
+        0 => 112,
+        1 => 102,
+        2 => 82,
+        _ => 72,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    // Taker 0 plays the Excuse first, the Petit falls in the last trick and
+    // the kitty holds six low spades
+    fn finished_game(game_type: GameType, winner: u8) -> GameState {
+        let kitty = [1, 2, 3, 4, 5, 6].map(|value| Card::new(Color::Spade, value).unwrap());
+        let mut cards: Vec<Card> = Card::all_possibles()
+            .into_iter()
+            .filter(|card| !kitty.contains(card))
+            .collect();
+        let petit = Card::new(Color::Trump, 1).unwrap();
+        cards.sort_by_key(|card| (card.color == Color::Excuse, *card == petit, card.index()));
+        cards.rotate_right(1);
+        let hands: [HashSet<Card>; 4] = [
+            HashSet::new(),
+            HashSet::new(),
+            HashSet::new(),
+            HashSet::new(),
+        ];
+        let mut state = GameState::initialize(hands, kitty, 3);
+        state.shared_state.bid(0, Some(game_type)).unwrap();
+        state.shared_state.played_tricks = cards
+            .chunks_exact(4)
+            .map(|chunk| PlayedTrick {
+                cards: chunk.try_into().unwrap(),
+                winner,
+                leader: 0,
+            })
+            .collect();
+        state
+    }
+
+    #[test]
+    fn test_unannounced_chelem() {
+        let score = Score::compute(&finished_game(GameType::Garde { chelem: false }, 0)).unwrap();
+        assert_eq!(score.taker_points, 182);
+        assert_eq!(score.oudlers, 3);
+        assert!(score.contract_won);
+        // (25 + 55) * 2, the Petit au bout for 10 * 2 and 200 for the chelem
+        assert_eq!(score.marque, 380);
+        assert_eq!(score.per_player(), [1140, -380, -380, -380]);
+    }
+
+    #[test]
+    fn test_lost_contract_without_a_trick() {
+        let score = Score::compute(&finished_game(GameType::Petit { chelem: false }, 1)).unwrap();
+        // The kitty and the Excuse given back for a low card
+        assert_eq!(score.taker_points, 6 + 8);
+        assert_eq!(score.oudlers, 1);
+        assert!(!score.contract_won);
+        // -(25 + 44), the Petit au bout lost and 200 for the defenders' chelem
+        assert_eq!(score.marque, -69 - 10 - 200);
+
+        // The kitty is not counted in a Garde contre
+        let score =
+            Score::compute(&finished_game(GameType::GardeContre { chelem: false }, 1)).unwrap();
+        assert_eq!(score.taker_points, 8);
+        assert_eq!(score.marque, -(25 + 47) * 6 - 60 - 200);
+    }
+
+    #[test]
+    fn test_unfinished_game_has_no_score() {
+        let mut state = finished_game(GameType::Garde { chelem: false }, 0);
+        state.shared_state.played_tricks.pop();
+        assert!(matches!(
+            Score::compute(&state),
+            Err(EngineError::UnfinishedHand)
+        ));
+    }
+}
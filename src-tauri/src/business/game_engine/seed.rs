@@ -0,0 +1,20 @@
+use rand::{rngs::StdRng, SeedableRng};
+
+pub type TarotRng = StdRng;
+
+pub fn rng_from_seed(seed: u64) -> TarotRng {
+    StdRng::seed_from_u64(seed)
+}
+
+pub fn random_seed() -> u64 {
+    rand::random()
+}
+
+// Gives independent seeds to parallel workers or successive samples, so that
+// results do not depend on how the work is scheduled
+pub fn derive_seed(seed: u64, stream: u64) -> u64 {
+    let mut z = seed ^ stream.wrapping_add(1).wrapping_mul(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
@@ -1,6 +1,7 @@
+use serde::{Deserialize, Serialize};
 use std::{collections::HashSet, fmt};
 
-use crate::business::Card;
+use crate::business::{Card, Color};
 
 use super::{
     engine_error::EngineError,
@@ -9,7 +10,7 @@ use super::{
     trick::{PlayedTrick, Trick},
 };
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SharedGameState {
     pub dealer: u8,
     pub taker: Option<u8>,
@@ -17,6 +18,9 @@ pub struct SharedGameState {
     pub played_tricks: Vec<PlayedTrick>,
     pub game_type: Option<GameType>,
     pub declared_handfuls: [Option<DeclaredHandfuls>; 4],
+    // Trumps of the écart, which the taker shows when setting them aside
+    #[serde(default)]
+    pub trumps_aside: Vec<Card>,
 }
 
 impl fmt::Display for SharedGameState {
@@ -39,15 +43,17 @@ impl fmt::Display for SharedGameState {
 
 impl SharedGameState {
     pub fn player_to_lead(&self) -> u8 {
-        match self.played_tricks.last() {
-            None => (self.dealer + 1) % 4,
-            Some(trick) => trick.winner,
+        match (self.played_tricks.last(), self.taker, self.game_type) {
+            (Some(trick), _, _) => trick.winner,
+            (None, Some(taker), Some(game_type)) if game_type.chelem_announced() => taker,
+            (None, _, _) => (self.dealer + 1) % 4,
         }
     }
 
     pub fn finish_trick(&mut self) -> Result<(), EngineError> {
         if let Some(trick) = self.current_trick {
             self.played_tricks.push(trick.into_played()?);
+            self.current_trick = None;
             Ok(())
         } else {
             Err(EngineError::NotBegunHand)
@@ -56,16 +62,31 @@ impl SharedGameState {
 
     pub fn bid(&mut self, player: u8, bid: Option<GameType>) -> Result<(), EngineError> {
         match (self.game_type, bid) {
+            (_, None) => Ok(()),
             (Some(previous_bid), Some(bid)) if (previous_bid > bid) => Err(EngineError::InvalidBid),
-            (None, _) | (_, Some(_)) => {
+            (_, Some(_)) => {
                 self.taker = Some(player);
                 self.game_type = bid;
                 Ok(())
             }
-            (_, None) => Ok(()),
         }
     }
 
+    pub fn declare_chelem(&mut self, player: u8) -> Result<(), EngineError> {
+        if self.taker != Some(player) || !self.played_tricks.is_empty() {
+            return Err(EngineError::InvalidAnnouncement);
+        }
+        if self
+            .current_trick
+            .is_some_and(|trick| trick.cards.iter().any(Option::is_some))
+        {
+            return Err(EngineError::InvalidAnnouncement);
+        }
+        let game_type = self.game_type.ok_or(EngineError::NoTaker)?;
+        self.game_type = Some(game_type.with_chelem(true));
+        Ok(())
+    }
+
     pub fn initialize(dealer: u8) -> Self {
         Self {
             dealer,
@@ -74,20 +95,18 @@ impl SharedGameState {
             played_tricks: vec![],
             game_type: None,
             declared_handfuls: [None, None, None, None],
+            trumps_aside: vec![],
         }
     }
 
     pub fn current_score(&self) -> usize {
-        let mut total: usize = 0;
         let Some(current_taker) = self.taker else {
-            return total;
+            return 0;
         };
-        for trick in &self.played_tricks {
-            if trick.winner == current_taker {
-                total += trick.points();
-            }
-        }
-        total
+        self.played_tricks
+            .iter()
+            .map(|trick| trick.taker_points(current_taker))
+            .sum()
     }
 
     pub fn kitty_should_be_revealed(&self) -> bool {
@@ -109,11 +128,62 @@ impl SharedGameState {
         })
     }
 
+    pub fn current_or_new_trick(&self) -> Trick {
+        self.current_trick
+            .unwrap_or(Trick::new(self.player_to_lead()))
+    }
+
     pub fn new_trick(&mut self) -> Trick {
         self.current_trick = Some(Trick::new(self.player_to_lead()));
         self.current_trick.unwrap()
     }
 
+    // Every card played so far with its player, in playing order
+    pub fn play_history(&self) -> Vec<(u8, Card)> {
+        let mut history = Vec::with_capacity(72);
+        for trick in &self.played_tricks {
+            for offset in 0..4 {
+                let player = (trick.leader + offset) % 4;
+                history.push((player, trick.cards[player as usize]));
+            }
+        }
+        if let Some(trick) = self.current_trick {
+            for offset in 0..4 {
+                let player = (trick.leader + offset) % 4;
+                if let Some(card) = trick.cards[player as usize] {
+                    history.push((player, card));
+                }
+            }
+        }
+        history
+    }
+
+    // Colours a player showed they lack, by not following or by completing a
+    // handful with the excuse, which shows every trump of the hand
+    pub fn voided_colors(&self, player: usize) -> HashSet<Color> {
+        let mut voided_colors = HashSet::new();
+        if self.declared_handfuls[player]
+            .as_ref()
+            .is_some_and(|handful| handful.cards.iter().any(|card| card.color == Color::Excuse))
+        {
+            voided_colors.insert(Color::Trump);
+        }
+        let tricks = self
+            .played_tricks
+            .iter()
+            .map(PlayedTrick::as_trick)
+            .chain(self.current_trick);
+        for trick in tricks {
+            if trick.did_not_have_color(player) {
+                voided_colors.extend(trick.color());
+            }
+            if trick.did_not_have_trump(player) {
+                voided_colors.insert(Color::Trump);
+            }
+        }
+        voided_colors
+    }
+
     pub fn cards_left_to_play(&self) -> HashSet<Card> {
         let mut all_cards = Card::all_possibles();
         for trick in &self.played_tricks {
@@ -121,6 +191,67 @@ impl SharedGameState {
                 all_cards.remove(&card);
             }
         }
+        for card in self.current_trick.iter().flat_map(|trick| trick.cards).flatten() {
+            all_cards.remove(&card);
+        }
         all_cards
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_auction_takes_one_round() {
+        let mut state = SharedGameState::initialize(3);
+        state
+            .bid(0, Some(GameType::Garde { chelem: false }))
+            .unwrap();
+        state.bid(1, None).unwrap();
+        assert!(matches!(
+            state.bid(2, Some(GameType::Petit { chelem: false })),
+            Err(EngineError::InvalidBid)
+        ));
+        state
+            .bid(3, Some(GameType::GardeSans { chelem: false }))
+            .unwrap();
+        assert_eq!(state.taker, Some(3));
+        assert_eq!(state.game_type, Some(GameType::GardeSans { chelem: false }));
+        assert!(!state.kitty_should_be_revealed());
+    }
+
+    #[test]
+    fn test_kitty_is_revealed_for_petit_and_garde() {
+        let mut state = SharedGameState::initialize(0);
+        assert!(!state.kitty_should_be_revealed());
+        state
+            .bid(1, Some(GameType::Petit { chelem: false }))
+            .unwrap();
+        assert!(state.kitty_should_be_revealed());
+        state
+            .bid(2, Some(GameType::Garde { chelem: false }))
+            .unwrap();
+        assert!(state.kitty_should_be_revealed());
+        state
+            .bid(3, Some(GameType::GardeContre { chelem: false }))
+            .unwrap();
+        assert!(!state.kitty_should_be_revealed());
+    }
+
+    #[test]
+    fn test_taker_leads_after_announcing_a_chelem() {
+        let mut state = SharedGameState::initialize(1);
+        state
+            .bid(3, Some(GameType::Garde { chelem: false }))
+            .unwrap();
+        assert_eq!(state.player_to_lead(), 2);
+        assert!(matches!(
+            state.declare_chelem(0),
+            Err(EngineError::InvalidAnnouncement)
+        ));
+        state.declare_chelem(3).unwrap();
+        assert_eq!(state.player_to_lead(), 3);
+        assert_eq!(state.next_to_play(), Some(3));
+    }
+}
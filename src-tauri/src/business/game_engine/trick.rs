@@ -1,3 +1,4 @@
+use serde::{Deserialize, Serialize};
 use std::fmt;
 
 use super::{
@@ -5,7 +6,7 @@ use super::{
     engine_error::EngineError,
 };
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct PlayedTrick {
     pub cards: [Card; 4],
     pub winner: u8,
@@ -20,6 +21,31 @@ impl PlayedTrick {
             + self.cards[3].points()
     }
 
+    pub fn excuse_player(&self) -> Option<u8> {
+        (0..4).find(|&player| self.cards[player as usize].color == Color::Excuse)
+    }
+
+    pub fn taker_points(&self, taker: u8) -> usize {
+        let taker_won = self.winner == taker;
+        match self.excuse_player() {
+            // The excuse goes back to its owner, who gives a low card in exchange
+            Some(owner) if (owner == taker) != taker_won => {
+                if taker_won {
+                    self.points() - 8
+                } else {
+                    8
+                }
+            }
+            _ => {
+                if taker_won {
+                    self.points()
+                } else {
+                    0
+                }
+            }
+        }
+    }
+
     pub fn color(&self) -> Color {
         if self.cards[self.leader as usize].color != Color::Excuse {
             self.cards[self.leader as usize].color
@@ -30,27 +56,26 @@ impl PlayedTrick {
     }
 
     pub fn did_not_have_color(&self, player: usize) -> bool {
-        let played_color = self.cards[player].color;
-        played_color != self.color() && played_color != Color::Excuse
+        self.as_trick().did_not_have_color(player)
+    }
+
+    pub fn did_not_have_trump(&self, player: usize) -> bool {
+        self.as_trick().did_not_have_trump(player)
     }
 
     pub fn did_not_have_trump_higher(&self, player: usize) -> Option<u8> {
-        let mut highest_trump = 0;
-        let mut current = self.leader as usize;
-        while current < player {
-            if self.cards[current].color == Color::Trump
-                && self.cards[current].value > highest_trump
-            {
-                highest_trump = self.cards[current].value;
-            }
-            current += 1;
+        self.as_trick().did_not_have_trump_higher(player)
+    }
+
+    pub fn as_trick(&self) -> Trick {
+        Trick {
+            cards: self.cards.map(Some),
+            leader: self.leader,
         }
-        (self.cards[player].color == Color::Trump && self.cards[player].value < highest_trump)
-            .then(|| highest_trump)
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct Trick {
     pub cards: [Option<Card>; 4],
     pub leader: u8,
@@ -58,8 +83,8 @@ pub struct Trick {
 
 impl fmt::Display for Trick {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        for _ in 0..4 {
-            let player = (self.leader + 1) % 4;
+        for offset in 0..4 {
+            let player = (self.leader + offset) % 4;
             if let Some(card) = self.cards[player as usize] {
                 write!(f, "Player {} played {}\n", player, card)?;
             }
@@ -90,24 +115,31 @@ impl Trick {
     }
 
     pub fn winner(&self) -> Result<u8, EngineError> {
-        if let Some(mut winner_card) = self.cards[self.leader as usize] {
-            let mut winner: u8 = self.leader;
-            let mut color: Color = winner_card.color;
-            for i in 0..4 {
-                if let Some(compared_card) = self.cards[i as usize] {
-                    if compared_card.win_against(&winner_card, &color) {
-                        winner_card = compared_card;
-                        winner = i;
-                        if color == Color::Excuse {
-                            color = winner_card.color;
-                        }
+        if self.cards[self.leader as usize].is_none() {
+            return Err(EngineError::NotBegunHand);
+        }
+        // The excuse never wins a trick, the first other card sets the color to follow
+        let mut best: Option<(u8, Card)> = None;
+        let mut lead_color = Color::Excuse;
+        for offset in 0..4 {
+            let player = (self.leader + offset) % 4;
+            let Some(card) = self.cards[player as usize] else {
+                continue;
+            };
+            if card.color == Color::Excuse {
+                continue;
+            }
+            best = match best {
+                Some((_, best_card)) if !card.win_against(&best_card, &lead_color) => best,
+                _ => {
+                    if best.is_none() {
+                        lead_color = card.color;
                     }
+                    Some((player, card))
                 }
-            }
-            Ok(winner)
-        } else {
-            Err(EngineError::NotBegunHand)
+            };
         }
+        Ok(best.map(|(player, _)| player).unwrap_or(self.leader))
     }
 
     pub fn next_to_play(&self) -> Option<u8> {
@@ -160,6 +192,33 @@ impl Trick {
         }
     }
 
+    // Neither the asked color nor the excuse
+    pub fn did_not_have_color(&self, player: usize) -> bool {
+        match (self.cards[player], self.color()) {
+            (Some(card), Some(color)) => card.color != color && card.color != Color::Excuse,
+            _ => false,
+        }
+    }
+
+    // Discarding another color means having no trump left either
+    pub fn did_not_have_trump(&self, player: usize) -> bool {
+        self.did_not_have_color(player)
+            && self.cards[player].is_some_and(|card| card.color != Color::Trump)
+    }
+
+    // A trump under one played before in the trick, whether trumps were led or
+    // the player trumped, means no higher trump could be played
+    pub fn did_not_have_trump_higher(&self, player: usize) -> Option<u8> {
+        let card = self.cards[player]?;
+        let before = (player as u8 + 4 - self.leader) % 4;
+        let highest_trump = (0..before)
+            .filter_map(|offset| self.cards[((self.leader + offset) % 4) as usize])
+            .filter(|card| card.color == Color::Trump)
+            .map(|card| card.value)
+            .max()?;
+        (card.color == Color::Trump && card.value < highest_trump).then_some(highest_trump)
+    }
+
     pub fn highest_trump(&self) -> u8 {
         let mut result = 0;
         for option_card in self.cards {
@@ -176,3 +235,109 @@ impl Trick {
         card.color == Color::Trump && card.value > self.highest_trump()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn card(color: Color, value: u8) -> Card {
+        Card::new(color, value).unwrap()
+    }
+
+    fn full_trick(leader: u8, cards_from_leader: [Card; 4]) -> Trick {
+        let mut trick = Trick::new(leader);
+        for (offset, card) in cards_from_leader.iter().enumerate() {
+            trick.play_card((leader + offset as u8) % 4, card).unwrap();
+        }
+        trick
+    }
+
+    #[test]
+    fn test_excuse_never_wins() {
+        let trick = full_trick(
+            1,
+            [
+                card(Color::Excuse, 0),
+                card(Color::Heart, 5),
+                card(Color::Heart, 10),
+                card(Color::Spade, 14),
+            ],
+        );
+        // The second card sets the color, the king of another color is a discard
+        assert_eq!(trick.color(), Some(Color::Heart));
+        assert_eq!(trick.winner().unwrap(), 3);
+
+        let mut alone = Trick::new(2);
+        alone.play_card(2, &card(Color::Excuse, 0)).unwrap();
+        assert_eq!(alone.winner().unwrap(), 2);
+        assert!(Trick::new(0).winner().is_err());
+    }
+
+    #[test]
+    fn test_display_follows_the_playing_order() {
+        let trick = full_trick(
+            2,
+            [
+                card(Color::Club, 3),
+                card(Color::Club, 7),
+                card(Color::Trump, 4),
+                card(Color::Club, 1),
+            ],
+        );
+        let text = trick.to_string();
+        let players: Vec<&str> = text.lines().map(|line| &line[..8]).collect();
+        assert_eq!(players, ["Player 2", "Player 3", "Player 0", "Player 1"]);
+    }
+
+    #[test]
+    fn test_excuse_is_exchanged_for_a_low_card() {
+        let played = full_trick(
+            0,
+            [
+                card(Color::Trump, 21),
+                card(Color::Excuse, 0),
+                card(Color::Diamond, 2),
+                card(Color::Diamond, 14),
+            ],
+        )
+        .into_played()
+        .unwrap();
+        assert_eq!(played.winner, 0);
+        assert_eq!(played.points(), 9 + 9 + 1 + 9);
+        // The taker wins the trick but gives the excuse back
+        assert_eq!(played.taker_points(0), 28 - 8);
+        // The taker keeps the excuse in a lost trick
+        assert_eq!(played.taker_points(1), 8);
+        assert_eq!(played.taker_points(2), 0);
+    }
+
+    #[test]
+    fn test_winner_follows_the_led_color_then_trumps() {
+        // A higher card of another color does not win
+        let trick = full_trick(
+            3,
+            [
+                card(Color::Spade, 7),
+                card(Color::Heart, 14),
+                card(Color::Spade, 10),
+                card(Color::Spade, 2),
+            ],
+        );
+        assert_eq!(trick.winner().unwrap(), 1);
+        // The smallest trump beats the led color, the highest trump wins
+        let trick = full_trick(
+            0,
+            [
+                card(Color::Club, 14),
+                card(Color::Trump, 2),
+                card(Color::Club, 1),
+                card(Color::Trump, 9),
+            ],
+        );
+        assert_eq!(trick.winner().unwrap(), 3);
+        let mut started = Trick::new(2);
+        started.play_card(2, &card(Color::Diamond, 3)).unwrap();
+        started.play_card(3, &card(Color::Diamond, 12)).unwrap();
+        assert_eq!(started.winner().unwrap(), 3);
+    }
+}
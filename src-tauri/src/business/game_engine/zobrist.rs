@@ -0,0 +1,171 @@
+use std::sync::OnceLock;
+
+use super::{
+    card::{Card, NUMBER_OF_CARDS},
+    engine_error::EngineError,
+    game_state::GameState,
+    seed::derive_seed,
+};
+
+// Half points go from 0 to 182 for a whole deal
+const MAX_POINTS: usize = 183;
+// Keys are derived from a constant so that hashes are stable between runs
+const KEYS_SEED: u64 = 0x7a72_6f62_7269_7374;
+
+pub struct ZobristKeys {
+    hand: [[u64; NUMBER_OF_CARDS]; 4],
+    trick: [[u64; NUMBER_OF_CARDS]; 4],
+    leader: [u64; 4],
+    taker: [u64; 4],
+    taker_points: [u64; MAX_POINTS],
+}
+
+impl ZobristKeys {
+    pub fn get() -> &'static ZobristKeys {
+        static KEYS: OnceLock<ZobristKeys> = OnceLock::new();
+        KEYS.get_or_init(|| {
+            let mut stream = 0;
+            let mut next = || {
+                stream += 1;
+                derive_seed(KEYS_SEED, stream)
+            };
+            let hand = [0; 4].map(|_| [0; NUMBER_OF_CARDS].map(|_| next()));
+            let trick = [0; 4].map(|_| [0; NUMBER_OF_CARDS].map(|_| next()));
+            let leader = [0; 4].map(|_| next());
+            let taker = [0; 4].map(|_| next());
+            let taker_points = [0; MAX_POINTS].map(|_| next());
+            ZobristKeys {
+                hand,
+                trick,
+                leader,
+                taker,
+                taker_points,
+            }
+        })
+    }
+
+    pub fn hand(&self, player: u8, card: &Card) -> u64 {
+        self.hand_by_index(player, card.index())
+    }
+
+    pub fn hand_by_index(&self, player: u8, card_index: usize) -> u64 {
+        self.hand[player as usize][card_index]
+    }
+
+    pub fn trick(&self, player: u8, card: &Card) -> u64 {
+        self.trick[player as usize][card.index()]
+    }
+
+    pub fn leader(&self, player: u8) -> u64 {
+        self.leader[player as usize]
+    }
+
+    pub fn taker(&self, taker: Option<u8>) -> u64 {
+        taker.map(|taker| self.taker[taker as usize]).unwrap_or(0)
+    }
+
+    pub fn taker_points(&self, points: usize) -> u64 {
+        self.taker_points[points.min(MAX_POINTS - 1)]
+    }
+}
+
+impl GameState {
+    pub fn zobrist_hash(&self) -> u64 {
+        let keys = ZobristKeys::get();
+        let shared_state = &self.shared_state;
+        let mut hash = keys.taker(shared_state.taker)
+            ^ keys.leader(shared_state.player_to_lead())
+            ^ keys.taker_points(shared_state.current_score());
+        for (player, player_state) in self.players_state.iter().enumerate() {
+            for card in &player_state.hand {
+                hash ^= keys.hand(player as u8, card);
+            }
+        }
+        if let Some(trick) = shared_state.current_trick {
+            for (player, card) in trick.cards.iter().enumerate() {
+                if let Some(card) = card {
+                    hash ^= keys.trick(player as u8, card);
+                }
+            }
+        }
+        hash
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HashedGameState {
+    state: GameState,
+    hash: u64,
+}
+
+impl HashedGameState {
+    pub fn new(state: GameState) -> Self {
+        let hash = state.zobrist_hash();
+        Self { state, hash }
+    }
+
+    pub fn state(&self) -> &GameState {
+        &self.state
+    }
+
+    pub fn into_state(self) -> GameState {
+        self.state
+    }
+
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    pub fn play_card(&mut self, player: u8, card: &Card) -> Result<(), EngineError> {
+        let keys = ZobristKeys::get();
+        let shared_state = &self.state.shared_state;
+        let previous_leader = shared_state.player_to_lead();
+        let previous_points = shared_state.current_score();
+        let trick_cards = shared_state.current_or_new_trick().cards;
+        self.state.play_card(player, card)?;
+        self.hash ^= keys.hand(player, card) ^ keys.trick(player, card);
+        if self.state.shared_state.current_trick.is_none() {
+            // The trick is over, its cards leave the table
+            for (seat, played) in trick_cards.iter().enumerate() {
+                if let Some(played) = played {
+                    self.hash ^= keys.trick(seat as u8, played);
+                }
+            }
+            self.hash ^= keys.trick(player, card)
+                ^ keys.leader(previous_leader)
+                ^ keys.leader(self.state.shared_state.player_to_lead())
+                ^ keys.taker_points(previous_points)
+                ^ keys.taker_points(self.state.shared_state.current_score());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{seq::IteratorRandom, SeedableRng};
+
+    use super::*;
+    use crate::business::{game_engine::seed::TarotRng, GameType};
+
+    #[test]
+    fn test_incremental_hash_matches_full_hash() {
+        let mut rng = TarotRng::seed_from_u64(29);
+        let mut state = GameState::random_init_with_rng(&mut rng).unwrap();
+        state
+            .shared_state
+            .bid(0, Some(GameType::GardeSans { chelem: false }))
+            .unwrap();
+        let mut hashed = HashedGameState::new(state);
+        while let Some(player) = hashed.state().shared_state.next_to_play() {
+            let card = **hashed
+                .state()
+                .cards_allowed(player)
+                .iter()
+                .choose(&mut rng)
+                .unwrap();
+            hashed.play_card(player, &card).unwrap();
+            assert_eq!(hashed.hash(), hashed.state().zobrist_hash());
+        }
+    }
+}
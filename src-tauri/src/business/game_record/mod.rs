@@ -0,0 +1,7 @@
+pub mod parser;
+pub mod record;
+pub mod record_error;
+pub mod replay;
+pub mod writer;
+
+pub use record::{GameRecord, RecordedPlay};
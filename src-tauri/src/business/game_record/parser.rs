@@ -0,0 +1,529 @@
+use std::str::FromStr;
+
+use super::{
+    record::{is_tag_name, parse_contract, GameRecord, RecordedPlay},
+    record_error::RecordError,
+};
+use crate::business::Card;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Comment(String),
+    Open,
+    Close,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Section {
+    Hand(usize),
+    Kitty,
+    Auction,
+    Ecart,
+    Handful(u8),
+    Score,
+}
+
+fn syntax_error(line: usize, message: impl Into<String>) -> RecordError {
+    RecordError::Syntax {
+        line,
+        message: message.into(),
+    }
+}
+
+fn parse_tag(line: usize, text: &str) -> Result<(String, String), RecordError> {
+    let inner = text
+        .strip_prefix('[')
+        .and_then(|rest| rest.strip_suffix(']'))
+        .ok_or_else(|| syntax_error(line, "Tags must be enclosed in brackets"))?;
+    let (name, quoted) = inner
+        .split_once(char::is_whitespace)
+        .ok_or_else(|| syntax_error(line, "Tag without value"))?;
+    if !is_tag_name(name) {
+        return Err(syntax_error(line, format!("Invalid tag name {}", name)));
+    }
+    let quoted = quoted.trim();
+    let raw = quoted
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+        .ok_or_else(|| syntax_error(line, "Tag values must be quoted"))?;
+    let mut value = String::new();
+    let mut chars = raw.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                let escaped = chars
+                    .next()
+                    .ok_or_else(|| syntax_error(line, "Dangling escape in tag"))?;
+                value.push(match escaped {
+                    'n' => '\n',
+                    'r' => '\r',
+                    't' => '\t',
+                    'u' => parse_code_point(line, &mut chars)?,
+                    c => c,
+                });
+            }
+            '"' => return Err(syntax_error(line, "Unescaped quote in tag")),
+            c => value.push(c),
+        }
+    }
+    Ok((String::from(name), value))
+}
+
+// The `{hex}` following a `\u` escape
+fn parse_code_point(line: usize, chars: &mut std::str::Chars) -> Result<char, RecordError> {
+    let invalid = || syntax_error(line, "Invalid character escape in tag");
+    if chars.next() != Some('{') {
+        return Err(invalid());
+    }
+    let hex: String = chars.by_ref().take_while(|c| *c != '}').collect();
+    u32::from_str_radix(&hex, 16)
+        .ok()
+        .and_then(char::from_u32)
+        .ok_or_else(invalid)
+}
+
+fn tokenize(lines: &[(usize, &str)]) -> Result<Vec<(usize, Token)>, RecordError> {
+    let mut tokens = vec![];
+    let mut comment: Option<(usize, String)> = None;
+    for (line, text) in lines {
+        let mut chars = text.chars();
+        let mut word = String::new();
+        while let Some(c) = chars.next() {
+            if let Some((_, content)) = &mut comment {
+                match c {
+                    '\\' => content.push(
+                        chars
+                            .next()
+                            .ok_or_else(|| syntax_error(*line, "Dangling escape in comment"))?,
+                    ),
+                    '}' => {
+                        let (start, content) = comment.take().unwrap();
+                        tokens.push((start, Token::Comment(content)));
+                    }
+                    c => content.push(c),
+                }
+                continue;
+            }
+            if c.is_whitespace() || matches!(c, '{' | '(' | ')') {
+                if !word.is_empty() {
+                    tokens.push((*line, Token::Word(std::mem::take(&mut word))));
+                }
+                match c {
+                    '{' => comment = Some((*line, String::new())),
+                    '(' => tokens.push((*line, Token::Open)),
+                    ')' => tokens.push((*line, Token::Close)),
+                    _ => (),
+                }
+            } else if c == '}' {
+                return Err(syntax_error(*line, "Unexpected end of comment"));
+            } else {
+                word.push(c);
+            }
+        }
+        if !word.is_empty() {
+            tokens.push((*line, Token::Word(word)));
+        }
+        if let Some((_, content)) = &mut comment {
+            content.push('\n');
+        }
+    }
+    if let Some((start, _)) = comment {
+        return Err(syntax_error(start, "Unterminated comment"));
+    }
+    Ok(tokens)
+}
+
+fn is_trick_number(word: &str) -> bool {
+    word.strip_suffix('.')
+        .is_some_and(|number| !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()))
+}
+
+fn is_keyword(word: &str) -> bool {
+    word.ends_with(':') || word == "Chelem"
+}
+
+fn parse_card(line: usize, word: &str) -> Result<Card, RecordError> {
+    Card::from_str(word).map_err(|e| syntax_error(line, e.to_string()))
+}
+
+fn parse_plays(
+    tokens: &[(usize, Token)],
+    position: &mut usize,
+    nested: bool,
+) -> Result<Vec<RecordedPlay>, RecordError> {
+    let mut plays: Vec<RecordedPlay> = vec![];
+    while let Some((line, token)) = tokens.get(*position) {
+        match token {
+            Token::Word(word) if is_trick_number(word) => (),
+            Token::Word(word) if is_keyword(word) => {
+                if nested {
+                    return Err(syntax_error(*line, "Unterminated variation"));
+                }
+                return Ok(plays);
+            }
+            Token::Word(word) => plays.push(RecordedPlay::new(parse_card(*line, word)?)),
+            Token::Comment(content) => {
+                let play = plays
+                    .last_mut()
+                    .ok_or_else(|| syntax_error(*line, "Comment before any card"))?;
+                if play.comment.is_some() {
+                    return Err(syntax_error(*line, "A card can only have one comment"));
+                }
+                play.comment = Some(content.clone());
+            }
+            Token::Open => {
+                *position += 1;
+                let variation = parse_plays(tokens, position, true)?;
+                plays
+                    .last_mut()
+                    .ok_or_else(|| syntax_error(*line, "Variation before any card"))?
+                    .variations
+                    .push(variation);
+            }
+            Token::Close => {
+                if nested {
+                    return Ok(plays);
+                }
+                return Err(syntax_error(*line, "Unexpected end of variation"));
+            }
+        }
+        *position += 1;
+    }
+    if nested {
+        return Err(syntax_error(
+            tokens.last().map(|(line, _)| *line).unwrap_or(0),
+            "Unterminated variation",
+        ));
+    }
+    Ok(plays)
+}
+
+fn section_of(line: usize, keyword: &str) -> Result<Section, RecordError> {
+    let name = keyword.trim_end_matches(':');
+    let seat = |prefix: &str| -> Option<u8> {
+        name.strip_prefix(prefix)
+            .and_then(|seat| seat.parse::<u8>().ok())
+            .filter(|seat| *seat < 4)
+    };
+    match name {
+        "Kitty" => Ok(Section::Kitty),
+        "Auction" => Ok(Section::Auction),
+        "Ecart" => Ok(Section::Ecart),
+        "Score" => Ok(Section::Score),
+        _ => {
+            if let Some(seat) = seat("Hand") {
+                Ok(Section::Hand(seat as usize))
+            } else if let Some(seat) = seat("Handful") {
+                Ok(Section::Handful(seat))
+            } else {
+                Err(syntax_error(line, format!("Unknown section {}", keyword)))
+            }
+        }
+    }
+}
+
+fn to_six(line: usize, cards: Vec<Card>, name: &str) -> Result<[Card; 6], RecordError> {
+    cards
+        .try_into()
+        .map_err(|_| syntax_error(line, format!("{} must contain exactly 6 cards", name)))
+}
+
+impl FromStr for GameRecord {
+    type Err = RecordError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let lines: Vec<(usize, &str)> = text
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line))
+            .collect();
+        let mut tags: Vec<(String, String)> = vec![];
+        let mut body_start = lines.len();
+        for (index, (line, content)) in lines.iter().enumerate() {
+            let content = content.trim();
+            if content.is_empty() {
+                continue;
+            }
+            if !content.starts_with('[') {
+                body_start = index;
+                break;
+            }
+            tags.push(parse_tag(*line, content)?);
+        }
+
+        let mut players = [0, 1, 2, 3].map(|seat| format!("Player {}", seat));
+        let mut dealer: Option<u8> = None;
+        let mut seed: Option<u64> = None;
+        let mut rules = String::from("FFT");
+        let mut contract = None;
+        let mut taker: Option<u8> = None;
+        let mut extra_tags = vec![];
+        for (name, value) in tags {
+            let invalid = || RecordError::InvalidTag {
+                name: name.clone(),
+                value: value.clone(),
+            };
+            match name.as_str() {
+                "Player0" | "Player1" | "Player2" | "Player3" => {
+                    players[(name.as_bytes()[6] - b'0') as usize] = value.clone();
+                }
+                "Dealer" => {
+                    dealer = Some(value.parse().ok().filter(|d| *d < 4).ok_or_else(invalid)?)
+                }
+                "Seed" => seed = Some(value.parse().map_err(|_| invalid())?),
+                "Rules" => rules = value.clone(),
+                "Contract" => contract = Some(parse_contract(&value).ok_or_else(invalid)?),
+                "Taker" => taker = Some(value.parse().ok().filter(|t| *t < 4).ok_or_else(invalid)?),
+                _ => extra_tags.push((name.clone(), value.clone())),
+            }
+        }
+        let dealer = dealer.ok_or(RecordError::MissingTag(String::from("Dealer")))?;
+
+        let tokens = tokenize(&lines[body_start..])?;
+        let mut comment: Option<String> = None;
+        let mut hands: [Vec<Card>; 4] = [vec![], vec![], vec![], vec![]];
+        let mut auction = vec![];
+        let mut handfuls: Vec<(u8, Vec<Card>)> = vec![];
+        let mut chelem = false;
+        let mut plays: Vec<RecordedPlay> = vec![];
+        let mut section: Option<Section> = None;
+        let mut kitty: Option<(usize, Vec<Card>)> = None;
+        let mut ecart: Option<(usize, Vec<Card>)> = None;
+        let mut score: Vec<i32> = vec![];
+        let mut score_line = 0;
+        let mut position = 0;
+        while let Some((line, token)) = tokens.get(position) {
+            match token {
+                Token::Comment(content) => {
+                    if comment.is_some() || section.is_some() {
+                        return Err(syntax_error(*line, "Unexpected comment"));
+                    }
+                    comment = Some(content.clone());
+                }
+                Token::Open | Token::Close => {
+                    return Err(syntax_error(*line, "Variations are only allowed in tricks"))
+                }
+                Token::Word(word) if word == "Chelem" => {
+                    chelem = true;
+                    section = None;
+                }
+                Token::Word(word) if is_trick_number(word) => {
+                    if !plays.is_empty() {
+                        return Err(syntax_error(*line, "Tricks must be written in one block"));
+                    }
+                    plays = parse_plays(&tokens, &mut position, false)?;
+                    section = None;
+                    continue;
+                }
+                Token::Word(word) if is_keyword(word) => {
+                    let new_section = section_of(*line, word)?;
+                    match new_section {
+                        Section::Kitty => kitty = Some((*line, vec![])),
+                        Section::Ecart => ecart = Some((*line, vec![])),
+                        Section::Handful(seat) => handfuls.push((seat, vec![])),
+                        Section::Score => score_line = *line,
+                        _ => (),
+                    }
+                    section = Some(new_section);
+                }
+                Token::Word(word) => match section {
+                    None => return Err(syntax_error(*line, format!("Unexpected {}", word))),
+                    Some(Section::Hand(seat)) => hands[seat].push(parse_card(*line, word)?),
+                    Some(Section::Kitty) => {
+                        if let Some((_, cards)) = &mut kitty {
+                            cards.push(parse_card(*line, word)?);
+                        }
+                    }
+                    Some(Section::Ecart) => {
+                        if let Some((_, cards)) = &mut ecart {
+                            cards.push(parse_card(*line, word)?);
+                        }
+                    }
+                    Some(Section::Handful(_)) => {
+                        if let Some((_, cards)) = handfuls.last_mut() {
+                            cards.push(parse_card(*line, word)?);
+                        }
+                    }
+                    Some(Section::Auction) => {
+                        auction.push(if word == "Pass" {
+                            None
+                        } else {
+                            Some(parse_contract(word).ok_or_else(|| {
+                                syntax_error(*line, format!("Unknown bid {}", word))
+                            })?)
+                        })
+                    }
+                    Some(Section::Score) => score.push(
+                        word.parse()
+                            .map_err(|_| syntax_error(*line, format!("Invalid score {}", word)))?,
+                    ),
+                },
+            }
+            position += 1;
+        }
+
+        let (kitty_line, kitty) =
+            kitty.ok_or(RecordError::MissingSection(String::from("Kitty")))?;
+        let ecart = match ecart {
+            Some((ecart_line, ecart)) => Some(to_six(ecart_line, ecart, "Ecart")?),
+            None => None,
+        };
+        let score =
+            if score_line > 0 {
+                Some(score.try_into().map_err(|_| {
+                    syntax_error(score_line, "Score must contain one value per player")
+                })?)
+            } else {
+                None
+            };
+        Ok(GameRecord {
+            players,
+            dealer,
+            seed,
+            rules,
+            contract,
+            taker,
+            extra_tags,
+            comment,
+            hands,
+            kitty: to_six(kitty_line, kitty, "Kitty")?,
+            auction,
+            ecart,
+            handfuls,
+            chelem,
+            plays,
+            score,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{seq::IteratorRandom, Rng, SeedableRng};
+
+    use super::*;
+    use crate::business::{
+        game_engine::{game_state::GameState, seed::TarotRng, GameType},
+        Color,
+    };
+
+    fn random_record<R: Rng + ?Sized>(rng: &mut R) -> GameRecord {
+        let mut state = GameState::random_init_with_rng(rng).unwrap();
+        let mut record = GameRecord::new(
+            state
+                .players_state
+                .clone()
+                .map(|player| player.hand.into_iter().collect()),
+            state.kitty,
+            state.shared_state.dealer,
+        );
+        record.auction = vec![Some(GameType::Garde { chelem: false }), None, None, None];
+        for (index, bid) in record.auction.iter().enumerate() {
+            state.shared_state.bid(record.bidder(index), *bid).unwrap();
+        }
+        state.take_kitty().unwrap();
+        let taker = state.shared_state.taker.unwrap();
+        let ecart: Vec<Card> = state.players_state[taker as usize]
+            .hand
+            .iter()
+            .filter(|card| card.color != Color::Trump && !card.is_oudler() && card.value != 14)
+            .take(6)
+            .cloned()
+            .collect();
+        record.ecart = Some(ecart.try_into().unwrap());
+        state.discard(record.ecart.unwrap()).unwrap();
+        while let Some(player) = state.shared_state.next_to_play() {
+            let allowed: Vec<Card> = state.cards_allowed(player).into_iter().cloned().collect();
+            let card = *allowed.iter().choose(rng).unwrap();
+            let mut play = RecordedPlay::new(card);
+            if record.plays.len() == 4 {
+                play.comment = Some(String::from("Leads {a} \"king\"\nsecond line"));
+                if let Some(other) = allowed.iter().find(|other| **other != card) {
+                    play.variations.push(vec![RecordedPlay::new(*other)]);
+                }
+            }
+            record.plays.push(play);
+            state.play_card(player, &card).unwrap();
+        }
+        record.contract = Some(GameType::Garde { chelem: false });
+        record.taker = Some(taker);
+        record.seed = Some(42);
+        record.comment = Some(String::from("Friendly game"));
+        record.score = Some(state.final_score().unwrap().per_player());
+        record.add_tag("Event", "Club \"night\"").unwrap();
+        record
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut rng = TarotRng::seed_from_u64(27);
+        for _ in 0..20 {
+            let record = random_record(&mut rng);
+            let text = record.to_string();
+            let parsed: GameRecord = text.parse().unwrap();
+            assert_eq!(parsed, record);
+            assert_eq!(parsed.to_string(), text);
+            assert!(parsed.replay().unwrap().shared_state.finished());
+        }
+    }
+
+    #[test]
+    fn test_round_trip_with_control_characters() {
+        let mut record = random_record(&mut TarotRng::seed_from_u64(27));
+        record.players[1] = String::from("Line\nbreak\tand \u{1} \\u{41}");
+        record.add_tag("Note", "first\r\nsecond").unwrap();
+        let text = record.to_string();
+        assert!(text
+            .lines()
+            .any(|line| line == "[Note \"first\\r\\nsecond\"]"));
+        let parsed: GameRecord = text.parse().unwrap();
+        assert_eq!(parsed, record);
+        let invalid = text.replace(r"first\r\nsecond", r"\u{110000}");
+        assert!(invalid.parse::<GameRecord>().is_err());
+    }
+
+    #[test]
+    fn test_rejects_illegal_play() {
+        let mut record = random_record(&mut TarotRng::seed_from_u64(27));
+        record.plays.swap(0, 1);
+        let text = record.to_string();
+        let parsed: GameRecord = text.parse().unwrap();
+        assert!(parsed.replay().is_err());
+    }
+
+    #[test]
+    fn test_rejects_invalid_tags_and_sections() {
+        let mut record = random_record(&mut TarotRng::seed_from_u64(27));
+        assert!(record.add_tag("Dealer", "2").is_err());
+        assert!(record.add_tag("Two words", "value").is_err());
+        assert!(record.add_tag("", "value").is_err());
+        // Names pushed by hand that could not be read back are left out
+        record
+            .extra_tags
+            .push((String::from("Bad] [Name"), String::from("value")));
+        let parsed: GameRecord = record.to_string().parse().unwrap();
+        assert_eq!(
+            parsed.extra_tags,
+            [(String::from("Event"), String::from("Club \"night\""))]
+        );
+
+        let text = record.to_string();
+        assert!(matches!(
+            text.replace("[Event", "[Ev=ent").parse::<GameRecord>(),
+            Err(RecordError::Syntax {
+                line: _,
+                message: _
+            })
+        ));
+        let without_kitty: String = text
+            .lines()
+            .filter(|line| !line.starts_with("Kitty:"))
+            .map(|line| format!("{}\n", line))
+            .collect();
+        assert!(matches!(
+            without_kitty.parse::<GameRecord>(),
+            Err(RecordError::MissingSection(section)) if section == "Kitty"
+        ));
+    }
+}
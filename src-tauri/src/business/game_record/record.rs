@@ -0,0 +1,108 @@
+use serde::{Deserialize, Serialize};
+
+use super::record_error::RecordError;
+use crate::business::{Card, GameType};
+
+// Tags with a field of their own in GameRecord
+const STANDARD_TAGS: [&str; 9] = [
+    "Player0", "Player1", "Player2", "Player3", "Dealer", "Seed", "Rules", "Contract", "Taker",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedPlay {
+    pub card: Card,
+    pub comment: Option<String>,
+    pub variations: Vec<Vec<RecordedPlay>>,
+}
+
+impl RecordedPlay {
+    pub fn new(card: Card) -> Self {
+        Self {
+            card,
+            comment: None,
+            variations: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GameRecord {
+    pub players: [String; 4],
+    pub dealer: u8,
+    pub seed: Option<u64>,
+    pub rules: String,
+    pub contract: Option<GameType>,
+    pub taker: Option<u8>,
+    pub extra_tags: Vec<(String, String)>,
+    pub comment: Option<String>,
+    pub hands: [Vec<Card>; 4],
+    pub kitty: [Card; 6],
+    pub auction: Vec<Option<GameType>>,
+    pub ecart: Option<[Card; 6]>,
+    pub handfuls: Vec<(u8, Vec<Card>)>,
+    pub chelem: bool,
+    pub plays: Vec<RecordedPlay>,
+    pub score: Option<[i32; 4]>,
+}
+
+impl GameRecord {
+    pub fn new(hands: [Vec<Card>; 4], kitty: [Card; 6], dealer: u8) -> Self {
+        Self {
+            players: [0, 1, 2, 3].map(|seat| format!("Player {}", seat)),
+            dealer,
+            seed: None,
+            rules: String::from("FFT"),
+            contract: None,
+            taker: None,
+            extra_tags: vec![],
+            comment: None,
+            hands,
+            kitty,
+            auction: vec![],
+            ecart: None,
+            handfuls: vec![],
+            chelem: false,
+            plays: vec![],
+            score: None,
+        }
+    }
+
+    pub fn bidder(&self, auction_index: usize) -> u8 {
+        ((self.dealer as usize + 1 + auction_index) % 4) as u8
+    }
+
+    pub fn add_tag(&mut self, name: &str, value: &str) -> Result<(), RecordError> {
+        if !is_tag_name(name) || STANDARD_TAGS.contains(&name) {
+            return Err(RecordError::InvalidTag {
+                name: name.to_string(),
+                value: value.to_string(),
+            });
+        }
+        self.extra_tags.push((name.to_string(), value.to_string()));
+        Ok(())
+    }
+}
+
+// Names are written unquoted, so they are kept to letters, digits and '_'
+pub fn is_tag_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+pub fn contract_notation(game_type: &GameType) -> &'static str {
+    match game_type {
+        GameType::Petit { chelem: _ } => "Petit",
+        GameType::Garde { chelem: _ } => "Garde",
+        GameType::GardeSans { chelem: _ } => "GardeSans",
+        GameType::GardeContre { chelem: _ } => "GardeContre",
+    }
+}
+
+pub fn parse_contract(notation: &str) -> Option<GameType> {
+    match notation {
+        "Petit" => Some(GameType::Petit { chelem: false }),
+        "Garde" => Some(GameType::Garde { chelem: false }),
+        "GardeSans" => Some(GameType::GardeSans { chelem: false }),
+        "GardeContre" => Some(GameType::GardeContre { chelem: false }),
+        _ => None,
+    }
+}
@@ -0,0 +1,38 @@
+use crate::business::game_engine::engine_error::EngineError;
+
+#[derive(Debug)]
+pub enum RecordError {
+    Syntax { line: usize, message: String },
+    MissingTag(String),
+    MissingSection(String),
+    InvalidTag { name: String, value: String },
+    Inconsistent(String),
+    Engine(EngineError),
+}
+
+impl From<EngineError> for RecordError {
+    fn from(err: EngineError) -> Self {
+        RecordError::Engine(err)
+    }
+}
+
+impl std::fmt::Display for RecordError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RecordError::Syntax { line, message } => {
+                write!(f, "Syntax error on line {}: {}", line, message)
+            }
+            RecordError::MissingTag(arg) => write!(f, "Missing tag {}", arg),
+            RecordError::MissingSection(arg) => write!(f, "Missing section {}", arg),
+            RecordError::InvalidTag { name, value } => {
+                write!(f, "Invalid value {} for tag {}", value, name)
+            }
+            RecordError::Inconsistent(arg) => {
+                write!(f, "Record does not match the replayed game: {}", arg)
+            }
+            RecordError::Engine(error) => write!(f, "Invalid move in record: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for RecordError {}
@@ -0,0 +1,108 @@
+use std::collections::HashSet;
+
+use super::{
+    record::{GameRecord, RecordedPlay},
+    record_error::RecordError,
+};
+use crate::business::{
+    game_engine::{engine_error::EngineError, game_state::GameState},
+    Card,
+};
+
+fn replay_line(mut state: GameState, plays: &[RecordedPlay]) -> Result<GameState, RecordError> {
+    for play in plays {
+        for variation in &play.variations {
+            replay_line(state.clone(), variation)?;
+        }
+        let player = state
+            .shared_state
+            .next_to_play()
+            .ok_or(EngineError::FinishedHand)?;
+        state.play_card(player, &play.card)?;
+    }
+    Ok(state)
+}
+
+impl GameRecord {
+    pub fn initial_state(&self) -> Result<GameState, RecordError> {
+        let mut all_cards: HashSet<Card> = self.kitty.iter().cloned().collect();
+        for hand in &self.hands {
+            if hand.len() != 18 {
+                return Err(RecordError::Inconsistent(String::from(
+                    "Every hand must contain 18 cards",
+                )));
+            }
+            all_cards.extend(hand.iter().cloned());
+        }
+        if all_cards != Card::all_possibles() {
+            return Err(RecordError::Inconsistent(String::from(
+                "The deal must contain every card exactly once",
+            )));
+        }
+        Ok(GameState::initialize(
+            self.hands.clone().map(|hand| hand.into_iter().collect()),
+            self.kitty,
+            self.dealer,
+        ))
+    }
+
+    // The deal once the auction, the écart and the announcements are done,
+    // before the first card
+    pub fn state_before_play(&self) -> Result<GameState, RecordError> {
+        let mut state = self.initial_state()?;
+        if self.auction.len() > 4 {
+            return Err(RecordError::Inconsistent(String::from(
+                "Every player only bids once",
+            )));
+        }
+        for (index, bid) in self.auction.iter().enumerate() {
+            state.bid(self.bidder(index), *bid)?;
+        }
+        let contract = state
+            .shared_state
+            .game_type
+            .map(|game_type| game_type.with_chelem(false));
+        if self.contract.is_some() && self.contract != contract {
+            return Err(RecordError::Inconsistent(String::from(
+                "Contract does not match the auction",
+            )));
+        }
+        if self.taker.is_some() && self.taker != state.shared_state.taker {
+            return Err(RecordError::Inconsistent(String::from(
+                "Taker does not match the auction",
+            )));
+        }
+        match self.ecart {
+            Some(ecart) => {
+                state.take_kitty()?;
+                state.discard(ecart)?;
+            }
+            None if state.shared_state.kitty_should_be_revealed() && !self.plays.is_empty() => {
+                return Err(RecordError::Inconsistent(String::from(
+                    "The taker has to set aside six cards",
+                )));
+            }
+            None => (),
+        }
+        for (seat, cards) in &self.handfuls {
+            state.declare_handful(*seat, cards.iter().cloned().collect())?;
+        }
+        if self.chelem {
+            let taker = state.shared_state.taker.ok_or(EngineError::NoTaker)?;
+            state.shared_state.declare_chelem(taker)?;
+        }
+        Ok(state)
+    }
+
+    pub fn replay(&self) -> Result<GameState, RecordError> {
+        let state = replay_line(self.state_before_play()?, &self.plays)?;
+        if let Some(score) = self.score {
+            if state.final_score()?.per_player() != score {
+                return Err(RecordError::Inconsistent(String::from(
+                    "Score does not match the replayed game",
+                )));
+            }
+        }
+        Ok(state)
+    }
+}
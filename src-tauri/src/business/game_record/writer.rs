@@ -0,0 +1,129 @@
+use std::fmt;
+
+use super::record::{contract_notation, is_tag_name, GameRecord, RecordedPlay};
+use crate::business::Card;
+
+fn escape(value: &str, special: char) -> String {
+    let mut result = String::new();
+    for c in value.chars() {
+        if c == '\\' || c == special {
+            result.push('\\');
+        }
+        result.push(c);
+    }
+    result
+}
+
+// Tags hold on one line, control characters are written as escapes
+fn escape_tag(value: &str) -> String {
+    let mut result = String::new();
+    for c in value.chars() {
+        match c {
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if c.is_control() => result.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            '\\' | '"' => {
+                result.push('\\');
+                result.push(c);
+            }
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+fn write_tag(f: &mut fmt::Formatter<'_>, name: &str, value: &str) -> fmt::Result {
+    writeln!(f, "[{} \"{}\"]", name, escape_tag(value))
+}
+
+fn write_cards(f: &mut fmt::Formatter<'_>, label: &str, cards: &[Card]) -> fmt::Result {
+    write!(f, "{}", label)?;
+    for card in cards {
+        write!(f, " {}", card.notation())?;
+    }
+    writeln!(f)
+}
+
+fn write_play(f: &mut fmt::Formatter<'_>, play: &RecordedPlay) -> fmt::Result {
+    write!(f, "{}", play.card.notation())?;
+    if let Some(comment) = &play.comment {
+        write!(f, " {{{}}}", escape(comment, '}'))?;
+    }
+    for variation in &play.variations {
+        write!(f, " (")?;
+        for (index, alternative) in variation.iter().enumerate() {
+            if index > 0 {
+                write!(f, " ")?;
+            }
+            write_play(f, alternative)?;
+        }
+        write!(f, ")")?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for GameRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (seat, name) in self.players.iter().enumerate() {
+            write_tag(f, &format!("Player{}", seat), name)?;
+        }
+        write_tag(f, "Dealer", &self.dealer.to_string())?;
+        if let Some(seed) = self.seed {
+            write_tag(f, "Seed", &seed.to_string())?;
+        }
+        write_tag(f, "Rules", &self.rules)?;
+        if let Some(contract) = &self.contract {
+            write_tag(f, "Contract", contract_notation(contract))?;
+        }
+        if let Some(taker) = self.taker {
+            write_tag(f, "Taker", &taker.to_string())?;
+        }
+        // A name that could not be read back would break the whole record
+        for (name, value) in self.extra_tags.iter().filter(|(name, _)| is_tag_name(name)) {
+            write_tag(f, name, value)?;
+        }
+        writeln!(f)?;
+        if let Some(comment) = &self.comment {
+            writeln!(f, "{{{}}}", escape(comment, '}'))?;
+        }
+        for (seat, hand) in self.hands.iter().enumerate() {
+            write_cards(f, &format!("Hand{}:", seat), hand)?;
+        }
+        write_cards(f, "Kitty:", &self.kitty)?;
+        write!(f, "Auction:")?;
+        for bid in &self.auction {
+            write!(
+                f,
+                " {}",
+                bid.as_ref().map(contract_notation).unwrap_or("Pass")
+            )?;
+        }
+        writeln!(f)?;
+        if let Some(ecart) = &self.ecart {
+            write_cards(f, "Ecart:", ecart)?;
+        }
+        for (seat, cards) in &self.handfuls {
+            write_cards(f, &format!("Handful{}:", seat), cards)?;
+        }
+        if self.chelem {
+            writeln!(f, "Chelem")?;
+        }
+        for (trick_index, trick) in self.plays.chunks(4).enumerate() {
+            write!(f, "{}.", trick_index + 1)?;
+            for play in trick {
+                write!(f, " ")?;
+                write_play(f, play)?;
+            }
+            writeln!(f)?;
+        }
+        if let Some(score) = self.score {
+            writeln!(
+                f,
+                "Score: {} {} {} {}",
+                score[0], score[1], score[2], score[3]
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -1,6 +1,9 @@
 pub mod analysis;
 pub mod business_error;
+pub mod companion;
+pub mod encoding;
 pub mod game_engine;
+pub mod game_record;
 pub mod player;
 pub mod tarot;
 
@@ -1,24 +1,49 @@
+use std::collections::HashSet;
+
 use super::{
-    analysis::analysis_error::AnalysisError,
+    analysis::{
+        analysis_error::AnalysisError, announcements::SimulatedAnnouncements,
+        bidding::SimulatedBidding, ecart::EcartOptimiser,
+    },
     game_engine::{card::Card, game_type::GameType, known_game_state::KnownGameState},
 };
 
+// Deals sampled by the default bids
+const BIDDING_SAMPLES: usize = 16;
+// Deals sampled by the default announcements
+const ANNOUNCEMENT_SAMPLES: usize = 16;
+
 pub trait Player {
     fn play_a_card(&mut self, game_state: &KnownGameState) -> Result<Card, AnalysisError>;
 
-    fn bid(&self, _game_state: &KnownGameState) -> Result<Option<GameType>, AnalysisError> {
-        Ok(Some(GameType::GardeSans { chelem: false }))
+    fn bid(&self, game_state: &KnownGameState) -> Result<Option<GameType>, AnalysisError> {
+        SimulatedBidding::new(BIDDING_SAMPLES, self.seed().unwrap_or_default()).bid(game_state)
     }
 
+    fn seed(&self) -> Option<u64> {
+        None
+    }
+
+    // Called before every deal, players keeping state between cards reset it
+    // here, so that their seed replays any deal on its own
+    fn new_deal(&mut self) {}
+
     fn chose_aside(&self, game_state: &KnownGameState) -> Result<[Card; 6], AnalysisError> {
-        let chosen: Vec<Card> = game_state
-            .player_state
-            .hand
-            .iter()
-            .cloned()
-            .take(6)
-            .collect();
-
-        chosen.try_into().map_err(|_| AnalysisError::NoCardToPlay)
+        EcartOptimiser::new(self.seed().unwrap_or_default()).optimise(game_state)
+    }
+
+    // Asked to the taker once the kitty is dealt with, before the first card
+    fn declare_chelem(&self, game_state: &KnownGameState) -> Result<bool, AnalysisError> {
+        SimulatedAnnouncements::new(ANNOUNCEMENT_SAMPLES, self.seed().unwrap_or_default())
+            .declare_chelem(game_state)
+    }
+
+    // Asked to every player before the first card, the trumps to show if any
+    fn declare_handful(
+        &self,
+        game_state: &KnownGameState,
+    ) -> Result<Option<HashSet<Card>>, AnalysisError> {
+        SimulatedAnnouncements::new(ANNOUNCEMENT_SAMPLES, self.seed().unwrap_or_default())
+            .declare_handful(game_state)
     }
 }
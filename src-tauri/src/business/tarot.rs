@@ -1,40 +1,73 @@
 use super::{
     business_error::BusinessError,
     game_engine::{
-        engine_error::EngineError, game_state::GameState, known_game_state::KnownGameState,
+        engine_error::EngineError,
+        game_state::GameState,
+        known_game_state::KnownGameState,
+        seed::{random_seed, rng_from_seed},
     },
+    game_record::{GameRecord, RecordedPlay},
     player::Player,
+    Card,
 };
 
 pub struct Tarot {
     state: GameState,
     players: [Box<dyn Player>; 4],
+    seed: u64,
+    record: GameRecord,
 }
 
 impl Tarot {
     pub fn initialize(players: [Box<dyn Player>; 4]) -> Self {
+        Self::initialize_with_seed(players, random_seed())
+    }
+
+    pub fn initialize_with_seed(mut players: [Box<dyn Player>; 4], seed: u64) -> Self {
+        let state =
+            GameState::random_init_with_rng(&mut rng_from_seed(seed)).expect("Could not init");
+        let mut record = GameRecord::new(
+            state.players_state.clone().map(|player_state| {
+                let mut hand: Vec<Card> = player_state.hand.into_iter().collect();
+                hand.sort_by_key(Card::index);
+                hand
+            }),
+            state.kitty,
+            state.shared_state.dealer,
+        );
+        record.seed = Some(seed);
+        for (seat, player) in players.iter_mut().enumerate() {
+            player.new_deal();
+            if let Some(player_seed) = player.seed() {
+                record
+                    .extra_tags
+                    .push((format!("Seed{}", seat), player_seed.to_string()));
+            }
+        }
         Self {
-            state: GameState::random_init().expect("Could not init"),
+            state,
             players,
+            seed,
+            record,
         }
     }
 
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn record(&self) -> &GameRecord {
+        &self.record
+    }
+
     pub fn bid(&mut self) -> Result<(), BusinessError> {
-        let mut current_player = (self.state.shared_state.dealer + 1) % 4;
-        let mut current_pass_count = 0;
-        while current_pass_count < 4 {
+        for offset in 1..=4 {
+            let current_player = (self.state.shared_state.dealer + offset) % 4;
             let player_bid = self.players[current_player as usize].bid(
                 &KnownGameState::from_omniscient(&self.state, current_player),
             )?;
-            if let Some(game_type) = player_bid {
-                self.state
-                    .shared_state
-                    .bid(current_player, Some(game_type))?;
-                current_pass_count = 0;
-            } else {
-                current_pass_count += 1;
-            }
-            current_player = (current_player + 1) % 4;
+            self.state.bid(current_player, player_bid)?;
+            self.record.auction.push(player_bid);
         }
         let game_type = self
             .state
@@ -46,34 +79,134 @@ impl Tarot {
             .shared_state
             .taker
             .ok_or(BusinessError::EveryonePassed)?;
+        self.record.contract = Some(game_type);
+        self.record.taker = Some(taker);
         if game_type.kitty_should_be_revealed() {
-            let aside = self.players[taker as usize].chose_aside(
-                &KnownGameState::from_omniscient(&self.state, current_player),
-            )?;
-            self.state.players_state[taker as usize].chose_aside(aside)?;
+            self.state.take_kitty()?;
+            let aside = self.players[taker as usize]
+                .chose_aside(&KnownGameState::from_omniscient(&self.state, taker))?;
+            self.state.discard(aside)?;
+            self.record.ecart = Some(aside);
+        }
+        Ok(())
+    }
+
+    // Chelem of the taker first, as it changes who leads, then handfuls in
+    // playing order
+    pub fn announce(&mut self) -> Result<(), BusinessError> {
+        let taker = self
+            .state
+            .shared_state
+            .taker
+            .ok_or(BusinessError::EveryonePassed)?;
+        if self.players[taker as usize]
+            .declare_chelem(&KnownGameState::from_omniscient(&self.state, taker))?
+        {
+            self.state.shared_state.declare_chelem(taker)?;
+            self.record.chelem = true;
+        }
+        let leader = self.state.shared_state.player_to_lead();
+        for offset in 0..4 {
+            let player = (leader + offset) % 4;
+            let handful = self.players[player as usize]
+                .declare_handful(&KnownGameState::from_omniscient(&self.state, player))?;
+            if let Some(cards) = handful {
+                let mut shown: Vec<Card> = cards.iter().copied().collect();
+                shown.sort_by_key(Card::index);
+                self.state.declare_handful(player, cards)?;
+                self.record.handfuls.push((player, shown));
+            }
         }
         Ok(())
     }
 
     pub fn play_a_new_trick(&mut self) -> Result<(), BusinessError> {
-        let new_trick = self.state.shared_state.new_trick();
+        self.state.shared_state.new_trick();
         for _ in 0..4 {
-            if let Some(player_index) = new_trick.next_to_play() {
+            if let Some(player_index) = self.state.shared_state.next_to_play() {
                 let card = self.players[player_index as usize]
                     .play_a_card(&KnownGameState::from_omniscient(&self.state, player_index))?;
                 self.state.play_card(player_index, &card)?;
+                self.record.plays.push(RecordedPlay::new(card));
             } else {
                 return Err(BusinessError::Engine(EngineError::FinishedHand));
             }
         }
-        Ok(self.state.shared_state.finish_trick()?)
+        Ok(())
     }
 
     pub fn play(&mut self) -> Result<usize, BusinessError> {
         self.bid()?;
+        self.announce()?;
         for _ in 0..18 {
             self.play_a_new_trick()?;
         }
+        self.record.score = Some(self.state.final_score()?.per_player());
         Ok(self.state.shared_state.current_score())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::business::{
+        analysis::{analysis_error::AnalysisError, players::random::Random},
+        GameType,
+    };
+
+    fn play_seeded_game(seed: u64) -> GameRecord {
+        let players: [Box<dyn Player>; 4] =
+            [0, 1, 2, 3].map(|seat| Box::new(Random::new(seed + seat)) as Box<dyn Player>);
+        let mut tarot = Tarot::initialize_with_seed(players, seed);
+        tarot.play().unwrap();
+        tarot.record().clone()
+    }
+
+    #[test]
+    fn test_seeded_games_are_reproducible() {
+        // Somebody takes in these deals, so that they are played to the end
+        let record = play_seeded_game(7);
+        assert_eq!(record, play_seeded_game(7));
+        assert_ne!(record.plays, play_seeded_game(9).plays);
+        let replayed: GameRecord = record.to_string().parse().unwrap();
+        assert!(replayed.replay().is_ok());
+    }
+
+    // Bids what it is given, then plays its lowest legal card
+    struct Scripted(Option<GameType>);
+
+    impl Player for Scripted {
+        fn play_a_card(&mut self, known: &KnownGameState) -> Result<Card, AnalysisError> {
+            Ok(known
+                .player_state
+                .sorted_cards_allowed(&known.shared_state.current_or_new_trick())[0])
+        }
+
+        fn bid(&self, _known: &KnownGameState) -> Result<Option<GameType>, AnalysisError> {
+            Ok(self.0)
+        }
+    }
+
+    #[test]
+    fn test_auction_is_a_single_round() {
+        let bids = [
+            Some(GameType::Petit { chelem: false }),
+            None,
+            Some(GameType::Garde { chelem: false }),
+            None,
+        ];
+        let players = bids.map(|bid| Box::new(Scripted(bid)) as Box<dyn Player>);
+        let mut tarot = Tarot::initialize_with_seed(players, 27);
+        let dealt = tarot.state.kitty;
+        tarot.bid().unwrap();
+        assert_eq!(tarot.record().auction.len(), 4);
+        assert_eq!(tarot.state.shared_state.taker, Some(2));
+        // The taker took the kitty and set the écart aside
+        assert_eq!(tarot.state.players_state[2].hand.len(), 18);
+        assert_eq!(tarot.state.revealed_kitty, Some(dealt));
+
+        let players = [None; 4].map(|bid| Box::new(Scripted(bid)) as Box<dyn Player>);
+        let mut tarot = Tarot::initialize_with_seed(players, 27);
+        assert!(matches!(tarot.bid(), Err(BusinessError::EveryonePassed)));
+    }
+}
@@ -3,6 +3,8 @@ pub mod integration;
 pub mod middlewares;
 pub mod presentation;
 
+use presentation::tauri::companion::{self, CompanionState};
+
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 #[tauri::command]
 fn greet(name: &str) -> String {
@@ -13,7 +15,16 @@ fn greet(name: &str) -> String {
 pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
-        .invoke_handler(tauri::generate_handler![greet])
+        .manage(CompanionState::default())
+        .invoke_handler(tauri::generate_handler![
+            greet,
+            companion::companion_start,
+            companion::companion_observe,
+            companion::companion_undo,
+            companion::companion_suggest,
+            companion::companion_announcements,
+            companion::companion_score
+        ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
@@ -1,6 +1,17 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+use clap::Parser;
+use frenchtarot_lib::presentation::cli::{cli_handler, CliArgs};
+
 fn main() {
+    // Any argument runs the command line instead of the app
+    if std::env::args_os().len() > 1 {
+        if let Err(error) = cli_handler(CliArgs::parse()) {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+        return;
+    }
     frenchtarot_lib::run()
 }
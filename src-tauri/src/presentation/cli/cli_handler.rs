@@ -1,10 +1,181 @@
-use crate::presentation::presentation_error::PresentationError;
-use clap::{command, Parser};
+use std::fs::{self, File};
+use std::io::{stdin, stdout, BufReader, BufWriter};
+use std::path::PathBuf;
+
+use crate::business::{
+    analysis::{
+        network::{
+            samples::{read_samples, samples_from_deal, write_samples},
+            train, Network, Optimizer, TrainingParameters,
+        },
+        players::expert::Expert,
+    },
+    business_error::BusinessError,
+    companion::{search_advisor, Companion},
+    game_engine::seed::{derive_seed, random_seed},
+    tarot::Tarot,
+    Player,
+};
+use crate::presentation::{
+    cli::companion::{parse_cards, run_companion},
+    presentation_error::PresentationError,
+};
+use clap::{Parser, Subcommand};
 
 #[derive(Parser, Debug)]
 #[command(name = "tarot")]
-pub struct CliArgs {}
+pub struct CliArgs {
+    #[command(subcommand)]
+    pub command: CliCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CliCommand {
+    #[command(about = "Follow a deal played with real cards and suggest what to play")]
+    Companion {
+        #[arg(long, help = "Your seat, from 0 to 3")]
+        seat: u8,
+        #[arg(long, help = "Seat of the dealer, the player after them bids first")]
+        dealer: u8,
+        #[arg(long, default_value_t = 2000, help = "MCTS iterations per card")]
+        iterations: usize,
+        #[arg(long, default_value_t = 32, help = "Deals simulated per bid")]
+        bidding_samples: usize,
+        #[arg(long)]
+        seed: Option<u64>,
+        #[arg(long, help = "Trained network guiding and evaluating the search")]
+        network: Option<PathBuf>,
+        #[arg(required = true, help = "Your eighteen cards, like T21 EX KS 10H")]
+        hand: Vec<String>,
+    },
+    #[command(
+        about = "Play deals between experts and write the positions met as training samples"
+    )]
+    SelfPlay {
+        #[arg(long, default_value_t = 100)]
+        deals: usize,
+        #[arg(long, help = "File the samples are added to, one per line")]
+        output: PathBuf,
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+    #[command(about = "Train a value and policy network on sample files")]
+    Train {
+        #[arg(long, help = "Where the trained network is written")]
+        output: PathBuf,
+        #[arg(long, help = "Network to start from, a new one otherwise")]
+        network: Option<PathBuf>,
+        #[arg(long, value_delimiter = ',', default_value = "256,64")]
+        hidden: Vec<usize>,
+        #[arg(long, default_value_t = 10)]
+        epochs: usize,
+        #[arg(long, default_value_t = 64)]
+        batch_size: usize,
+        #[arg(long, default_value_t = 0.001)]
+        learning_rate: f32,
+        #[arg(long, help = "Plain SGD with momentum instead of Adam")]
+        sgd: bool,
+        #[arg(long)]
+        seed: Option<u64>,
+        #[arg(required = true, help = "Sample files written by self-play")]
+        data: Vec<PathBuf>,
+    },
+}
 
-fn cli_handler(_args: CliArgs) -> Result<(), PresentationError> {
-    Ok(())
+pub fn cli_handler(args: CliArgs) -> Result<(), PresentationError> {
+    match args.command {
+        CliCommand::Companion {
+            seat,
+            dealer,
+            iterations,
+            bidding_samples,
+            seed,
+            network,
+            hand,
+        } => {
+            let hand = parse_cards(hand.iter().map(String::as_str))?;
+            let seed = seed.unwrap_or_else(random_seed);
+            println!("Seed {}", seed);
+            let network = match network {
+                Some(path) => Some(
+                    Network::from_json(&fs::read_to_string(path)?).map_err(BusinessError::from)?,
+                ),
+                None => None,
+            };
+            let advisor = search_advisor(iterations, bidding_samples, seed, network);
+            let mut companion =
+                Companion::new(hand.into_iter().collect(), seat, dealer, Box::new(advisor))?;
+            run_companion(&mut companion, stdin().lock(), &mut stdout())
+        }
+        CliCommand::SelfPlay {
+            deals,
+            output,
+            seed,
+        } => {
+            let seed = seed.unwrap_or_else(random_seed);
+            let mut output =
+                BufWriter::new(File::options().create(true).append(true).open(output)?);
+            let mut written = 0;
+            for deal in 0..deals {
+                let players: [Box<dyn Player>; 4] =
+                    [0, 1, 2, 3].map(|_| Box::new(Expert::new()) as Box<dyn Player>);
+                let mut tarot =
+                    Tarot::initialize_with_seed(players, derive_seed(seed, deal as u64));
+                match tarot.play() {
+                    Err(BusinessError::EveryonePassed) => continue,
+                    result => result?,
+                };
+                let record = tarot.record();
+                let plays: Vec<_> = record.plays.iter().map(|play| play.card).collect();
+                let samples = samples_from_deal(
+                    &record.state_before_play().map_err(BusinessError::from)?,
+                    &plays,
+                )
+                .map_err(BusinessError::from)?;
+                write_samples(&mut output, &samples).map_err(BusinessError::from)?;
+                written += samples.len();
+            }
+            println!("{} samples written with seed {}", written, seed);
+            Ok(())
+        }
+        CliCommand::Train {
+            output,
+            network,
+            hidden,
+            epochs,
+            batch_size,
+            learning_rate,
+            sgd,
+            seed,
+            data,
+        } => {
+            let seed = seed.unwrap_or_else(random_seed);
+            let mut samples = vec![];
+            for path in data {
+                let file = BufReader::new(File::open(path)?);
+                samples.extend(read_samples(file).map_err(BusinessError::from)?);
+            }
+            let mut network = match network {
+                Some(path) => {
+                    Network::from_json(&fs::read_to_string(path)?).map_err(BusinessError::from)?
+                }
+                None => Network::new(&hidden, seed),
+            };
+            let optimizer = if sgd {
+                Optimizer::sgd(learning_rate)
+            } else {
+                Optimizer::adam(learning_rate)
+            };
+            let parameters = TrainingParameters::new(epochs, seed)
+                .with_batch_size(batch_size)
+                .with_optimizer(optimizer);
+            println!("Seed {}", seed);
+            let losses = train(&mut network, &samples, &parameters).map_err(BusinessError::from)?;
+            for (epoch, loss) in losses.iter().enumerate() {
+                println!("Epoch {}: loss {:.4}", epoch + 1, loss);
+            }
+            fs::write(output, network.to_json().map_err(BusinessError::from)?)?;
+            Ok(())
+        }
+    }
 }
@@ -0,0 +1,316 @@
+use std::io::{BufRead, Write};
+use std::str::FromStr;
+
+use crate::business::{
+    companion::{Companion, Suggestion},
+    game_engine::observation::Observation,
+    game_record::record::parse_contract,
+    Card,
+};
+use crate::presentation::presentation_error::PresentationError;
+
+const CONTRACTS: [&str; 4] = ["Petit", "Garde", "GardeSans", "GardeContre"];
+
+pub const HELP: &str = "\
+Seats go from 0 to 3, cards are written like T21, EX, KS, 10H or CD.
+  <seat> <card>             a card put down
+  bid <seat> <contract>     pass, petit, garde, gardesans or gardecontre
+  kitty <six cards>         the kitty shown after a petit or a garde
+  aside <six cards>         the écart, when you are the taker
+  trumps <cards>            the trumps the taker shows when setting them aside
+  chelem <seat>             a chelem announced by the taker
+  handful <seat> <cards>    the trumps shown for a handful
+  won <seat>                the winner of the last trick, checked
+  suggest                   what to bid, set aside or play
+  announce                  whether to announce a chelem or a handful
+  status                    trumps and honours still out, known voids
+  undo                      forget the last line entered
+  score                     the score once every card is down
+  quit";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CompanionCommand {
+    Observe(Observation),
+    Suggest,
+    Announce,
+    Status,
+    Undo,
+    Score,
+    Help,
+    Quit,
+}
+
+fn invalid(reason: String) -> PresentationError {
+    PresentationError::InvalidInput(reason)
+}
+
+fn parse_seat(word: Option<&str>) -> Result<u8, PresentationError> {
+    let word = word.ok_or_else(|| invalid(String::from("A seat is missing")))?;
+    match word.parse::<u8>() {
+        Ok(seat) if seat < 4 => Ok(seat),
+        _ => Err(invalid(format!("{} is not a seat", word))),
+    }
+}
+
+pub fn parse_cards<'a>(
+    words: impl Iterator<Item = &'a str>,
+) -> Result<Vec<Card>, PresentationError> {
+    words
+        .map(|word| Card::from_str(word).map_err(|error| invalid(error.to_string())))
+        .collect()
+}
+
+fn parse_six_cards<'a>(
+    words: impl Iterator<Item = &'a str>,
+) -> Result<[Card; 6], PresentationError> {
+    parse_cards(words)?
+        .try_into()
+        .map_err(|_| invalid(String::from("Six cards are expected")))
+}
+
+pub fn parse_command(line: &str) -> Result<CompanionCommand, PresentationError> {
+    let mut words = line.split_whitespace();
+    let Some(first) = words.next() else {
+        return Err(invalid(String::from("Nothing to do")));
+    };
+    let command = match first.to_lowercase().as_str() {
+        "bid" => {
+            let player = parse_seat(words.next())?;
+            let word = words.next().unwrap_or("pass");
+            let bid = if word.eq_ignore_ascii_case("pass") {
+                None
+            } else {
+                let contract = CONTRACTS
+                    .into_iter()
+                    .find(|contract| contract.eq_ignore_ascii_case(word))
+                    .and_then(parse_contract)
+                    .ok_or_else(|| invalid(format!("{} is not a contract", word)))?;
+                Some(contract)
+            };
+            CompanionCommand::Observe(Observation::Bid { player, bid })
+        }
+        "kitty" => {
+            CompanionCommand::Observe(Observation::KittyRevealed(parse_six_cards(words.by_ref())?))
+        }
+        "aside" => CompanionCommand::Observe(Observation::Aside(parse_six_cards(words.by_ref())?)),
+        "trumps" => {
+            CompanionCommand::Observe(Observation::TrumpsAside(parse_cards(words.by_ref())?))
+        }
+        "chelem" => CompanionCommand::Observe(Observation::ChelemDeclared {
+            player: parse_seat(words.next())?,
+        }),
+        "handful" => {
+            let player = parse_seat(words.next())?;
+            let cards = parse_cards(words.by_ref())?;
+            CompanionCommand::Observe(Observation::HandfulShown { player, cards })
+        }
+        "won" => CompanionCommand::Observe(Observation::TrickWon {
+            winner: parse_seat(words.next())?,
+        }),
+        "suggest" => CompanionCommand::Suggest,
+        "announce" => CompanionCommand::Announce,
+        "status" => CompanionCommand::Status,
+        "undo" => CompanionCommand::Undo,
+        "score" => CompanionCommand::Score,
+        "help" => CompanionCommand::Help,
+        "quit" | "exit" => CompanionCommand::Quit,
+        _ => {
+            let player = parse_seat(Some(first))?;
+            let card = words
+                .next()
+                .ok_or_else(|| invalid(String::from("A card is missing")))?;
+            let card = Card::from_str(card).map_err(|error| invalid(error.to_string()))?;
+            CompanionCommand::Observe(Observation::CardPlayed { player, card })
+        }
+    };
+    if let Some(extra) = words.next() {
+        return Err(invalid(format!("Unexpected {}", extra)));
+    }
+    Ok(command)
+}
+
+fn notation(cards: &[Card]) -> String {
+    cards
+        .iter()
+        .map(Card::notation)
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+// What to print back, None once the user quits
+fn execute(
+    companion: &mut Companion,
+    command: CompanionCommand,
+) -> Result<Option<String>, PresentationError> {
+    let text = match command {
+        CompanionCommand::Observe(observation) => {
+            let closes_trick = matches!(observation, Observation::CardPlayed { .. });
+            companion.observe(observation)?;
+            let shared_state = &companion.known().shared_state;
+            if shared_state.finished() {
+                let score = companion.score()?;
+                format!(
+                    "Deal over, the taker made {} points: {:?}",
+                    score.taker_points as f64 / 2.0,
+                    score.per_player()
+                )
+            } else if closes_trick && shared_state.current_trick.is_none() {
+                let winner = shared_state
+                    .played_tricks
+                    .last()
+                    .map(|trick| trick.winner)
+                    .unwrap_or_default();
+                format!("Player {} wins the trick", winner)
+            } else {
+                String::new()
+            }
+        }
+        CompanionCommand::Suggest => match companion.suggest()? {
+            Suggestion::Bid(Some(contract)) => format!("Bid {}", contract),
+            Suggestion::Bid(None) => String::from("Pass"),
+            Suggestion::Aside(aside) => format!("Set aside {}", notation(&aside)),
+            Suggestion::Card(card) => format!("Play {}", card.notation()),
+        },
+        CompanionCommand::Announce => {
+            let announcements = companion.announcements()?;
+            let mut lines = vec![];
+            if announcements.chelem {
+                lines.push(String::from("Announce a chelem"));
+            }
+            match announcements.handful {
+                Some(cards) => lines.push(format!("Show {}", notation(&cards))),
+                None => lines.push(String::from("No handful to show")),
+            }
+            lines.join("\n")
+        }
+        CompanionCommand::Status => {
+            let tracker = companion.tracker()?;
+            let mut lines = vec![
+                format!(
+                    "Trumps out ({}): {}",
+                    tracker.trumps_out.len(),
+                    notation(&tracker.trumps_out)
+                ),
+                format!("Honours out: {}", notation(&tracker.honours_out)),
+                format!(
+                    "Taker's tricks: {} points",
+                    tracker.taker_points as f64 / 2.0
+                ),
+            ];
+            for (player, voids) in tracker.voids.iter().enumerate() {
+                if !voids.is_empty() {
+                    let colors: Vec<String> = voids.iter().map(ToString::to_string).collect();
+                    lines.push(format!("Player {} has no {}", player, colors.join(", ")));
+                }
+            }
+            lines.join("\n")
+        }
+        CompanionCommand::Undo => match companion.undo()? {
+            Some(observation) => format!("Forgot {:?}", observation),
+            None => String::from("Nothing to undo"),
+        },
+        CompanionCommand::Score => {
+            let score = companion.score()?;
+            format!(
+                "Contract {}, marque {}: {:?}",
+                if score.contract_won { "made" } else { "lost" },
+                score.marque,
+                score.per_player()
+            )
+        }
+        CompanionCommand::Help => String::from(HELP),
+        CompanionCommand::Quit => return Ok(None),
+    };
+    Ok(Some(text))
+}
+
+// Reads one line per event until the input ends, mistakes are reported and
+// ignored so that the session goes on
+pub fn run_companion(
+    companion: &mut Companion,
+    input: impl BufRead,
+    output: &mut impl Write,
+) -> Result<(), PresentationError> {
+    writeln!(output, "{}", HELP)?;
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match parse_command(&line).and_then(|command| execute(companion, command)) {
+            Ok(None) => break,
+            Ok(Some(text)) if text.is_empty() => (),
+            Ok(Some(text)) => writeln!(output, "{}", text)?,
+            Err(error) => writeln!(output, "{}", error)?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::business::{
+        analysis::players::random::Random, game_engine::game_state::GameState, GameType, Player,
+    };
+
+    #[test]
+    fn test_commands_are_parsed() {
+        assert_eq!(
+            parse_command("bid 1 GardeSans").unwrap(),
+            CompanionCommand::Observe(Observation::Bid {
+                player: 1,
+                bid: Some(GameType::GardeSans { chelem: false }),
+            })
+        );
+        assert_eq!(
+            parse_command(" 3  t21 ").unwrap(),
+            CompanionCommand::Observe(Observation::CardPlayed {
+                player: 3,
+                card: Card::from_str("T21").unwrap(),
+            })
+        );
+        assert_eq!(parse_command("status").unwrap(), CompanionCommand::Status);
+        for wrong in ["4 T21", "bid 1 grande", "kitty T1 T2", "2 T22", "won 1 2"] {
+            assert!(parse_command(wrong).is_err(), "{}", wrong);
+        }
+    }
+
+    #[test]
+    fn test_session_reports_mistakes_and_goes_on() {
+        let state = GameState::initialize(
+            [0, 1, 2, 3].map(|player| {
+                Card::all_possibles()
+                    .into_iter()
+                    .filter(|card| card.index() % 4 == player && card.index() < 72)
+                    .collect()
+            }),
+            Card::all_possibles()
+                .into_iter()
+                .filter(|card| card.index() >= 72)
+                .collect::<Vec<Card>>()
+                .try_into()
+                .unwrap(),
+            3,
+        );
+        let mut companion = Companion::new(
+            state.players_state[0].hand.clone(),
+            0,
+            3,
+            Box::new(Random::new(47)),
+        )
+        .unwrap();
+        let input = "bid 1 pass\nbid 0 garde\nsuggest\nundo\nsuggest\nquit\nstatus\n";
+        let mut output = vec![];
+        run_companion(&mut companion, input.as_bytes(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("Player tried to play out of turn"));
+        let advice = match Random::new(47).bid(companion.known()).unwrap() {
+            Some(contract) => format!("Bid {}", contract),
+            None => String::from("Pass"),
+        };
+        assert!(output.contains(&advice));
+        assert!(!output.contains("Trumps out"));
+        assert!(companion.known().auction.is_empty());
+    }
+}
@@ -1,2 +1,3 @@
 pub mod cli_handler;
+pub mod companion;
 pub use cli_handler::*;
@@ -1,14 +1,34 @@
 use std::fmt;
 
+use crate::business::business_error::BusinessError;
+
 #[derive(Debug)]
 pub enum PresentationError {
     NotFound(String),
+    InvalidInput(String),
+    Business(BusinessError),
+    Io(String),
+}
+
+impl From<BusinessError> for PresentationError {
+    fn from(err: BusinessError) -> Self {
+        PresentationError::Business(err)
+    }
+}
+
+impl From<std::io::Error> for PresentationError {
+    fn from(err: std::io::Error) -> Self {
+        PresentationError::Io(err.to_string())
+    }
 }
 
 impl fmt::Display for PresentationError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             PresentationError::NotFound(arg) => write!(f, "Entity not found {}", arg),
+            PresentationError::InvalidInput(arg) => write!(f, "Invalid input: {}", arg),
+            PresentationError::Business(error) => write!(f, "{}", error),
+            PresentationError::Io(arg) => write!(f, "Could not read or write: {}", arg),
         }
     }
 }
@@ -0,0 +1,126 @@
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tauri::State;
+
+use crate::business::{
+    business_error::BusinessError,
+    companion::{search_advisor, Announcements, Companion, Consultation, Suggestion, Tracker},
+    game_engine::{observation::Observation, score::Score, seed::random_seed},
+    Card, KnownGameState,
+};
+
+const ITERATIONS: usize = 2000;
+const BIDDING_SAMPLES: usize = 32;
+
+// The deal followed at a real table, once started
+#[derive(Default)]
+pub struct CompanionState(Mutex<Option<Companion>>);
+
+// Sent back to the front end after every change
+#[derive(Serialize)]
+pub struct CompanionView {
+    pub known: KnownGameState,
+    pub tracker: Tracker,
+    pub seed: Option<u64>,
+}
+
+fn with_companion<T>(
+    state: &CompanionState,
+    action: impl FnOnce(&mut Companion) -> Result<T, BusinessError>,
+) -> Result<T, String> {
+    let mut companion = state
+        .0
+        .lock()
+        .map_err(|_| String::from("The companion is unavailable"))?;
+    let companion = companion
+        .as_mut()
+        .ok_or_else(|| String::from("No deal is being followed"))?;
+    action(companion).map_err(|error| error.to_string())
+}
+
+fn view(companion: &Companion) -> Result<CompanionView, BusinessError> {
+    Ok(CompanionView {
+        known: companion.known().clone(),
+        tracker: companion.tracker()?,
+        seed: companion.seed(),
+    })
+}
+
+#[tauri::command]
+pub fn companion_start(
+    state: State<'_, CompanionState>,
+    hand: Vec<String>,
+    seat: u8,
+    dealer: u8,
+    seed: Option<u64>,
+) -> Result<CompanionView, String> {
+    let hand = hand
+        .iter()
+        .map(|card| Card::from_str(card))
+        .collect::<Result<_, _>>()
+        .map_err(|error| error.to_string())?;
+    let seed = seed.unwrap_or_else(random_seed);
+    let advisor = search_advisor(ITERATIONS, BIDDING_SAMPLES, seed, None);
+    let companion =
+        Companion::new(hand, seat, dealer, Box::new(advisor)).map_err(|error| error.to_string())?;
+    let result = view(&companion).map_err(|error| error.to_string())?;
+    *state
+        .0
+        .lock()
+        .map_err(|_| String::from("The companion is unavailable"))? = Some(companion);
+    Ok(result)
+}
+
+#[tauri::command]
+pub fn companion_observe(
+    state: State<'_, CompanionState>,
+    observation: Observation,
+) -> Result<CompanionView, String> {
+    with_companion(&state, |companion| {
+        companion.observe(observation)?;
+        view(companion)
+    })
+}
+
+#[tauri::command]
+pub fn companion_undo(state: State<'_, CompanionState>) -> Result<CompanionView, String> {
+    with_companion(&state, |companion| {
+        companion.undo()?;
+        view(companion)
+    })
+}
+
+// Runs a search with the advisor lent out, so that the companion stays
+// available while it lasts
+fn consult<T>(
+    state: &CompanionState,
+    search: impl FnOnce(&mut Consultation) -> Result<T, BusinessError>,
+) -> Result<T, String> {
+    let mut consultation = with_companion(state, Companion::consult)?;
+    let result = search(&mut consultation);
+    with_companion(state, |companion| {
+        companion.dismiss(consultation);
+        Ok(())
+    })?;
+    result.map_err(|error| error.to_string())
+}
+
+// Searches take a while, async keeps them off the main thread
+#[tauri::command]
+pub async fn companion_suggest(state: State<'_, CompanionState>) -> Result<Suggestion, String> {
+    consult(&state, Consultation::suggest)
+}
+
+#[tauri::command]
+pub async fn companion_announcements(
+    state: State<'_, CompanionState>,
+) -> Result<Announcements, String> {
+    consult(&state, |consultation| consultation.announcements())
+}
+
+#[tauri::command]
+pub fn companion_score(state: State<'_, CompanionState>) -> Result<Score, String> {
+    with_companion(&state, |companion| companion.score())
+}
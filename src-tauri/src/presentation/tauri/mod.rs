@@ -1,2 +1,3 @@
+pub mod companion;
 // pub mod tauri_handler;
 // pub use tauri_handler;